@@ -0,0 +1,277 @@
+//! Definitions shared by the kernel and user programs
+//!
+//! 内核（`os`）和用户库（`user`）都依赖本 crate ，系统调用号、错误码、通过系统调用传递的结构体
+//! 和参数的取值只在这里定义一次，增加系统调用时也只需在这里增加调用号。
+//! 结构体都是 `#[repr(C)]` 的，字段使用固定宽度的整数且没有填充字节，布局不随指针宽度变化，
+//! 32 位的用户程序也可以使用同样的定义
+
+#![no_std]
+
+use core::ops::Sub;
+
+pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_MKDIR: usize = 34;
+pub const SYSCALL_OPEN: usize = 56;
+pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_GETDENTS: usize = 61;
+pub const SYSCALL_READ: usize = 63;
+pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
+pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_SLEEP: usize = 101;
+pub const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
+pub const SYSCALL_SCHED_GETSCHEDULER: usize = 120;
+pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_GET_PRIORITY: usize = 141;
+pub const SYSCALL_GETRLIMIT: usize = 163;
+pub const SYSCALL_SETRLIMIT: usize = 164;
+pub const SYSCALL_UMASK: usize = 166;
+pub const SYSCALL_PRCTL: usize = 167;
+pub const SYSCALL_GETCPU: usize = 168;
+pub const SYSCALL_GET_TIME: usize = 169;
+pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GETPPID: usize = 173;
+pub const SYSCALL_SBRK: usize = 214;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_SPAWN: usize = 400;
+pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_FAULT_INJECT: usize = 500;
+pub const SYSCALL_GET_MAPS: usize = 501;
+pub const SYSCALL_YIELD_TO: usize = 502;
+pub const SYSCALL_PERF_READ: usize = 503;
+pub const SYSCALL_HART_COUNT: usize = 504;
+pub const SYSCALL_MEM_REPORT: usize = 505;
+pub const SYSCALL_SET_TIME_SLICE: usize = 506;
+pub const SYSCALL_GET_TIME_SLICE: usize = 507;
+pub const SYSCALL_GET_APP_NAMES: usize = 508;
+pub const SYSCALL_FIND_APP: usize = 509;
+pub const SYSCALL_PS: usize = 510;
+pub const SYSCALL_FD_STAT: usize = 511;
+pub const SYSCALL_IOSTAT: usize = 512;
+pub const SYSCALL_FS_ROLLBACK: usize = 513;
+pub const SYSCALL_DMESG: usize = 515;
+
+/// 错误码：用户缓冲区不可访问，与 Linux 相同，系统调用返回其相反数
+pub const EFAULT: isize = 14;
+/// 错误码：系统调用不存在
+pub const ENOSYS: isize = 38;
+
+/// 调度类别：普通
+pub const SCHED_NORMAL: usize = 0;
+/// 调度类别：交互，优先于普通类别调度
+pub const SCHED_INTERACTIVE: usize = 1;
+/// 调度类别：空闲，只在没有其他就绪应用时运行
+pub const SCHED_IDLE: usize = 2;
+
+/// `mmap` 的权限位：可读
+pub const PROT_READ: usize = 1 << 0;
+/// `mmap` 的权限位：可写
+pub const PROT_WRITE: usize = 1 << 1;
+/// `mmap` 的权限位：可执行
+pub const PROT_EXEC: usize = 1 << 2;
+/// `mmap` 的标志：私有映射，与 Linux 相同
+pub const MAP_PRIVATE: usize = 0x02;
+/// `mmap` 的标志：匿名映射，不对应任何文件，与 Linux 相同
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+/// `prctl` 的操作：设置当前任务的名字
+pub const PR_SET_NAME: usize = 15;
+/// `prctl` 的操作：获取当前任务的名字
+pub const PR_GET_NAME: usize = 16;
+/// 任务名的最大字节数，不含结尾的 `\0` ，与 Linux 的 `TASK_COMM_LEN - 1` 相同
+pub const TASK_NAME_LEN: usize = 15;
+
+/// time in seconds and microseconds, with the same layout as `struct timeval` on 64-bit Linux
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: u64,
+    pub usec: u64,
+}
+
+/// 资源限制的种类：CPU 时间，单位为秒
+pub const RLIMIT_CPU: usize = 0;
+/// 表示不限制的资源限制
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// resource limit, with the same layout as `struct rlimit` on 64-bit Linux
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct RLimit {
+    /// 软限制
+    pub cur: u64,
+    /// 硬限制
+    pub max: u64,
+}
+
+/// 内核使用的调度器：stride 调度
+pub const SCHEDULER_STRIDE: u64 = 0;
+/// 内核使用的调度器：多级反馈队列
+pub const SCHEDULER_MLFQ: u64 = 1;
+
+/// scheduling information of a task
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TaskInfo {
+    /// `SCHEDULER_STRIDE` 或 `SCHEDULER_MLFQ`
+    pub scheduler: u64,
+    /// 在多级反馈队列中的级别，0 为最高级，stride 调度下总是 0
+    pub mlfq_level: u64,
+    /// 下次被调度时的时间片长度，单位为时钟中断的周期
+    pub quantum: u64,
+    /// 累计占用 CPU 的时间，单位为 `ms`
+    pub time_ms: u64,
+    /// 系统调用的次数
+    pub syscalls: u64,
+    /// 时间片用完而被抢占的次数，不包括主动让出处理器
+    pub preemptions: u64,
+    /// 缺页异常被内核修复（分配或换入页面）后继续运行的次数
+    pub fault_fixups: u64,
+}
+
+/// per-task event counters
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PerfCounters {
+    /// 缺页异常的次数
+    pub page_faults: u64,
+    /// 系统调用的次数
+    pub syscalls: u64,
+    /// 被切换出去的次数
+    pub context_switches: u64,
+    /// 在进出内核时刷新快表的次数
+    pub tlb_flushes: u64,
+    /// 时间片用完而被抢占的次数
+    pub preemptions: u64,
+    /// 被内核修复的缺页异常的次数
+    pub fault_fixups: u64,
+}
+
+impl Sub for PerfCounters {
+    type Output = PerfCounters;
+
+    /// 两次读取之间发生的事件
+    fn sub(self, rhs: Self) -> Self::Output {
+        PerfCounters {
+            page_faults: self.page_faults.saturating_sub(rhs.page_faults),
+            syscalls: self.syscalls.saturating_sub(rhs.syscalls),
+            context_switches: self.context_switches.saturating_sub(rhs.context_switches),
+            tlb_flushes: self.tlb_flushes.saturating_sub(rhs.tlb_flushes),
+            preemptions: self.preemptions.saturating_sub(rhs.preemptions),
+            fault_fixups: self.fault_fixups.saturating_sub(rhs.fault_fixups),
+        }
+    }
+}
+
+/// fragmentation of the kernel's free frames and heap, returned by `mem_report`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemReport {
+    /// 空闲页帧总数
+    pub free_frames: u64,
+    /// 物理页号连续的空闲页帧的最大个数
+    pub largest_free_run: u64,
+    /// 被回收而等待再次分配的页帧数
+    pub recycled_frames: u64,
+    /// 内核堆中空闲的字节数
+    pub heap_free_bytes: u64,
+    /// 内核堆中空闲块的个数
+    pub heap_free_blocks: u64,
+    /// 内核堆中最大的空闲块的字节数
+    pub heap_largest_block: u64,
+}
+
+/// `open` 的标志：只读打开
+pub const O_RDONLY: u32 = 0;
+/// `open` 的标志：只写打开
+pub const O_WRONLY: u32 = 1 << 0;
+/// `open` 的标志：读写打开
+pub const O_RDWR: u32 = 1 << 1;
+/// `open` 的标志：文件不存在时创建
+pub const O_CREAT: u32 = 1 << 9;
+/// `open` 的标志：打开时把文件截断为空
+pub const O_TRUNC: u32 = 1 << 10;
+
+/// 文件类型的掩码
+pub const S_IFMT: u32 = 0o170000;
+/// 文件类型：字符设备
+pub const S_IFCHR: u32 = 0o020000;
+/// 文件类型：目录
+pub const S_IFDIR: u32 = 0o040000;
+/// 文件类型：普通文件
+pub const S_IFREG: u32 = 0o100000;
+
+/// status of a file
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stat {
+    /// 文件所在设备的编号
+    pub dev: u64,
+    /// 索引节点编号
+    pub ino: u64,
+    /// 文件类型（`S_IFDIR`/`S_IFREG`）和权限位
+    pub mode: u32,
+    /// 硬链接数
+    pub nlink: u32,
+    /// 文件大小，单位为字节
+    pub size: u64,
+    /// 保留，总是 0
+    pub pad: [u64; 6],
+}
+
+/// 文件名的最大字节数，不含结尾的 `\0` ，与 easy-fs 的目录项相同
+pub const NAME_MAX: usize = 27;
+
+/// an entry of a directory, returned by `getdents`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Dirent {
+    /// 索引节点编号
+    pub ino: u64,
+    /// 文件类型，`S_IFDIR` 或 `S_IFREG`
+    pub kind: u32,
+    /// 以 `\0` 结尾的文件名
+    pub name: [u8; NAME_MAX + 1],
+}
+
+impl Dirent {
+    /// the file name, without the ending `\0`
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(NAME_MAX + 1);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+
+/// the I/O done through a file descriptor since it was opened
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct IoStats {
+    /// 读操作的次数，包括读到文件末尾的读
+    pub reads: u64,
+    /// 写操作的次数
+    pub writes: u64,
+    /// 读取的字节数
+    pub read_bytes: u64,
+    /// 写入的字节数
+    pub write_bytes: u64,
+}
+
+/// what to do when a signal is delivered
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SignalAction {
+    /// 信号处理函数的地址，0 表示默认处理
+    pub handler: u64,
+    /// 执行处理函数期间屏蔽的信号
+    pub mask: u32,
+    /// `SA_*` 标志
+    pub flags: u32,
+}
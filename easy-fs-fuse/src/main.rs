@@ -0,0 +1,327 @@
+//! Pack the user apps into an easy-fs disk image
+//!
+//! 用法：`easy-fs-fuse [--checksum] <源文件目录> <可执行文件目录> <磁盘镜像> [交换区大小 MiB]`，例如
+//! `easy-fs-fuse ../user/src/bin ../user/target/riscv64gc-unknown-none-elf/release ../os/target/fs.img` 。
+//! 可执行文件目录中每个在源文件目录中仍有同名源文件的应用都被写入根目录，文件名即应用名。
+//!
+//! 给出交换区大小时，磁盘镜像带有 MBR 分区表，依次为文件系统分区和交换区分区。
+//! 给出 `--checksum` 时，文件系统建立在 [`ChecksumDevice`] 上，内核读到损坏的块时立即报错。
+//! 打包之前先在内存中的设备上检查校验和能发现损坏的块
+
+use std::env;
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+use easy_fs::{
+    crc32, sync_all, write_partition_table, BlockDevice, ChecksumDevice, EasyFileSystem, Partition,
+    PartitionEntry, BLOCK_SZ, PARTITION_TYPE_EASY_FS, PARTITION_TYPE_SWAP,
+};
+
+/// 文件系统的大小：16 MiB
+const TOTAL_BLOCKS: u32 = 16 * 2048;
+/// 有分区表时文件系统分区的起始块，与常见的分区工具一样对齐到 1 MiB
+const FS_START_BLOCK: u32 = 2048;
+/// 索引节点位图占 1 个块，最多 4096 个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// ELF 文件开头的魔数
+const ELF_MAGIC: [u8; 4] = [0x7f, 0x45, 0x4c, 0x46];
+/// flat 格式文件开头的魔数，见 `os/src/loader/flat.rs`
+const FLAT_MAGIC: [u8; 8] = *b"rCoreFLT";
+
+/// a disk image file as a block device
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+}
+
+/// an in-memory block device for [`check_checksums`], which can drop writes to
+/// simulate a power loss
+struct MemoryDevice {
+    data: Mutex<Vec<u8>>,
+    /// 还会写入的块数，`None` 时不限制
+    writes_left: Mutex<Option<usize>>,
+}
+
+impl MemoryDevice {
+    fn new(blocks: usize) -> Self {
+        Self {
+            data: Mutex::new(vec![0; blocks * BLOCK_SZ]),
+            writes_left: Mutex::new(None),
+        }
+    }
+
+    /// flip the bits of the first byte of block `block_id`
+    fn corrupt(&self, block_id: usize) {
+        self.data.lock().unwrap()[block_id * BLOCK_SZ] ^= 0xff;
+    }
+}
+
+impl BlockDevice for MemoryDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.data.lock().unwrap()[block_id * BLOCK_SZ..][..BLOCK_SZ]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if let Some(left) = self.writes_left.lock().unwrap().as_mut() {
+            if *left == 0 {
+                return;
+            }
+            *left -= 1;
+        }
+        self.data.lock().unwrap()[block_id * BLOCK_SZ..][..BLOCK_SZ].copy_from_slice(buf);
+    }
+}
+
+/// Check [`ChecksumDevice`] on an in-memory device: the CRC32 test vector, a
+/// corrupted block being found and a write cut short by a power loss not being
+/// taken for corruption.
+fn check_checksums() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    // 跨越多个校验和块
+    const BLOCKS: usize = 300;
+    let memory = Arc::new(MemoryDevice::new(ChecksumDevice::lower_blocks(BLOCKS)));
+    let data_start = ChecksumDevice::lower_blocks(BLOCKS) - BLOCKS;
+    let device = ChecksumDevice::create(memory.clone(), BLOCKS);
+    device.write_block(200, &[0x5a; BLOCK_SZ]);
+    assert!((0..BLOCKS).all(|block_id| device.verify_block(block_id)));
+    memory.corrupt(data_start + 200);
+    assert!(!device.verify_block(200));
+    memory.corrupt(data_start + 200);
+
+    // 一次写入依次写校验和块、数据块、校验和块，在每一步之后断电
+    for writes in 0..=3 {
+        *memory.writes_left.lock().unwrap() = Some(writes);
+        device.write_block(100, &[writes as u8 + 1; BLOCK_SZ]);
+        *memory.writes_left.lock().unwrap() = None;
+        let reopened = ChecksumDevice::open(memory.clone()).unwrap();
+        assert!(
+            (0..BLOCKS).all(|block_id| reopened.verify_block(block_id)),
+            "a write cut short after {} blocks is taken for corruption",
+            writes
+        );
+    }
+    // 修复之后的块仍然能发现损坏
+    memory.corrupt(data_start + 100);
+    assert!(!device.verify_block(100));
+}
+
+/// 是否是内核的加载器能识别的可执行文件
+fn is_executable(path: &Path) -> bool {
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| magic.starts_with(&ELF_MAGIC) || magic == FLAT_MAGIC)
+        .unwrap_or(false)
+}
+
+/// 扫描 `target_dir` 中已经构建好的应用可执行文件，只保留在 `src_dir` 中仍有源文件的应用，
+/// 避免把已经删除的应用留下的旧可执行文件打包进镜像
+fn find_apps(src_dir: &Path, target_dir: &Path) -> Result<Vec<String>> {
+    let sources: Vec<String> = read_dir(src_dir)?
+        .filter_map(|dir_entry| {
+            let path = dir_entry.ok()?.path();
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .collect();
+    let mut apps: Vec<String> = match read_dir(target_dir) {
+        Ok(entries) => entries
+            .filter_map(|dir_entry| {
+                let path = dir_entry.ok()?.path();
+                if !path.is_file() || path.extension().is_some() || !is_executable(&path) {
+                    return None;
+                }
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .filter(|app| sources.contains(app))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    apps.sort();
+    for source in sources.iter().filter(|source| !apps.contains(source)) {
+        eprintln!("warning: user app `{}` has not been built, skipped", source);
+    }
+    Ok(apps)
+}
+
+/// how the image is laid out
+struct ImageOptions {
+    /// 交换区分区的大小，单位为 MiB ，`None` 时没有分区表
+    swap_mib: Option<u32>,
+    /// 是否为文件系统的每个块保存校验和
+    checksum: bool,
+}
+
+impl ImageOptions {
+    /// the number of blocks of the image the file system takes
+    fn fs_blocks(&self) -> u32 {
+        if self.checksum {
+            ChecksumDevice::lower_blocks(TOTAL_BLOCKS as usize) as u32
+        } else {
+            TOTAL_BLOCKS
+        }
+    }
+}
+
+/// the file system on the image: the whole image or its partition, with checksums
+/// if `options.checksum`, which are laid out anew if `create`
+fn fs_device(
+    image: Arc<dyn BlockDevice>,
+    options: &ImageOptions,
+    create: bool,
+) -> Arc<dyn BlockDevice> {
+    let device: Arc<dyn BlockDevice> = match options.swap_mib {
+        Some(_) => Arc::new(Partition::new(
+            image,
+            &PartitionEntry {
+                kind: PARTITION_TYPE_EASY_FS,
+                start_block: FS_START_BLOCK,
+                blocks: options.fs_blocks(),
+            },
+        )),
+        None => image,
+    };
+    if !options.checksum {
+        device
+    } else if create {
+        Arc::new(ChecksumDevice::create(device, TOTAL_BLOCKS as usize))
+    } else {
+        Arc::new(ChecksumDevice::open(device).expect("the image has no checksums"))
+    }
+}
+
+fn pack(src_dir: &Path, target_dir: &Path, image: &Path, options: &ImageOptions) -> Result<()> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(image)?;
+    let fs_blocks = options.fs_blocks();
+    let block_file: Arc<dyn BlockDevice> = match options.swap_mib {
+        Some(swap_mib) => {
+            let swap_blocks = swap_mib * 2048;
+            let swap_start = FS_START_BLOCK + fs_blocks;
+            file.set_len((swap_start + swap_blocks) as u64 * BLOCK_SZ as u64)?;
+            let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(file)));
+            write_partition_table(
+                &block_file,
+                &[
+                    PartitionEntry {
+                        kind: PARTITION_TYPE_EASY_FS,
+                        start_block: FS_START_BLOCK,
+                        blocks: fs_blocks,
+                    },
+                    PartitionEntry {
+                        kind: PARTITION_TYPE_SWAP,
+                        start_block: swap_start,
+                        blocks: swap_blocks,
+                    },
+                ],
+            );
+            block_file
+        }
+        None => {
+            file.set_len(fs_blocks as u64 * BLOCK_SZ as u64)?;
+            Arc::new(BlockFile(Mutex::new(file)))
+        }
+    };
+    let efs = EasyFileSystem::create(
+        fs_device(block_file, options, true),
+        TOTAL_BLOCKS,
+        INODE_BITMAP_BLOCKS,
+    );
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let apps = find_apps(src_dir, target_dir)?;
+    for app in apps.iter() {
+        let mut data = Vec::new();
+        File::open(target_dir.join(app))?.read_to_end(&mut data)?;
+        let inode = root_inode
+            .create(app, 0o755)
+            .unwrap_or_else(|| panic!("can not create `{}` in the image", app));
+        assert_eq!(
+            inode.write_at(0, &data),
+            data.len(),
+            "`{}` does not fit in the image",
+            app
+        );
+    }
+    // 把块缓存中修改过的块写回镜像文件
+    sync_all();
+    // 重新打开镜像文件读回并比较，确认写入镜像的内容与可执行文件一致。
+    // 新打开的文件是另一个块设备，读取不会命中之前的块缓存
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(
+        OpenOptions::new().read(true).write(true).open(image)?,
+    )));
+    let efs = EasyFileSystem::open(fs_device(block_file, options, false))
+        .expect("the image is not an easy-fs");
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    for app in apps.iter() {
+        let mut data = Vec::new();
+        File::open(target_dir.join(app))?.read_to_end(&mut data)?;
+        let inode = root_inode.find(app).unwrap();
+        let mut packed = vec![0u8; data.len()];
+        assert_eq!(inode.read_at(0, &mut packed), data.len());
+        assert!(packed == data, "`{}` is corrupted in the image", app);
+    }
+    println!("packed {} apps into {}", apps.len(), image.display());
+    Ok(())
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "usage: {} [--checksum] <src dir> <target dir> <image> [swap size in MiB]",
+        program
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let checksum = match args.iter().position(|arg| arg == "--checksum") {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    };
+    let swap_mib = match args.len() {
+        4 => None,
+        5 => match args[4].parse::<u32>() {
+            Ok(size) => Some(size),
+            Err(_) => usage(&args[0]),
+        },
+        _ => usage(&args[0]),
+    };
+    if checksum {
+        check_checksums();
+    }
+    let options = ImageOptions { swap_mib, checksum };
+    if let Err(err) = pack(
+        Path::new(&args[1]),
+        Path::new(&args[2]),
+        Path::new(&args[3]),
+        &options,
+    ) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
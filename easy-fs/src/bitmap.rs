@@ -0,0 +1,81 @@
+//! Bitmaps recording which inodes or data blocks are allocated
+
+use alloc::sync::Arc;
+
+use crate::block::modify_block;
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// a block of the bitmap
+type BitmapBlock = [u64; BLOCK_SZ / 8];
+
+/// 每个块中的位数
+const BLOCK_BITS: usize = BLOCK_SZ * 8;
+
+/// a bitmap stored in consecutive blocks
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+    /// 有效的位数，可能少于 `blocks` 个块中的位数
+    bits: usize,
+}
+
+/// the block, the `u64` in the block and the bit in the `u64` of bit `bit`
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    /// a bitmap of `bits` bits in `blocks` blocks from `start_block_id`
+    pub fn new(start_block_id: usize, blocks: usize, bits: usize) -> Self {
+        assert!(bits <= blocks * BLOCK_BITS);
+        Self {
+            start_block_id,
+            blocks,
+            bits,
+        }
+    }
+
+    /// Set the first clear bit and return its index, `None` if every bit is set.
+    pub fn alloc(&self, device: &Arc<dyn BlockDevice>) -> Option<usize> {
+        for block_id in 0..self.blocks {
+            let bit = modify_block(
+                device,
+                self.start_block_id + block_id,
+                0,
+                |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, inner_pos) = bitmap_block
+                        .iter()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)
+                        .map(|(pos, bits64)| (pos, bits64.trailing_ones() as usize))?;
+                    let bit = block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos;
+                    if bit >= self.bits {
+                        return None;
+                    }
+                    bitmap_block[bits64_pos] |= 1 << inner_pos;
+                    Some(bit)
+                },
+            );
+            if bit.is_some() {
+                return bit;
+            }
+        }
+        None
+    }
+
+    /// Clear bit `bit`, which must be set.
+    pub fn dealloc(&self, device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        modify_block(
+            device,
+            self.start_block_id + block_pos,
+            0,
+            |bitmap_block: &mut BitmapBlock| {
+                assert!(bitmap_block[bits64_pos] & (1 << inner_pos) != 0);
+                bitmap_block[bits64_pos] &= !(1 << inner_pos);
+            },
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Typed access to blocks through a block cache
+//!
+//! 最近访问的 [`BLOCK_CACHE_SIZE`] 个块缓存在内存中，按 LRU 替换。读写都在缓存上进行，
+//! 修改只把块标记为脏，在块被替换出缓存或调用 [`sync_all`] 时才写回块设备
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::mem::{align_of, size_of};
+
+use spin::Mutex;
+
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// 缓存的块数
+pub const BLOCK_CACHE_SIZE: usize = 16;
+
+/// a buffer holding a block, aligned for the on-disk structures
+#[repr(C, align(8))]
+struct BlockBuf([u8; BLOCK_SZ]);
+
+/// a cached block of a block device
+struct BlockCache {
+    buf: BlockBuf,
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    /// 读入之后是否被修改过
+    dirty: bool,
+}
+
+impl BlockCache {
+    fn load(block_device: Arc<dyn BlockDevice>, block_id: usize) -> Self {
+        let mut buf = BlockBuf([0; BLOCK_SZ]);
+        block_device.read_block(block_id, &mut buf.0);
+        Self {
+            buf,
+            block_id,
+            block_device,
+            dirty: false,
+        }
+    }
+
+    fn as_ptr<T>(&self, offset: usize) -> *const T {
+        assert!(offset + size_of::<T>() <= BLOCK_SZ);
+        assert_eq!(offset % align_of::<T>(), 0);
+        unsafe { self.buf.0.as_ptr().add(offset) as *const T }
+    }
+
+    fn as_ref<T>(&self, offset: usize) -> &T {
+        unsafe { &*self.as_ptr(offset) }
+    }
+
+    fn as_mut<T>(&mut self, offset: usize) -> &mut T {
+        self.dirty = true;
+        unsafe { &mut *(self.as_ptr::<T>(offset) as *mut T) }
+    }
+
+    /// write the block back if it is dirty
+    fn sync(&mut self) {
+        if self.dirty {
+            self.dirty = false;
+            self.block_device.write_block(self.block_id, &self.buf.0);
+        }
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+/// the identity of a block device, the address of the device object
+fn device_key(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const () as usize
+}
+
+/// the cached blocks of all block devices
+struct BlockCacheManager {
+    /// `(设备, 块号, 缓存)` ，从最久没有被访问的到最近被访问的
+    queue: VecDeque<(usize, usize, Arc<Mutex<BlockCache>>)>,
+}
+
+impl BlockCacheManager {
+    /// the cache of block `block_id` of `block_device`, loading it if it is not cached
+    fn get(
+        &mut self,
+        block_device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+    ) -> Arc<Mutex<BlockCache>> {
+        let key = device_key(block_device);
+        if let Some(pos) = self
+            .queue
+            .iter()
+            .position(|&(device, id, _)| device == key && id == block_id)
+        {
+            let entry = self.queue.remove(pos).unwrap();
+            let cache = entry.2.clone();
+            self.queue.push_back(entry);
+            return cache;
+        }
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // 替换最久没有被访问、并且没有正在被访问的块，被替换的块在 drop 时写回
+            let pos = self
+                .queue
+                .iter()
+                .position(|(_, _, cache)| Arc::strong_count(cache) == 1)
+                .expect("Run out of BlockCache!");
+            self.queue.remove(pos);
+        }
+        let cache = Arc::new(Mutex::new(BlockCache::load(block_device.clone(), block_id)));
+        self.queue.push_back((key, block_id, cache.clone()));
+        cache
+    }
+}
+
+static BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> = Mutex::new(BlockCacheManager {
+    queue: VecDeque::new(),
+});
+
+fn get_block_cache(block_device: &Arc<dyn BlockDevice>, block_id: usize) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER.lock().get(block_device, block_id)
+}
+
+/// call `f` with the `T` at `offset` of block `block_id` of `device`
+pub fn read_block<T, V>(
+    device: &Arc<dyn BlockDevice>,
+    block_id: usize,
+    offset: usize,
+    f: impl FnOnce(&T) -> V,
+) -> V {
+    f(get_block_cache(device, block_id).lock().as_ref(offset))
+}
+
+/// call `f` with the mutable `T` at `offset` of block `block_id` of `device`,
+/// marking the block dirty
+///
+/// 闭包中可以访问其他块，但不能再访问同一个块，否则会死锁
+pub fn modify_block<T, V>(
+    device: &Arc<dyn BlockDevice>,
+    block_id: usize,
+    offset: usize,
+    f: impl FnOnce(&mut T) -> V,
+) -> V {
+    f(get_block_cache(device, block_id).lock().as_mut(offset))
+}
+
+/// Drop the cached blocks of `block_device` without writing them back.
+pub fn discard_cached(block_device: &Arc<dyn BlockDevice>) {
+    let key = device_key(block_device);
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    manager.queue.retain(|(device, _, cache)| {
+        if *device != key {
+            return true;
+        }
+        cache.lock().dirty = false;
+        false
+    });
+}
+
+/// Write all dirty cached blocks back to their devices.
+pub fn sync_all() {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    for (_, _, cache) in manager.queue.iter() {
+        cache.lock().sync();
+    }
+}
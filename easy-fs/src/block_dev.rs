@@ -0,0 +1,9 @@
+//! The interface to block devices
+
+/// a device storing data in blocks of [`crate::BLOCK_SZ`] bytes
+pub trait BlockDevice: Send + Sync {
+    /// read block `block_id` into `buf`
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    /// write `buf` into block `block_id`
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
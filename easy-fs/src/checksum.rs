@@ -0,0 +1,192 @@
+//! Per-block checksums over a block device
+//!
+//! 下层设备的 0 号块是头部，记录魔数和数据块数；之后是校验和区域，依次保存每个数据块的 CRC32 ；
+//! 再之后才是数据块。每次写入都同时更新校验和，每次读取都检查校验和，文件系统的错误或镜像的损坏
+//! 因此在读到坏块时就被发现，而不是在之后解析 ELF 或执行应用时才以莫名其妙的方式出错。
+//! 校验和直接读写下层设备而不经过块缓存，每次读写都要多访问一个校验和块。
+//!
+//! 每个校验和块的最后 4 字节记录正在写入的数据块号加 1 （没有时为 0）。写入时先在校验和块中
+//! 记下这个块，再写数据，最后写入新的校验和并清除记录；中途断电时，被记下的块的数据和校验和
+//! 可能不一致，读取时不算作损坏，而是按读到的数据修复校验和
+
+use alloc::sync::Arc;
+
+use spin::Mutex;
+
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// 头部的魔数，用于识别带校验和的设备
+const CHECKSUM_MAGIC: u32 = 0x3b80_c5c6;
+/// 一个校验和块中的校验和个数，最后 4 字节留给正在写入的块号
+const CHECKSUMS_PER_BLOCK: usize = BLOCK_SZ / 4 - 1;
+/// 校验和块中正在写入的块号的偏移
+const PENDING_OFFSET: usize = CHECKSUMS_PER_BLOCK * 4;
+
+/// CRC32 (IEEE 802.3) 的查找表
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 (IEEE 802.3) of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// a block device whose blocks are checked against the CRC32s kept on `lower`
+pub struct ChecksumDevice {
+    lower: Arc<dyn BlockDevice>,
+    /// 数据块数
+    blocks: usize,
+    /// 第一个数据块在下层设备上的块号
+    data_start: usize,
+    /// 读写数据块和它的校验和之间不能被其他读写打断
+    lock: Mutex<()>,
+}
+
+impl ChecksumDevice {
+    fn new(lower: Arc<dyn BlockDevice>, blocks: usize) -> Self {
+        Self {
+            lower,
+            blocks,
+            data_start: 1 + blocks.div_ceil(CHECKSUMS_PER_BLOCK),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// the number of blocks on the lower device a checksummed device of `blocks`
+    /// data blocks takes
+    pub fn lower_blocks(blocks: usize) -> usize {
+        1 + blocks.div_ceil(CHECKSUMS_PER_BLOCK) + blocks
+    }
+
+    /// Lay out a checksummed device of `blocks` data blocks on the first
+    /// [`ChecksumDevice::lower_blocks`] blocks of `lower`.
+    ///
+    /// 数据块都被清零，校验和区域相应地填满全 0 块的校验和，没有正在写入的块
+    pub fn create(lower: Arc<dyn BlockDevice>, blocks: usize) -> Self {
+        let device = Self::new(lower, blocks);
+        let mut header = [0u8; BLOCK_SZ];
+        header[0..4].copy_from_slice(&CHECKSUM_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&(blocks as u32).to_le_bytes());
+        device.lower.write_block(0, &header);
+        let zero = [0u8; BLOCK_SZ];
+        let mut zero_checksums = crc32(&zero).to_le_bytes().repeat(CHECKSUMS_PER_BLOCK);
+        zero_checksums.extend_from_slice(&0u32.to_le_bytes());
+        for block_id in 1..device.data_start {
+            device.lower.write_block(block_id, &zero_checksums);
+        }
+        for block_id in device.data_start..device.data_start + blocks {
+            device.lower.write_block(block_id, &zero);
+        }
+        device
+    }
+
+    /// Open the checksummed device on `lower`, `None` if it has no checksums.
+    pub fn open(lower: Arc<dyn BlockDevice>) -> Option<Self> {
+        let mut header = [0u8; BLOCK_SZ];
+        lower.read_block(0, &mut header);
+        if read_u32(&header, 0) != CHECKSUM_MAGIC {
+            return None;
+        }
+        let blocks = read_u32(&header, 4);
+        Some(Self::new(lower, blocks as usize))
+    }
+
+    /// the number of data blocks
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// the block on `lower` holding the checksum of data block `block_id`, and its
+    /// offset in the block
+    fn checksum_pos(&self, block_id: usize) -> (usize, usize) {
+        assert!(
+            block_id < self.blocks,
+            "block {} is out of the checksummed device of {} blocks",
+            block_id,
+            self.blocks
+        );
+        (
+            1 + block_id / CHECKSUMS_PER_BLOCK,
+            block_id % CHECKSUMS_PER_BLOCK * 4,
+        )
+    }
+
+    /// Read data block `block_id` into `buf` and check it, `Err((actual, expected))`
+    /// if the checksums do not match.
+    ///
+    /// 正在写入时中断的块不算作损坏，它的校验和按读到的数据更新
+    fn read_checked(&self, block_id: usize, buf: &mut [u8]) -> Result<(), (u32, u32)> {
+        let (checksum_block, offset) = self.checksum_pos(block_id);
+        let mut checksums = [0u8; BLOCK_SZ];
+        let _guard = self.lock.lock();
+        self.lower.read_block(self.data_start + block_id, buf);
+        self.lower.read_block(checksum_block, &mut checksums);
+        let expected = read_u32(&checksums, offset);
+        let actual = crc32(buf);
+        if actual == expected {
+            return Ok(());
+        }
+        if read_u32(&checksums, PENDING_OFFSET) as usize != block_id + 1 {
+            return Err((actual, expected));
+        }
+        checksums[offset..offset + 4].copy_from_slice(&actual.to_le_bytes());
+        checksums[PENDING_OFFSET..].copy_from_slice(&0u32.to_le_bytes());
+        self.lower.write_block(checksum_block, &checksums);
+        Ok(())
+    }
+
+    /// Whether data block `block_id` matches its checksum, without panicking.
+    pub fn verify_block(&self, block_id: usize) -> bool {
+        let mut buf = [0u8; BLOCK_SZ];
+        self.read_checked(block_id, &mut buf).is_ok()
+    }
+}
+
+fn read_u32(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap())
+}
+
+impl BlockDevice for ChecksumDevice {
+    /// 校验和不符时 panic
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        if let Err((actual, expected)) = self.read_checked(block_id, buf) {
+            panic!(
+                "checksum mismatch on block {}: {:#010x}, expected {:#010x}",
+                block_id, actual, expected
+            );
+        }
+    }
+
+    /// 校验和块先记下正在写入的块再写数据，保证中途断电时能区分出这个块
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let (checksum_block, offset) = self.checksum_pos(block_id);
+        let mut checksums = [0u8; BLOCK_SZ];
+        let _guard = self.lock.lock();
+        self.lower.read_block(checksum_block, &mut checksums);
+        checksums[PENDING_OFFSET..].copy_from_slice(&(block_id as u32 + 1).to_le_bytes());
+        self.lower.write_block(checksum_block, &checksums);
+        self.lower.write_block(self.data_start + block_id, buf);
+        checksums[offset..offset + 4].copy_from_slice(&crc32(buf).to_le_bytes());
+        checksums[PENDING_OFFSET..].copy_from_slice(&0u32.to_le_bytes());
+        self.lower.write_block(checksum_block, &checksums);
+    }
+}
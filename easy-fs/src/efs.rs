@@ -0,0 +1,166 @@
+//! Allocation of inodes and data blocks
+
+use alloc::sync::Arc;
+use core::mem::size_of;
+
+use spin::Mutex;
+
+use crate::bitmap::Bitmap;
+use crate::block::{modify_block, read_block};
+use crate::layout::{DiskInode, DiskInodeType, SuperBlock};
+use crate::vfs::Inode;
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// 一个块中的索引节点个数
+const INODES_PER_BLOCK: usize = BLOCK_SZ / size_of::<DiskInode>();
+
+/// Zero block `block_id` of `block_device`.
+///
+/// 经过块缓存写入，避免缓存中留下旧的内容
+fn clear_block(block_device: &Arc<dyn BlockDevice>, block_id: usize) {
+    modify_block(block_device, block_id, 0, |block: &mut [u8; BLOCK_SZ]| {
+        block.fill(0)
+    });
+}
+
+/// an easy-fs on a block device
+pub struct EasyFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    inode_bitmap: Bitmap,
+    data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+}
+
+impl EasyFileSystem {
+    /// Create an empty file system on the first `total_blocks` blocks of `block_device`,
+    /// with `inode_bitmap_blocks` blocks of inode bitmap.
+    ///
+    /// 索引节点区域的大小由索引节点位图决定，其余的块由数据块位图和数据块区域按 1:4096 划分
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        let inode_num = inode_bitmap_blocks as usize * BLOCK_SZ * 8;
+        let inode_area_blocks = inode_num.div_ceil(INODES_PER_BLOCK) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let data_bitmap_blocks = data_total_blocks.div_ceil(BLOCK_SZ as u32 * 8 + 1);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let efs = Self::new(
+            block_device.clone(),
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        );
+        for block_id in 0..total_blocks as usize {
+            clear_block(&block_device, block_id);
+        }
+        modify_block(&block_device, 0, 0, |super_block: &mut SuperBlock| {
+            super_block.initialize(
+                total_blocks,
+                inode_bitmap_blocks,
+                inode_area_blocks,
+                data_bitmap_blocks,
+                data_area_blocks,
+            );
+        });
+        // 0 号索引节点是根目录
+        assert_eq!(efs.alloc_inode(), Some(0));
+        let (root_block, root_offset) = efs.get_disk_inode_pos(0);
+        modify_block(
+            &block_device,
+            root_block,
+            root_offset,
+            |disk_inode: &mut DiskInode| disk_inode.initialize(DiskInodeType::Directory, 0o755),
+        );
+        Arc::new(Mutex::new(efs))
+    }
+
+    /// Open the file system on `block_device`, `None` if it does not hold an easy-fs.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        let efs = read_block(&block_device, 0, 0, |super_block: &SuperBlock| {
+            super_block.is_valid().then(|| {
+                Self::new(
+                    block_device.clone(),
+                    super_block.inode_bitmap_blocks,
+                    super_block.inode_area_blocks,
+                    super_block.data_bitmap_blocks,
+                    super_block.data_area_blocks,
+                )
+            })
+        })?;
+        Some(Arc::new(Mutex::new(efs)))
+    }
+
+    fn new(
+        block_device: Arc<dyn BlockDevice>,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) -> Self {
+        let inode_area_start_block = 1 + inode_bitmap_blocks;
+        let data_bitmap_start_block = inode_area_start_block + inode_area_blocks;
+        Self {
+            block_device,
+            inode_bitmap: Bitmap::new(
+                1,
+                inode_bitmap_blocks as usize,
+                inode_area_blocks as usize * INODES_PER_BLOCK,
+            ),
+            data_bitmap: Bitmap::new(
+                data_bitmap_start_block as usize,
+                data_bitmap_blocks as usize,
+                data_area_blocks as usize,
+            ),
+            inode_area_start_block,
+            data_area_start_block: data_bitmap_start_block + data_bitmap_blocks,
+        }
+    }
+
+    /// the root directory
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = efs.lock().block_device.clone();
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        Inode::new(0, block_id, block_offset, efs.clone(), block_device)
+    }
+
+    /// the block and the offset in it of inode `inode_id`
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (usize, usize) {
+        let inode_id = inode_id as usize;
+        (
+            self.inode_area_start_block as usize + inode_id / INODES_PER_BLOCK,
+            inode_id % INODES_PER_BLOCK * size_of::<DiskInode>(),
+        )
+    }
+
+    /// Allocate an inode, `None` if all are in use.
+    pub fn alloc_inode(&self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|inode_id| inode_id as u32)
+    }
+
+    pub fn dealloc_inode(&self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
+
+    /// Allocate a zeroed data block and return its block id, `None` if the disk is full.
+    pub fn alloc_data(&self) -> Option<u32> {
+        let block_id = self.data_bitmap.alloc(&self.block_device)? as u32;
+        Some(block_id + self.data_area_start_block)
+    }
+
+    /// Free data block `block_id`, clearing its content.
+    pub fn dealloc_data(&self, block_id: u32) {
+        clear_block(&self.block_device, block_id as usize);
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        );
+    }
+}
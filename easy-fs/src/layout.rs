@@ -0,0 +1,410 @@
+//! On-disk data structures
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::block::{modify_block, read_block};
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// 超级块中的魔数，用于识别 easy-fs 文件系统
+const EFS_MAGIC: u32 = 0x3b80_0003;
+/// 索引节点中直接索引的数据块个数，使 [`DiskInode`] 恰好占 128 字节
+const INODE_DIRECT_COUNT: usize = 27;
+/// 文件名的最大长度，目录项中还要留出结尾的 `\0`
+const NAME_LENGTH_LIMIT: usize = 27;
+/// 一个索引块中的数据块编号个数
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+/// 直接索引和一级间接索引能覆盖的数据块个数
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// 文件的最大字节数
+pub const MAX_FILE_SIZE: u32 = (INDIRECT2_BOUND * BLOCK_SZ) as u32;
+
+/// the first block of the file system, recording the sizes of the other areas
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    /// whether the block is the super block of an easy-fs
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+/// a block of data block ids
+type IndirectBlock = [u32; BLOCK_SZ / 4];
+/// a block of file content
+type DataBlock = [u8; BLOCK_SZ];
+
+/// an inode on the disk
+///
+/// 前 27 个数据块直接索引；之后的 128 个数据块由一级索引块 `indirect1` 索引；
+/// 再之后的数据块由二级索引块 `indirect2` 索引的一级索引块索引
+#[repr(C)]
+pub struct DiskInode {
+    /// 文件的字节数，目录的大小为其中目录项的总字节数
+    pub size: u32,
+    /// 硬链接数，即指向它的目录项个数，目录还要算上自己的 `.`
+    pub nlink: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    type_: DiskInodeType,
+    /// 权限位，即 Unix 文件模式中文件类型之外的部分
+    pub mode: u16,
+}
+
+// 一个块正好放下 4 个 inode ，改变字段时要保持大小不变，否则已有的镜像无法使用
+const _: () = assert!(core::mem::size_of::<DiskInode>() == 128);
+
+impl DiskInode {
+    /// an empty file or directory with permission bits `mode`, whose indirect blocks
+    /// are not allocated yet
+    pub fn initialize(&mut self, type_: DiskInodeType, mode: u16) {
+        self.size = 0;
+        self.nlink = match type_ {
+            DiskInodeType::File => 1,
+            DiskInodeType::Directory => 2,
+        };
+        self.direct.fill(0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_;
+        self.mode = mode;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+
+    /// the number of data blocks holding the content
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+
+    fn _data_blocks(size: u32) -> u32 {
+        size.div_ceil(BLOCK_SZ as u32)
+    }
+
+    /// the number of blocks a file of `size` bytes needs, index blocks included
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1;
+            // 二级索引块索引的一级索引块
+            total += (data_blocks - INDIRECT1_BOUND).div_ceil(INODE_INDIRECT1_COUNT);
+        }
+        total as u32
+    }
+
+    /// the number of blocks to allocate to grow the file to `new_size` bytes
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// the id of data block `inner_id` of the file
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            read_block(
+                block_device,
+                self.indirect1 as usize,
+                0,
+                |indirect1: &IndirectBlock| indirect1[inner_id - DIRECT_BOUND],
+            )
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = read_block(
+                block_device,
+                self.indirect2 as usize,
+                0,
+                |indirect2: &IndirectBlock| indirect2[last / INODE_INDIRECT1_COUNT],
+            );
+            read_block(
+                block_device,
+                indirect1 as usize,
+                0,
+                |indirect1: &IndirectBlock| indirect1[last % INODE_INDIRECT1_COUNT],
+            )
+        }
+    }
+
+    /// Grow the file to `new_size` bytes with the [`DiskInode::blocks_num_needed`]
+    /// zeroed blocks in `new_blocks`.
+    ///
+    /// 新的块依次用作数据块和尚未分配的索引块
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = self.data_blocks() as usize;
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks() as usize;
+        let mut new_blocks = new_blocks.into_iter();
+        // 直接索引
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT) {
+            self.direct[current_blocks] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+        // 一级间接索引
+        if total_blocks <= INODE_DIRECT_COUNT {
+            return;
+        }
+        if current_blocks == INODE_DIRECT_COUNT {
+            self.indirect1 = new_blocks.next().unwrap();
+        }
+        current_blocks -= INODE_DIRECT_COUNT;
+        total_blocks -= INODE_DIRECT_COUNT;
+        modify_block(
+            block_device,
+            self.indirect1 as usize,
+            0,
+            |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT) {
+                    indirect1[current_blocks] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            },
+        );
+        // 二级间接索引
+        if total_blocks <= INODE_INDIRECT1_COUNT {
+            return;
+        }
+        if current_blocks == INODE_INDIRECT1_COUNT {
+            self.indirect2 = new_blocks.next().unwrap();
+        }
+        current_blocks -= INODE_INDIRECT1_COUNT;
+        total_blocks -= INODE_INDIRECT1_COUNT;
+        modify_block(
+            block_device,
+            self.indirect2 as usize,
+            0,
+            |indirect2: &mut IndirectBlock| {
+                while current_blocks < total_blocks {
+                    let (a, b) = (
+                        current_blocks / INODE_INDIRECT1_COUNT,
+                        current_blocks % INODE_INDIRECT1_COUNT,
+                    );
+                    if b == 0 {
+                        indirect2[a] = new_blocks.next().unwrap();
+                    }
+                    modify_block(
+                        block_device,
+                        indirect2[a] as usize,
+                        0,
+                        |indirect1: &mut IndirectBlock| {
+                            indirect1[b] = new_blocks.next().unwrap();
+                        },
+                    );
+                    current_blocks += 1;
+                }
+            },
+        );
+    }
+
+    /// Truncate the file to 0 bytes, return the blocks it used, index blocks included.
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        self.size = 0;
+        // 直接索引
+        let direct = data_blocks.min(INODE_DIRECT_COUNT);
+        v.extend_from_slice(&self.direct[..direct]);
+        self.direct.fill(0);
+        // 一级间接索引
+        if data_blocks <= INODE_DIRECT_COUNT {
+            return v;
+        }
+        data_blocks -= INODE_DIRECT_COUNT;
+        v.push(self.indirect1);
+        read_block(
+            block_device,
+            self.indirect1 as usize,
+            0,
+            |indirect1: &IndirectBlock| {
+                v.extend_from_slice(&indirect1[..data_blocks.min(INODE_INDIRECT1_COUNT)]);
+            },
+        );
+        self.indirect1 = 0;
+        // 二级间接索引
+        if data_blocks <= INODE_INDIRECT1_COUNT {
+            return v;
+        }
+        data_blocks -= INODE_INDIRECT1_COUNT;
+        v.push(self.indirect2);
+        read_block(
+            block_device,
+            self.indirect2 as usize,
+            0,
+            |indirect2: &IndirectBlock| {
+                for (a, &indirect1) in indirect2[..data_blocks.div_ceil(INODE_INDIRECT1_COUNT)]
+                    .iter()
+                    .enumerate()
+                {
+                    v.push(indirect1);
+                    let count =
+                        (data_blocks - a * INODE_INDIRECT1_COUNT).min(INODE_INDIRECT1_COUNT);
+                    read_block(
+                        block_device,
+                        indirect1 as usize,
+                        0,
+                        |indirect1: &IndirectBlock| v.extend_from_slice(&indirect1[..count]),
+                    );
+                }
+            },
+        );
+        self.indirect2 = 0;
+        v
+    }
+
+    /// Read the content at `offset` into `buf`, return the number of bytes read,
+    /// which is less than `buf.len()` at the end of the file.
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let end = (offset + buf.len()).min(self.size as usize);
+        if offset >= end {
+            return 0;
+        }
+        let mut start = offset;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let dst = &mut buf[start - offset..end_current_block - offset];
+            read_block(
+                block_device,
+                self.get_block_id((start / BLOCK_SZ) as u32, block_device) as usize,
+                0,
+                |data_block: &DataBlock| {
+                    let src_start = start % BLOCK_SZ;
+                    dst.copy_from_slice(&data_block[src_start..src_start + dst.len()]);
+                },
+            );
+            start = end_current_block;
+        }
+        end - offset
+    }
+
+    /// Write `buf` at `offset`, return the number of bytes written.
+    ///
+    /// 不会改变文件大小，调用者需要先用 [`DiskInode::increase_size`] 扩大文件
+    pub fn write_at(
+        &self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(offset <= end);
+        let mut start = offset;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let src = &buf[start - offset..end_current_block - offset];
+            modify_block(
+                block_device,
+                self.get_block_id((start / BLOCK_SZ) as u32, block_device) as usize,
+                0,
+                |data_block: &mut DataBlock| {
+                    let dst_start = start % BLOCK_SZ;
+                    data_block[dst_start..dst_start + src.len()].copy_from_slice(src);
+                },
+            );
+            start = end_current_block;
+        }
+        end - offset
+    }
+}
+
+/// an entry of a directory
+#[repr(C)]
+pub struct DirEntry {
+    /// 以 `\0` 结尾的文件名
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+/// 目录项的大小，一个块中恰好能放下 16 个
+pub const DIRENT_SZ: usize = 32;
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// an entry of the file `name` at inode `inode_number`, `None` if `name` is
+    /// empty, too long or contains `\0`
+    pub fn new(name: &str, inode_number: u32) -> Option<Self> {
+        if name.is_empty() || name.len() > NAME_LENGTH_LIMIT || name.contains('\0') {
+            return None;
+        }
+        let mut entry = Self::empty();
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        entry.inode_number = inode_number;
+        Some(entry)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, DIRENT_SZ) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, DIRENT_SZ) }
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
@@ -0,0 +1,46 @@
+//! A simple file system on block devices
+//!
+//! 磁盘按块划分为五个区域，依次为：
+//!
+//! - 超级块，记录其余各区域的大小；
+//! - 索引节点位图，记录索引节点区域中哪些索引节点已被分配；
+//! - 索引节点区域，每个索引节点记录一个文件或目录的大小和数据块编号；
+//! - 数据块位图，记录数据块区域中哪些块已被分配；
+//! - 数据块区域，保存文件的内容、目录项和索引块。
+//!
+//! 块设备上的块经过 [`BLOCK_CACHE_SIZE`] 个块的缓存读写，修改过的块在被替换出缓存或
+//! [`sync_all`] 时写回。在 [`OverlayDevice`] 上写回的块只保存在内存中，可以整体丢弃。
+//!
+//! 文件系统可以占据整个块设备，也可以在 MBR 分区表中的一个 [`Partition`] 上。
+//! 块设备还可以由 [`ChecksumDevice`] 为每个块保存校验和，读到损坏的块时立即报错。
+//!
+//! 0 号索引节点是根目录，目录中可以再建立子目录。本 crate 不依赖内核，
+//! 内核和在开发机上打包磁盘镜像的 `easy-fs-fuse` 使用同一份实现
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block;
+mod block_dev;
+mod checksum;
+mod efs;
+mod layout;
+mod overlay;
+mod partition;
+mod vfs;
+
+/// 块的大小，单位为字节
+pub const BLOCK_SZ: usize = 512;
+
+pub use block::{sync_all, BLOCK_CACHE_SIZE};
+pub use block_dev::BlockDevice;
+pub use checksum::{crc32, ChecksumDevice};
+pub use efs::EasyFileSystem;
+pub use overlay::OverlayDevice;
+pub use partition::{
+    read_partition_table, write_partition_table, Partition, PartitionEntry, PartitionTableError,
+    PARTITION_TYPE_EASY_FS, PARTITION_TYPE_SWAP,
+};
+pub use vfs::Inode;
@@ -0,0 +1,59 @@
+//! A copy-on-write overlay over a block device
+//!
+//! 所有写入都保存在内存中的增量里，下层设备只被读取。丢弃增量即可回到原来的磁盘镜像，
+//! 文件系统测试因此每次都可以从同一个干净的镜像开始，而不需要重新打包
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+use spin::Mutex;
+
+use crate::block::discard_cached;
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// a block device whose writes go to an in-memory delta over `lower`
+pub struct OverlayDevice {
+    lower: Arc<dyn BlockDevice>,
+    /// 被写入过的块的最新内容
+    delta: Mutex<BTreeMap<usize, Box<[u8; BLOCK_SZ]>>>,
+}
+
+impl OverlayDevice {
+    pub fn new(lower: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            lower,
+            delta: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// the number of blocks written since the overlay was created or rolled back
+    pub fn delta_blocks(&self) -> usize {
+        self.delta.lock().len()
+    }
+
+    /// Discard every write, including those still in the block cache, so that the
+    /// device reads the same as `lower` again.
+    ///
+    /// 之后不能再使用回滚之前得到的 [`crate::Inode`] ，它们指向的索引节点可能已经不存在
+    pub fn rollback(self: &Arc<Self>) {
+        let device: Arc<dyn BlockDevice> = self.clone();
+        discard_cached(&device);
+        self.delta.lock().clear();
+    }
+}
+
+impl BlockDevice for OverlayDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        match self.delta.lock().get(&block_id) {
+            Some(block) => buf.copy_from_slice(&block[..]),
+            None => self.lower.read_block(block_id, buf),
+        }
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut block = Box::new([0u8; BLOCK_SZ]);
+        block.copy_from_slice(buf);
+        self.delta.lock().insert(block_id, block);
+    }
+}
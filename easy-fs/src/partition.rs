@@ -0,0 +1,158 @@
+//! MBR partition tables
+//!
+//! 一个磁盘可以用 MBR 分区表划分为多个分区，例如同时保存 easy-fs 文件系统和交换区。
+//! 每个分区通过 [`Partition`] 作为一个独立的块设备使用，块号从分区的开头算起
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::{BlockDevice, BLOCK_SZ};
+
+/// 分区类型：easy-fs 文件系统，借用 Linux 文件系统的类型号
+pub const PARTITION_TYPE_EASY_FS: u8 = 0x83;
+/// 分区类型：交换区，与 Linux swap 相同
+pub const PARTITION_TYPE_SWAP: u8 = 0x82;
+
+/// MBR 中分区表的偏移
+const TABLE_OFFSET: usize = 446;
+/// 每个分区表项的大小
+const ENTRY_SIZE: usize = 16;
+/// MBR 最多有 4 个主分区
+const MAX_PARTITIONS: usize = 4;
+/// MBR 结尾的签名
+const SIGNATURE: [u8; 2] = [0x55, 0xaa];
+
+/// a primary partition in the MBR
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PartitionEntry {
+    /// 分区类型，见 `PARTITION_TYPE_*`
+    pub kind: u8,
+    pub start_block: u32,
+    pub blocks: u32,
+}
+
+/// why [`read_partition_table`] found no usable partition table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PartitionTableError {
+    /// 0 号块不是 MBR
+    NoMbr,
+    /// 第 `i` 个分区包含 0 号块的 MBR
+    OverlapsMbr(usize),
+    /// 第 `i` 个分区的块数为 0
+    Empty(usize),
+    /// 第 `i` 个分区的结尾超出 MBR 能表示的最大块号
+    PastEnd(usize),
+    /// 两个分区有重叠的块
+    Overlap(usize, usize),
+}
+
+/// Check that the partitions do not overlap each other or the MBR.
+///
+/// 块设备不知道自己的大小，只能检查分区结尾不超过 32 位块号的范围
+fn validate(entries: &[PartitionEntry]) -> Result<(), PartitionTableError> {
+    let end = |entry: &PartitionEntry| entry.start_block as u64 + entry.blocks as u64;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.start_block == 0 {
+            return Err(PartitionTableError::OverlapsMbr(i));
+        }
+        if entry.blocks == 0 {
+            return Err(PartitionTableError::Empty(i));
+        }
+        if end(entry) > u32::MAX as u64 + 1 {
+            return Err(PartitionTableError::PastEnd(i));
+        }
+        for (j, other) in entries.iter().enumerate().take(i) {
+            if (entry.start_block as u64) < end(other) && (other.start_block as u64) < end(entry) {
+                return Err(PartitionTableError::Overlap(j, i));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read the partitions in the MBR of `device`.
+///
+/// 不使用的表项（类型为 0）被跳过。没有分区表的 easy-fs 镜像的 0 号块是超级块，
+/// 结尾两个字节总是 0 ，不会被误认为 MBR 。分区互相重叠或者与 MBR 重叠的分区表不能使用
+pub fn read_partition_table(
+    device: &Arc<dyn BlockDevice>,
+) -> Result<Vec<PartitionEntry>, PartitionTableError> {
+    let mut mbr = [0u8; BLOCK_SZ];
+    device.read_block(0, &mut mbr);
+    if mbr[BLOCK_SZ - 2..] != SIGNATURE {
+        return Err(PartitionTableError::NoMbr);
+    }
+    let entries: Vec<PartitionEntry> = mbr
+        [TABLE_OFFSET..TABLE_OFFSET + MAX_PARTITIONS * ENTRY_SIZE]
+        .chunks(ENTRY_SIZE)
+        .map(|entry| PartitionEntry {
+            kind: entry[4],
+            start_block: u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+            blocks: u32::from_le_bytes(entry[12..16].try_into().unwrap()),
+        })
+        .filter(|entry| entry.kind != 0)
+        .collect();
+    validate(&entries)?;
+    Ok(entries)
+}
+
+/// Write an MBR holding `entries` to block 0 of `device`.
+///
+/// 只填写起始块号和块数，CHS 地址都为 0
+pub fn write_partition_table(device: &Arc<dyn BlockDevice>, entries: &[PartitionEntry]) {
+    assert!(entries.len() <= MAX_PARTITIONS);
+    if let Err(err) = validate(entries) {
+        panic!("invalid partition table: {:?}", err);
+    }
+    let mut mbr = [0u8; BLOCK_SZ];
+    for (i, entry) in entries.iter().enumerate() {
+        let raw = &mut mbr[TABLE_OFFSET + i * ENTRY_SIZE..TABLE_OFFSET + (i + 1) * ENTRY_SIZE];
+        raw[4] = entry.kind;
+        raw[8..12].copy_from_slice(&entry.start_block.to_le_bytes());
+        raw[12..16].copy_from_slice(&entry.blocks.to_le_bytes());
+    }
+    mbr[BLOCK_SZ - 2..].copy_from_slice(&SIGNATURE);
+    device.write_block(0, &mbr);
+}
+
+/// a partition of a block device as a block device of its own
+pub struct Partition {
+    device: Arc<dyn BlockDevice>,
+    start_block: usize,
+    blocks: usize,
+}
+
+impl Partition {
+    pub fn new(device: Arc<dyn BlockDevice>, entry: &PartitionEntry) -> Self {
+        Self {
+            device,
+            start_block: entry.start_block as usize,
+            blocks: entry.blocks as usize,
+        }
+    }
+
+    /// the size of the partition in blocks
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    fn block_on_device(&self, block_id: usize) -> usize {
+        assert!(
+            block_id < self.blocks,
+            "block {} is out of the partition of {} blocks",
+            block_id,
+            self.blocks
+        );
+        self.start_block + block_id
+    }
+}
+
+impl BlockDevice for Partition {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.device.read_block(self.block_on_device(block_id), buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.device.write_block(self.block_on_device(block_id), buf);
+    }
+}
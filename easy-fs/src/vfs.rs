@@ -0,0 +1,249 @@
+//! Inodes as seen by the users of the file system
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use spin::Mutex;
+
+use crate::block::{modify_block, read_block};
+use crate::efs::EasyFileSystem;
+use crate::layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SZ, MAX_FILE_SIZE};
+use crate::BlockDevice;
+
+/// a file or directory, pointing at its [`DiskInode`]
+///
+/// 所有操作都先获取文件系统的锁，同一个文件系统上的操作因此是互斥的
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    /// the inode `inode_id` at `block_offset` of block `block_id`
+    pub fn new(
+        inode_id: u32,
+        block_id: usize,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        read_block(&self.block_device, self.block_id, self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        modify_block(&self.block_device, self.block_id, self.block_offset, f)
+    }
+
+    /// the entries of the directory `disk_inode`
+    fn dir_entries(&self, disk_inode: &DiskInode) -> Vec<DirEntry> {
+        assert!(disk_inode.is_dir());
+        let file_count = disk_inode.size as usize / DIRENT_SZ;
+        (0..file_count)
+            .map(|i| {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ
+                );
+                dirent
+            })
+            .collect()
+    }
+
+    /// the inode of the file `name` in the directory `disk_inode`, `None` if it is
+    /// not a directory
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        if !disk_inode.is_dir() {
+            return None;
+        }
+        self.dir_entries(disk_inode)
+            .into_iter()
+            .find(|dirent| dirent.name() == name)
+            .map(|dirent| dirent.inode_number())
+    }
+
+    fn inode_at(&self, fs: &EasyFileSystem, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    /// the inode number, 0 for the root directory
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    /// the size in bytes, for a directory the total size of its entries
+    pub fn size(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size)
+    }
+
+    /// the number of hard links to the file
+    pub fn nlink(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+
+    /// the permission bits
+    pub fn mode(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.mode as u32)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    /// Find the file `name` in this directory.
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(self.inode_at(&fs, inode_id))
+    }
+
+    /// Grow `disk_inode` to `new_size` bytes, return `false` without changing it
+    /// if the disk is full or the file would be too large.
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &EasyFileSystem,
+    ) -> bool {
+        if new_size <= disk_inode.size {
+            return true;
+        }
+        if new_size > MAX_FILE_SIZE {
+            return false;
+        }
+        let mut new_blocks = Vec::new();
+        for _ in 0..disk_inode.blocks_num_needed(new_size) {
+            match fs.alloc_data() {
+                Some(block_id) => new_blocks.push(block_id),
+                None => {
+                    for block_id in new_blocks {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, new_blocks, &self.block_device);
+        true
+    }
+
+    /// Create an empty file `name` with permission bits `mode` in this directory,
+    /// `None` if it exists, the name is invalid, this is not a directory or the disk is full.
+    pub fn create(&self, name: &str, mode: u32) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File, mode)
+    }
+
+    /// Create an empty directory `name` with permission bits `mode` in this directory,
+    /// `None` if it exists, the name is invalid, this is not a directory or the disk is full.
+    ///
+    /// 目录中没有 `.` 和 `..` 目录项，但链接数与 Unix 一样计算：新目录为 2 ，父目录加 1
+    pub fn create_dir(&self, name: &str, mode: u32) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory, mode)
+    }
+
+    fn create_inode(&self, name: &str, type_: DiskInodeType, mode: u32) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        let can_create = self.read_disk_inode(|disk_inode| {
+            disk_inode.is_dir() && self.find_inode_id(name, disk_inode).is_none()
+        });
+        if !can_create {
+            return None;
+        }
+        let new_inode_id = fs.alloc_inode()?;
+        let Some(dirent) = DirEntry::new(name, new_inode_id) else {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        };
+        // 新的索引节点可能与目录的索引节点在同一个块中，需要在修改目录之前初始化
+        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        modify_block(
+            &self.block_device,
+            block_id,
+            block_offset,
+            |new_inode: &mut DiskInode| new_inode.initialize(type_, (mode & 0o7777) as u16),
+        );
+        let added = self.modify_disk_inode(|dir_inode| {
+            let offset = dir_inode.size as usize;
+            if !self.increase_size((offset + DIRENT_SZ) as u32, dir_inode, &fs) {
+                return false;
+            }
+            dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+            if type_ == DiskInodeType::Directory {
+                dir_inode.nlink += 1;
+            }
+            true
+        });
+        if !added {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
+        Some(self.inode_at(&fs, new_inode_id))
+    }
+
+    /// the names of the files in this directory
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            self.dir_entries(disk_inode)
+                .iter()
+                .map(|dirent| dirent.name().to_string())
+                .collect()
+        })
+    }
+
+    /// Read the content at `offset` into `buf`, return the number of bytes read.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// Write `buf` at `offset`, growing the file if needed, return the number of
+    /// bytes written, which is 0 if the file can not grow.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let Some(end) = u32::try_from(offset + buf.len()).ok() else {
+                return 0;
+            };
+            if !self.increase_size(end, disk_inode, &fs) {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device)
+        })
+    }
+
+    /// Truncate the file to 0 bytes and free its blocks.
+    pub fn clear(&self) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            for block_id in disk_inode.clear_size(&self.block_device) {
+                fs.dealloc_data(block_id);
+            }
+        });
+    }
+}
@@ -1,67 +1,66 @@
-use std::fs::{read_dir, File};
-use std::io::{Result, Write};
+use std::env;
+use std::fs::File;
+use std::io::{Read, Result, Write};
+use std::path::PathBuf;
 
 fn main() {
-    println!("cargo:rerun-if-changed=../user/src/");
-    println!("cargo:rerun-if-changed={}", TARGET_PATH);
-    insert_app_data().unwrap();
+    println!("cargo:rerun-if-env-changed=KSYM");
+    insert_ksym_table().unwrap();
 }
 
-static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
-
-fn insert_app_data() -> Result<()> {
-    let mut f = File::create("src/link_app.S").unwrap();
-    let mut apps: Vec<_> = read_dir("../user/src/bin")
-        .unwrap()
-        .into_iter()
-        .map(|dir_entry| {
-            let mut name_with_ext = dir_entry.unwrap().file_name().into_string().unwrap();
-            name_with_ext.drain(name_with_ext.find('.').unwrap()..name_with_ext.len());
-            name_with_ext
-        })
-        .collect();
-    apps.sort();
+/// 把 `rust-nm -n -C` 导出的内核符号表（由环境变量 `KSYM` 给出路径）生成为汇编，
+/// 第一遍构建时没有符号表，生成一个空表
+///
+/// 符号表放在 .rodata 中且只通过链接符号访问，因此两遍构建得到的 .text 完全相同，
+/// 第一遍导出的函数地址在第二遍构建出的内核中依然有效
+fn insert_ksym_table() -> Result<()> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let mut f = File::create(out_dir.join("ksym.S"))?;
+    let mut symbols: Vec<(usize, String)> = Vec::new();
+    if let Ok(path) = env::var("KSYM") {
+        println!("cargo:rerun-if-changed={}", path);
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+        for line in content.lines() {
+            // `<addr> <type> <name>`，只保留代码段中的符号
+            let mut parts = line.splitn(3, ' ');
+            let (Some(addr), Some(ty), Some(name)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            if !ty.eq_ignore_ascii_case("t") {
+                continue;
+            }
+            if let Ok(addr) = usize::from_str_radix(addr, 16) {
+                symbols.push((addr, name.replace('\\', "\\\\").replace('"', "\\\"")));
+            }
+        }
+    }
+    symbols.sort();
+    symbols.dedup_by_key(|(addr, _)| *addr);
 
     writeln!(
         f,
         r#"
+    .section .rodata
     .align 3
-    .section .data
-    .global _num_app
-_num_app:
-    .quad {}"#,
-        apps.len()
+    .global _ksym_num
+    .global _ksym_addrs
+    .global _ksym_names
+_ksym_num:
+    .quad {}
+_ksym_addrs:"#,
+        symbols.len()
     )?;
-
-    for i in 0..apps.len() {
-        writeln!(f, r#"    .quad app_{}_start"#, i)?;
+    for (addr, _) in symbols.iter() {
+        writeln!(f, r#"    .quad {:#x}"#, addr)?;
     }
-    writeln!(f, r#"    .quad app_{}_end"#, apps.len() - 1)?;
-
-    writeln!(
-        f,
-        r#"
-    .global _app_names
-_app_names:"#
-    )?;
-    for app in apps.iter() {
-        writeln!(f, r#"    .string "{}""#, app)?;
+    writeln!(f, "_ksym_names:")?;
+    for idx in 0..symbols.len() {
+        writeln!(f, r#"    .quad .Lksym_{}"#, idx)?;
     }
-
-    for (idx, app) in apps.iter().enumerate() {
-        println!("app_{}: {}", idx, app);
-        writeln!(
-            f,
-            r#"
-    .section .data
-    .global app_{0}_start
-    .global app_{0}_end
-    .align 3
-app_{0}_start:
-    .incbin "{2}{1}"
-app_{0}_end:"#,
-            idx, app, TARGET_PATH
-        )?;
+    for (idx, (_, name)) in symbols.iter().enumerate() {
+        writeln!(f, ".Lksym_{}:\n    .string \"{}\"", idx, name)?;
     }
     Ok(())
 }
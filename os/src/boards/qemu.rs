@@ -1,11 +1,28 @@
 //! Constants used in rCore for qemu
 
+/// 默认的时钟频率，启动时若能从设备树中读到 `timebase-frequency` 则以其为准
 pub const CLOCK_FREQ: usize = 12500000;
 
 pub const MMIO: &[(usize, usize)] = &[
     (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
+    (0x1000_1000, 0x00_1000), // Virtio Block in virt machine
+    (0x1000_2000, 0x00_1000), // Virtio Block in virt machine
 ];
 
+/// 文件系统所在的 virtio 块设备的 MMIO 地址，即 QEMU 中 `virtio-mmio-bus.1`
+pub const VIRTIO_FS: usize = 0x1000_2000;
+
+/// 设备树之外还需要保留、不交给物理页帧分配器的物理内存区间 `(start, len)` ，
+/// 例如设备使用的 DMA 缓冲区或紧邻 MMIO 的区间
+pub const RESERVED_MEMORY: &[(usize, usize)] = &[];
+
+/// `split_console` 特性下内核输出和应用输出使用的串口在设备树中的序号（按地址排序），
+/// 0 号串口即 SBI 控制台，QEMU 需要以多个 `-serial` 参数启动
+#[cfg(feature = "split_console")]
+pub const KERNEL_SERIAL: usize = 1;
+#[cfg(feature = "split_console")]
+pub const USER_SERIAL: usize = 0;
+
 //ref:: https://github.com/andre-richter/qemu-exit
 
 const EXIT_SUCCESS: u32 = 0x5555; // Equals `exit(0)`. qemu successful exit
@@ -0,0 +1,82 @@
+//! Boot banner and boot-stage timing
+//!
+//! `rust_main` 在每个初始化阶段结束时调用 [`stage_done`] ，启动完成后由 [`report`]
+//! 打印各阶段的耗时，新增子系统拖慢启动时可以直接看出是哪一个阶段
+
+use crate::sync::UPSafeCell;
+use crate::timer;
+
+/// 最多记录的启动阶段数，多出的阶段计入最后一个阶段
+const MAX_STAGES: usize = 16;
+
+struct BootStages {
+    /// 各阶段的名字和耗时，单位为 `mtime` 的计数
+    stages: [(&'static str, usize); MAX_STAGES],
+    len: usize,
+    /// 启动时和上一个阶段结束时 `mtime` 的值
+    start: usize,
+    last: usize,
+}
+
+static BOOT_STAGES: UPSafeCell<BootStages> = unsafe {
+    UPSafeCell::new(BootStages {
+        stages: [("", 0); MAX_STAGES],
+        len: 0,
+        start: 0,
+        last: 0,
+    })
+};
+
+const BANNER: &str = r"
+        ____
+   _ __/ ___|___  _ __ ___
+  | '__| |   / _ \| '__/ _ \
+  | |  | |__| (_) | | |  __/
+  |_|   \____\___/|_|  \___|
+";
+
+/// print the boot banner in color
+pub fn banner() {
+    println!("\x1b[36m{}\x1b[0m", BANNER);
+}
+
+/// Start timing the boot stages from `start`, the `mtime` read on entry.
+///
+/// 在 `.bss` 清零之后调用，否则记录会被清除
+pub fn init(start: usize) {
+    let mut boot = BOOT_STAGES.exclusive_access();
+    boot.start = start;
+    boot.last = start;
+}
+
+/// record that the boot stage `name` finished now
+pub fn stage_done(name: &'static str) {
+    let mut boot = BOOT_STAGES.exclusive_access();
+    let now = timer::get_time();
+    let ticks = now - boot.last;
+    boot.last = now;
+    let index = boot.len.min(MAX_STAGES - 1);
+    if boot.len < MAX_STAGES {
+        boot.stages[index] = (name, ticks);
+        boot.len += 1;
+    } else {
+        boot.stages[index].1 += ticks;
+    }
+}
+
+/// print the time spent in every boot stage
+///
+/// 计数在报告时才换算为时间，因此读取设备树中的时钟频率之前的阶段也是准确的
+pub fn report() {
+    let boot = BOOT_STAGES.exclusive_access();
+    let ticks_per_us = timer::clock_freq() / timer::MICRO_PER_SEC;
+    println!("[kernel] boot stages:");
+    for &(name, ticks) in boot.stages[..boot.len].iter() {
+        println!("{:>16} {:>8}us", name, ticks / ticks_per_us);
+    }
+    println!(
+        "{:>16} {:>8}us",
+        "total",
+        (boot.last - boot.start) / ticks_per_us
+    );
+}
@@ -1,25 +1,74 @@
 //! Constants used in rCore
 
-pub use crate::board::{CLOCK_FREQ, MMIO};
+pub use crate::board::{CLOCK_FREQ, MMIO, RESERVED_MEMORY, VIRTIO_FS};
+#[cfg(feature = "split_console")]
+pub use crate::board::{KERNEL_SERIAL, USER_SERIAL};
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
+/// 应用可以申请的最大用户栈大小
+pub const MAX_USER_STACK_SIZE: usize = 4096 * 64;
+/// 应用可以申请的最大内核栈大小，内核地址空间中按此大小为每个应用预留内核栈
+pub const MAX_KERNEL_STACK_SIZE: usize = 4096 * 8;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+/// 内核堆耗尽时使用的后备堆的大小，只用于完成当前的内核操作
+pub const KERNEL_HEAP_RESERVE_SIZE: usize = 0x4000;
 /// 物理内存的终止物理地址
 pub const MEMORY_END: usize = 0x80800000;
+/// 设备树报告的物理内存多于 `MEMORY_END` 时，内核最多使用到的物理地址
+///
+/// 内核以 4K 页恒等映射物理内存，每 2MiB 内存需要一个页帧存放页表，这些页帧在扩展之前
+/// 只能从 `MEMORY_END` 以下的内存中分配
+pub const MAX_MEMORY_END: usize = 0xc0000000;
 /// 每物理个页面的大小
 pub const PAGE_SIZE: usize = 0x1000;
 /// 每物理个页页内偏移的位宽
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
+/// 应用的默认优先级
+pub const DEFAULT_PRIORITY: usize = 16;
+/// 应用可以设置的最低优先级，更低的优先级会导致 stride 调度中步长过大
+pub const MIN_PRIORITY: usize = 2;
+/// 应用可以设置的最高优先级，更高的优先级会被截断为此值
+pub const MAX_PRIORITY: usize = 1024;
+/// 每秒的时钟中断次数
+pub const TICKS_PER_SEC: usize = 100;
+/// 应用默认的时间片长度，单位为时钟中断的周期，时间片用完时才会被抢占
+pub const DEFAULT_TIME_SLICE: usize = 1;
+/// 应用可以设置的最长时间片，更长的时间片会被截断为此值
+pub const MAX_TIME_SLICE: usize = 100;
+/// 多级反馈队列的级数，第 `l` 级的时间片为任务时间片的 `2^l` 倍
+#[cfg(feature = "mlfq")]
+pub const MLFQ_LEVELS: usize = 4;
+/// 多级反馈队列把所有任务提升到最高级的周期，单位为 `ms`
+#[cfg(feature = "mlfq")]
+pub const MLFQ_BOOST_MS: usize = 500;
+/// 块缓存中修改过的块定期写回磁盘的周期，单位为 `ms`
+pub const FS_SYNC_INTERVAL_MS: usize = 1000;
+/// 日志文件的最大字节数，超过时轮转
+#[cfg(feature = "log_file")]
+pub const LOG_FILE_SIZE: usize = 64 * 1024;
+/// `Ready` 任务等待调度的时间超过此值时认为它发生了饥饿，单位为 `ms`
+pub const STARVATION_BOUND_MS: usize = 1000;
+
+/// 应用默认的 CPU 时间限制，单位为秒，`RLIM_INFINITY` 表示不限制
+pub const DEFAULT_CPU_LIMIT: u64 = abi::RLIM_INFINITY;
+
+/// 应用的默认文件创建掩码
+pub const DEFAULT_UMASK: u32 = 0o022;
+
+/// 应用可以通过 `mmap` 映射的最高地址（不含），即 SV39 地址空间低半部分的上界
+pub const USER_SPACE_END: usize = 1 << 38;
 /// 内核和应用地址空间共享的跳板页面的起始地址
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 ///  Trap 上下文在应用地址空间中的虚拟地址
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
-/// Return (bottom, top) of a kernel stack in kernel space.
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
-    let bottom = top - KERNEL_STACK_SIZE;
+/// Return (bottom, top) of a kernel stack of `size` bytes in kernel space.
+///
+/// 每个应用的内核栈占据 `MAX_KERNEL_STACK_SIZE` 大小的一段预留区域的顶部，相邻的预留区域之间有一个保护页
+pub fn kernel_stack_position(app_id: usize, size: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - app_id * (MAX_KERNEL_STACK_SIZE + PAGE_SIZE);
+    let bottom = top - size;
     (bottom, top)
 }
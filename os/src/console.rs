@@ -1,20 +1,96 @@
+//! Console output, split into a kernel channel and a user channel
+//!
+//! 默认两个通道都通过 SBI 输出到第一个串口；`split_console` 特性下按
+//! [`config::KERNEL_SERIAL`] 和 [`config::USER_SERIAL`] 把它们分别输出到设备树中的不同串口，
+//! 使得测试脚本可以直接比较应用的输出，而不必从中剔除内核日志。输入始终来自 SBI 控制台
+
 use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use crate::sbi::console_putchar;
+#[cfg(feature = "split_console")]
+use crate::{config, mm, uart::Uart};
+
+/// an output channel of the console
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Channel {
+    /// 内核的 `print!` 和日志
+    Kernel,
+    /// 应用的标准输出和终端回显
+    User,
+}
+
+/// 各通道输出到的串口的 MMIO 地址，为 0 时通过 SBI 输出
+static KERNEL_UART: AtomicUsize = AtomicUsize::new(0);
+static USER_UART: AtomicUsize = AtomicUsize::new(0);
+
+impl Channel {
+    fn uart(self) -> &'static AtomicUsize {
+        match self {
+            Channel::Kernel => &KERNEL_UART,
+            Channel::User => &USER_UART,
+        }
+    }
+
+    /// 直接输出，不经过也不修改控制台的任何状态
+    fn putchar(self, c: u8) {
+        match self.uart().load(Ordering::Relaxed) {
+            0 => console_putchar(c as usize),
+            #[cfg(feature = "split_console")]
+            base => Uart::new(base).putchar(c),
+            #[cfg(not(feature = "split_console"))]
+            _ => unreachable!(),
+        }
+    }
+}
 
-struct Stdout;
+struct Stdout(Channel);
 
 impl core::fmt::Write for Stdout {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for c in s.bytes() {
+            self.0.putchar(c);
         }
         Ok(())
     }
 }
 
+/// 控制台是否正在输出。若在输出的过程中被 trap 打断并再次进入 [`print`]，
+/// 单核上等待它被释放只会死锁，因此改为直接输出
+static CONSOLE_BUSY: AtomicBool = AtomicBool::new(false);
+
+fn print_to(channel: Channel, args: core::fmt::Arguments) {
+    if CONSOLE_BUSY
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        let _ = Stdout(channel).write_fmt(args);
+        return;
+    }
+    Stdout(channel).write_fmt(args).unwrap();
+    CONSOLE_BUSY.store(false, Ordering::Release);
+}
+
+/// print to the kernel channel
 pub fn print(args: core::fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    self::print_to(Channel::Kernel, args);
+}
+
+/// print to the user channel, for the output of apps
+pub fn user_print(args: core::fmt::Arguments) {
+    self::print_to(Channel::User, args);
+}
+
+/// echo a character of terminal input on the user channel
+pub fn user_putchar(c: u8) {
+    Channel::User.putchar(c);
+}
+
+/// Print directly to the kernel channel, safe to use from panic and fault contexts
+/// even when the console is in use.
+pub fn emergency_print(args: core::fmt::Arguments) {
+    // 在 panic 中再次 panic 没有意义，忽略错误
+    let _ = Stdout(Channel::Kernel).write_fmt(args);
 }
 
 #[macro_export]
@@ -30,3 +106,55 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
     }
 }
+
+#[macro_export]
+macro_rules! emergency_println {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::emergency_print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
+    }
+}
+
+/// `println!` usable before `.bss` is cleared and the heap and logger are set up
+#[macro_export]
+macro_rules! earlyprintln {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::logging::early_print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
+    }
+}
+
+/// Move the kernel and user channels to the serial ports configured for them,
+/// given the `ports` reported by the device tree.
+///
+/// 0 号串口由 SBI 负责；其余串口需要在内核地址空间中映射，必须在 [`mm::init`] 之后调用。
+/// 找不到或映射失败的串口保持原来的输出
+#[cfg(feature = "split_console")]
+pub fn split(ports: &[usize]) {
+    for (channel, index) in [
+        (Channel::Kernel, config::KERNEL_SERIAL),
+        (Channel::User, config::USER_SERIAL),
+    ] {
+        if index == 0 {
+            continue;
+        }
+        let Some(&base) = ports.get(index) else {
+            println!(
+                "[kernel] serial port {} not found, {:?} output stays on the console",
+                index, channel
+            );
+            continue;
+        };
+        if let Err(err) = mm::map_identical(base, base + config::PAGE_SIZE) {
+            println!(
+                "[kernel] failed to map serial port {} at {:#x}: {:?}",
+                index, base, err
+            );
+            continue;
+        }
+        Uart::new(base).init();
+        channel.uart().store(base, Ordering::Relaxed);
+        println!(
+            "[kernel] {:?} output moved to serial port {} at {:#x}",
+            channel, index, base
+        );
+    }
+}
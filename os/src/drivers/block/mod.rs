@@ -0,0 +1,6 @@
+//! Block devices
+
+mod virtio_blk;
+
+pub use easy_fs::{BlockDevice, BLOCK_SZ};
+pub use virtio_blk::VirtIOBlock;
@@ -0,0 +1,85 @@
+//! Virtio block device on the virtio-mmio bus of QEMU
+
+use alloc::vec::Vec;
+
+use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
+
+use crate::config;
+use crate::mm::{self, FrameTracker};
+use crate::sync::UPSafeCell;
+
+use super::BlockDevice;
+
+/// a virtio block device
+pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+
+impl VirtIOBlock {
+    /// the device whose registers are at `base`, panic if there is no virtio block device
+    pub fn new(base: usize) -> Self {
+        let header = unsafe { &mut *(base as *mut VirtIOHeader) };
+        let blk = VirtIOBlk::<VirtioHal>::new(header)
+            .unwrap_or_else(|err| panic!("no virtio block device at {:#x}: {:?}", base, err));
+        Self(unsafe { UPSafeCell::new(blk) })
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.0
+            .exclusive_access()
+            .read_block(block_id, buf)
+            .expect("Error when reading VirtIOBlk");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0
+            .exclusive_access()
+            .write_block(block_id, buf)
+            .expect("Error when writing VirtIOBlk");
+    }
+}
+
+/// 分配给 virtio 队列的物理页帧
+static QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+
+/// memory operations the virtio driver needs from the kernel
+pub struct VirtioHal;
+
+impl Hal for VirtioHal {
+    /// 分配物理页号连续的 `pages` 个页帧，只在初始化设备时调用，此时新分配的页帧是连续的
+    fn dma_alloc(pages: usize) -> usize {
+        let mut frames: Vec<FrameTracker> = Vec::with_capacity(pages);
+        for i in 0..pages {
+            let frame = mm::frame_alloc().expect("out of frames for the virtio queue");
+            if let Some(first) = frames.first() {
+                assert_eq!(
+                    usize::from(frame.ppn),
+                    usize::from(first.ppn) + i,
+                    "frames of the virtio queue are not contiguous"
+                );
+            }
+            frames.push(frame);
+        }
+        let pa = usize::from(frames[0].ppn) << config::PAGE_SIZE_BITS;
+        QUEUE_FRAMES.exclusive_access().extend(frames);
+        pa
+    }
+
+    fn dma_dealloc(pa: usize, pages: usize) -> i32 {
+        let start = pa >> config::PAGE_SIZE_BITS;
+        QUEUE_FRAMES
+            .exclusive_access()
+            .retain(|frame| !(start..start + pages).contains(&usize::from(frame.ppn)));
+        0
+    }
+
+    /// 物理内存在内核地址空间中是恒等映射的
+    fn phys_to_virt(addr: usize) -> usize {
+        addr
+    }
+
+    fn virt_to_phys(vaddr: usize) -> usize {
+        mm::kernel_virt_to_phys(vaddr)
+            .unwrap_or_else(|| panic!("{:#x} is not mapped in kernel space", vaddr))
+    }
+}
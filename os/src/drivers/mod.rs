@@ -0,0 +1,7 @@
+//! Device drivers
+//!
+//! 串口由 SBI 或 `uart.rs` 直接驱动，这里是其余需要内核驱动的设备
+
+pub mod block;
+
+pub use block::{BlockDevice, VirtIOBlock, BLOCK_SZ};
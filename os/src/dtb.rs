@@ -0,0 +1,287 @@
+//! Minimal flattened device tree (DTB) reader
+//!
+//! 只实现内核启动时需要的查询：SBI 在 `a1` 中传入设备树的物理地址，
+//! 内核在开启分页之前读取其中的 `timebase-frequency` 属性、核的个数、物理内存区间、被保留的物理内存区间以及串口的地址
+//!
+//! 设备树位于内核地址空间之外，[`DeviceTree`] 按物理地址直接访问它，因此所有读取设备树的函数
+//! 都只能在开启分页之前调用
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 设备树头部的魔数
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// a flattened device tree in physical memory
+pub struct DeviceTree {
+    base: usize,
+    total_size: usize,
+    struct_offset: usize,
+    strings_offset: usize,
+}
+
+impl DeviceTree {
+    /// Check the header of the device tree at physical address `dtb_pa`.
+    pub fn from_pa(dtb_pa: usize) -> Option<Self> {
+        if dtb_pa == 0 || dtb_pa % 8 != 0 {
+            return None;
+        }
+        let mut dt = Self {
+            base: dtb_pa,
+            total_size: 40,
+            struct_offset: 0,
+            strings_offset: 0,
+        };
+        if dt.read_u32(0)? != FDT_MAGIC {
+            return None;
+        }
+        dt.total_size = dt.read_u32(4)? as usize;
+        dt.struct_offset = dt.read_u32(8)? as usize;
+        dt.strings_offset = dt.read_u32(12)? as usize;
+        Some(dt)
+    }
+
+    /// 读取偏移为 `offset` 的大端序 32 位整数
+    fn read_u32(&self, offset: usize) -> Option<u32> {
+        if offset + 4 > self.total_size {
+            return None;
+        }
+        let value = unsafe { ((self.base + offset) as *const u32).read_volatile() };
+        Some(u32::from_be(value))
+    }
+
+    /// 偏移为 `offset` 处以 `\0` 结尾的字符串
+    fn read_str(&self, offset: usize) -> Option<&'static [u8]> {
+        let mut len: usize = 0;
+        loop {
+            if offset + len >= self.total_size {
+                return None;
+            }
+            if unsafe { ((self.base + offset + len) as *const u8).read_volatile() } == 0 {
+                break;
+            }
+            len += 1;
+        }
+        Some(unsafe { core::slice::from_raw_parts((self.base + offset) as *const u8, len) })
+    }
+
+    /// `len` bytes at offset `offset`
+    fn read_bytes(&self, offset: usize, len: usize) -> Option<&'static [u8]> {
+        if offset + len > self.total_size {
+            return None;
+        }
+        Some(unsafe { core::slice::from_raw_parts((self.base + offset) as *const u8, len) })
+    }
+
+    /// Call `visit` with every property in the structure block and the node it belongs to,
+    /// stop early if `visit` returns `true`.
+    ///
+    /// 同一节点中属性总是出现在子节点之前，因此遍历到子节点的属性时父节点的
+    /// `#address-cells` 和 `#size-cells` 已经确定
+    fn walk(&self, mut visit: impl FnMut(&Node, &[u8], &'static [u8]) -> bool) -> Option<()> {
+        let mut offset = self.struct_offset;
+        let mut node = Node {
+            names: [b""; MAX_DEPTH],
+            depth: 0,
+            reg_cells: (2, 1),
+        };
+        // 每一层节点声明的 (#address-cells, #size-cells)，未声明时为规范中的默认值
+        let mut cells = [(2usize, 1usize); MAX_DEPTH + 1];
+        loop {
+            let token = self.read_u32(offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = self.read_str(offset)?;
+                    offset = align_up(offset + name.len() + 1);
+                    if node.depth == MAX_DEPTH {
+                        return None;
+                    }
+                    node.names[node.depth] = name;
+                    node.depth += 1;
+                    cells[node.depth] = (2, 1);
+                    node.reg_cells = cells[node.depth - 1];
+                }
+                FDT_END_NODE => {
+                    node.depth = node.depth.checked_sub(1)?;
+                    node.reg_cells = cells[node.depth.saturating_sub(1)];
+                }
+                FDT_PROP => {
+                    let len = self.read_u32(offset)? as usize;
+                    let name_offset = self.read_u32(offset + 4)? as usize;
+                    let value = self.read_bytes(offset + 8, len)?;
+                    offset = align_up(offset + 8 + len);
+                    let name = self.read_str(self.strings_offset + name_offset)?;
+                    match name {
+                        b"#address-cells" => cells[node.depth].0 = be_cells(value)?,
+                        b"#size-cells" => cells[node.depth].1 = be_cells(value)?,
+                        _ => {}
+                    }
+                    if visit(&node, name, value) {
+                        return Some(());
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => return Some(()),
+                _ => return None,
+            }
+        }
+    }
+
+    /// The `timebase-frequency` property, found in `/cpus` or in one of its `cpu@N` children.
+    pub fn timebase_frequency(&self) -> Option<usize> {
+        let mut freq: Option<usize> = None;
+        self.walk(|node, name, value| {
+            if name == b"timebase-frequency" && (node.is(&[b"cpus"]) || node.is(&[b"cpus", b"cpu"]))
+            {
+                freq = be_cells(value);
+            }
+            freq.is_some()
+        })?;
+        freq
+    }
+
+    /// Number of harts, i.e. the `/cpus/cpu@N` nodes.
+    pub fn hart_count(&self) -> usize {
+        let mut count: usize = 0;
+        self.walk(|node, name, _| {
+            if name == b"reg" && node.is(&[b"cpus", b"cpu"]) {
+                count += 1;
+            }
+            false
+        });
+        count
+    }
+
+    /// Physical memory ranges `[start, end)`, from the `reg` of the `/memory` nodes.
+    pub fn memory_regions(&self) -> Vec<(usize, usize)> {
+        let mut regions: Vec<(usize, usize)> = Vec::new();
+        self.walk(|node, name, value| {
+            if name == b"reg" && node.is(&[b"memory"]) {
+                regions.extend(reg_entries(value, node.reg_cells));
+            }
+            false
+        });
+        regions.sort();
+        regions
+    }
+
+    /// Base addresses of the `serial` nodes, sorted by address.
+    #[cfg_attr(not(feature = "split_console"), allow(unused))]
+    pub fn serial_ports(&self) -> Vec<usize> {
+        let mut ports: Vec<usize> = Vec::new();
+        self.walk(|node, name, value| {
+            if name == b"reg"
+                && node.depth > 1
+                && Node::base_name(node.names[node.depth - 1]) == b"serial"
+            {
+                ports.extend(
+                    reg_entries(value, node.reg_cells)
+                        .map(|(start, _)| start)
+                        .take(1),
+                );
+            }
+            false
+        });
+        ports.sort();
+        ports
+    }
+
+    /// Physical ranges `[start, end)` that must not be used as normal memory:
+    /// the memory reservation block, the `reg` of `/reserved-memory` children
+    /// and the device tree itself.
+    pub fn reserved_regions(&self) -> Vec<(usize, usize)> {
+        let mut regions: Vec<(usize, usize)> = vec![(self.base, self.base + self.total_size)];
+        // 内存保留块由成对的大端序 u64 (address, size) 组成，以 (0, 0) 结尾
+        if let Some(mut offset) = self.read_u32(16).map(|offset| offset as usize) {
+            while let (Some(address), Some(size)) =
+                (self.read_bytes(offset, 8), self.read_bytes(offset + 8, 8))
+            {
+                let (address, size) = (be_cells(address).unwrap(), be_cells(size).unwrap());
+                if size == 0 {
+                    break;
+                }
+                regions.push((address, address + size));
+                offset += 16;
+            }
+        }
+        self.walk(|node, name, value| {
+            if name == b"reg" && node.depth == 3 && node.is_child_of(&[b"reserved-memory"]) {
+                regions.extend(reg_entries(value, node.reg_cells));
+            }
+            false
+        });
+        regions
+    }
+}
+
+/// 节点的最大嵌套深度，包括根节点
+const MAX_DEPTH: usize = 8;
+
+/// the node a property belongs to while walking the structure block
+struct Node {
+    /// 从根节点（名字为空）开始的各级节点名
+    names: [&'static [u8]; MAX_DEPTH],
+    /// 节点的深度，根节点为 1
+    depth: usize,
+    /// 父节点声明的 (#address-cells, #size-cells) ，决定本节点 `reg` 属性的格式
+    reg_cells: (usize, usize),
+}
+
+impl Node {
+    /// 去掉 `@` 之后的单元地址的节点名
+    fn base_name(name: &[u8]) -> &[u8] {
+        name.split(|&c| c == b'@').next().unwrap()
+    }
+
+    /// whether the node is `/path[0]/path[1]/...`, ignoring unit addresses
+    fn is(&self, path: &[&[u8]]) -> bool {
+        self.depth == path.len() + 1 && self.is_child_of(path)
+    }
+
+    /// whether the node is below `/path[0]/path[1]/...`, ignoring unit addresses
+    fn is_child_of(&self, path: &[&[u8]]) -> bool {
+        self.depth > path.len()
+            && path
+                .iter()
+                .zip(self.names[1..].iter())
+                .all(|(expected, name)| Self::base_name(name) == *expected)
+    }
+}
+
+/// 把 1 个或 2 个大端序 32 位 cell 组成的值读为整数
+fn be_cells(value: &[u8]) -> Option<usize> {
+    match value.len() {
+        4 | 8 => Some(
+            value
+                .iter()
+                .fold(0usize, |acc, &byte| acc << 8 | byte as usize),
+        ),
+        _ => None,
+    }
+}
+
+/// `reg` 属性中的各个 (address, size) 对，转换为 `[start, end)` 区间
+fn reg_entries(
+    value: &'static [u8],
+    (address_cells, size_cells): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    let entry_len = (address_cells + size_cells) * 4;
+    value
+        .chunks_exact(entry_len.max(4))
+        .filter_map(move |entry| {
+            let (address, size) = entry.split_at(address_cells * 4);
+            Some((be_cells(address)?, be_cells(size)?))
+        })
+        .map(|(address, size)| (address, address + size))
+}
+
+/// 结构块中的每个 token 都按 4 字节对齐
+fn align_up(offset: usize) -> usize {
+    (offset + 3) & !3
+}
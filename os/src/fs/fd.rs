@@ -0,0 +1,78 @@
+//! Entries of the fd table
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use abi::{Dirent, IoStats, Stat, S_IFCHR, S_IFMT};
+
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+
+use super::File;
+
+/// an opened file in the fd table, with the I/O done through it
+pub struct FileDesc {
+    /// 打开时的文件名，标准输入输出为 `stdin`/`stdout`/`stderr`
+    pub name: String,
+    file: Arc<dyn File>,
+    stats: UPSafeCell<IoStats>,
+}
+
+impl FileDesc {
+    pub fn new(name: &str, file: Arc<dyn File>) -> Self {
+        Self {
+            name: String::from(name),
+            file,
+            stats: unsafe { UPSafeCell::new(IoStats::default()) },
+        }
+    }
+
+    pub fn readable(&self) -> bool {
+        self.file.readable()
+    }
+
+    pub fn writable(&self) -> bool {
+        self.file.writable()
+    }
+
+    pub fn wait_readable(&self) -> bool {
+        self.file.wait_readable()
+    }
+
+    /// Read from the file into `buf` and count it, return the number of bytes read.
+    pub fn read(&self, buf: UserBuffer) -> usize {
+        let len = self.file.read(buf);
+        let mut stats = self.stats.exclusive_access();
+        stats.reads += 1;
+        stats.read_bytes += len as u64;
+        len
+    }
+
+    /// Write `buf` to the file and count it, return the number of bytes written.
+    pub fn write(&self, buf: UserBuffer) -> usize {
+        let len = self.file.write(buf);
+        let mut stats = self.stats.exclusive_access();
+        stats.writes += 1;
+        stats.write_bytes += len as u64;
+        len
+    }
+
+    /// whether the file is in the file system, not the terminal
+    pub fn in_fs(&self) -> bool {
+        self.file.stat().mode & S_IFMT != S_IFCHR
+    }
+
+    pub fn stat(&self) -> Stat {
+        self.file.stat()
+    }
+
+    /// the next `count` entries of the directory, `None` if it is not a directory
+    pub fn dirents(&self, count: usize) -> Option<Vec<Dirent>> {
+        self.file.dirents(count)
+    }
+
+    pub fn stats(&self) -> IoStats {
+        *self.stats.exclusive_access()
+    }
+}
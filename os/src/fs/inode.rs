@@ -0,0 +1,207 @@
+//! Files in the easy-fs file system opened by tasks
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use abi::{Dirent, Stat, NAME_MAX, S_IFDIR, S_IFREG};
+use bitflags::*;
+use easy_fs::Inode;
+
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::task;
+
+use super::{File, ROOT_INODE};
+
+/// 文件系统所在设备的编号，终端为 0
+const FS_DEV: u64 = 1;
+
+bitflags! {
+    /// flags of `open`, see `abi::O_*`
+    pub struct OpenFlags: u32 {
+        const RDONLY = abi::O_RDONLY;
+        const WRONLY = abi::O_WRONLY;
+        const RDWR = abi::O_RDWR;
+        const CREATE = abi::O_CREAT;
+        const TRUNC = abi::O_TRUNC;
+    }
+}
+
+impl OpenFlags {
+    /// whether the file is opened for reading and for writing
+    ///
+    /// 同时设置 `WRONLY` 和 `RDWR` 时按 `RDWR` 处理
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::RDWR) {
+            (true, true)
+        } else if self.contains(Self::WRONLY) {
+            (false, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+/// an opened file in the file system, with its own offset
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    inner: UPSafeCell<OSInodeInner>,
+}
+
+struct OSInodeInner {
+    /// 下一次读写开始的位置
+    offset: usize,
+    inode: Arc<Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+        }
+    }
+}
+
+/// the directory holding `path` and the last component of `path`, `None` if the
+/// directory does not exist or the last component is empty, `.` or `..`
+///
+/// 路径都从根目录开始，开头的 `/` 可以省略，不支持 `.` 和 `..`
+fn lookup_parent(path: &str) -> Option<(Arc<Inode>, &str)> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (lookup(dir)?, name),
+        None => (Arc::clone(&ROOT_INODE), path),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some((dir, name))
+}
+
+/// the file or directory at `path`, `None` if it does not exist
+fn lookup(path: &str) -> Option<Arc<Inode>> {
+    path.split('/')
+        .filter(|name| !name.is_empty())
+        .try_fold(Arc::clone(&ROOT_INODE), |dir, name| dir.find(name))
+}
+
+/// Open the file at `path` with `flags`, `None` if it does not exist and `CREATE`
+/// is not given, or it can not be created.
+///
+/// 目录只能以 `RDONLY` 打开，之后不能读写，只能通过 [`File::dirents`] 列出其中的文件
+pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    let inode = match lookup(path) {
+        Some(inode) if inode.is_dir() => {
+            if writable || flags.contains(OpenFlags::TRUNC) {
+                return None;
+            }
+            return Some(Arc::new(OSInode::new(false, false, inode)));
+        }
+        Some(inode) => {
+            if flags.contains(OpenFlags::TRUNC) {
+                inode.clear();
+            }
+            inode
+        }
+        None if flags.contains(OpenFlags::CREATE) => {
+            let (dir, name) = lookup_parent(path)?;
+            dir.create(name, 0o666 & !task::current_umask())?
+        }
+        None => return None,
+    };
+    Some(Arc::new(OSInode::new(readable, writable, inode)))
+}
+
+/// Create the directory at `path`, return `false` if it exists or its parent
+/// does not.
+pub fn make_dir(path: &str) -> bool {
+    lookup_parent(path).is_some_and(|(dir, name)| {
+        dir.create_dir(name, 0o777 & !task::current_umask())
+            .is_some()
+    })
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_read_size = 0;
+        for slice in buf.buffers {
+            let read_size = inner.inode.read_at(inner.offset, slice);
+            inner.offset += read_size;
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        total_read_size
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_write_size = 0;
+        for slice in buf.buffers {
+            let write_size = inner.inode.write_at(inner.offset, slice);
+            inner.offset += write_size;
+            total_write_size += write_size;
+            // 磁盘已满或文件过大时写入失败
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        total_write_size
+    }
+
+    /// easy-fs 没有权限位，总是报告所有人可读写
+    fn stat(&self) -> Stat {
+        let inode = &self.inner.exclusive_access().inode;
+        let kind = if inode.is_dir() { S_IFDIR } else { S_IFREG };
+        Stat {
+            dev: FS_DEV,
+            ino: inode.inode_id() as u64,
+            mode: kind | inode.mode(),
+            nlink: inode.nlink(),
+            size: inode.size() as u64,
+            ..Default::default()
+        }
+    }
+
+    /// 目录的偏移为下一个要列出的目录项的序号
+    fn dirents(&self, count: usize) -> Option<Vec<Dirent>> {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.inode.is_dir() {
+            return None;
+        }
+        let dirents: Vec<Dirent> = inner
+            .inode
+            .ls()
+            .iter()
+            .skip(inner.offset)
+            .take(count)
+            .filter_map(|name| {
+                let inode = inner.inode.find(name)?;
+                let mut dirent = Dirent {
+                    ino: inode.inode_id() as u64,
+                    kind: if inode.is_dir() { S_IFDIR } else { S_IFREG },
+                    ..Default::default()
+                };
+                let len = name.len().min(NAME_MAX);
+                dirent.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+                Some(dirent)
+            })
+            .collect();
+        inner.offset += dirents.len();
+        Some(dirents)
+    }
+}
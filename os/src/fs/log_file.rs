@@ -0,0 +1,169 @@
+//! Kernel log records kept in a file on easy-fs
+//!
+//! 文件系统挂载之后，日志还会追加到 `/log/kernel.log` ，长时间的压力测试结束后仍然可以查看。
+//! 记录先缓存在内存中，由 [`super::sync`] 在写回块缓存之前写入文件，输出日志的代码因此不会在
+//! 持有文件系统的锁时再次进入文件系统。文件超过 [`config::LOG_FILE_SIZE`] 时轮转：原有内容移到
+//! `kernel.log.1` ，覆盖更早的日志。文件系统回滚时日志文件随之消失，由 [`reopen`] 重新创建
+
+use alloc::sync::Arc;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use easy_fs::Inode;
+use log::{LevelFilter, Record};
+
+use crate::config;
+use crate::drivers::BLOCK_SZ;
+use crate::logging::{self, LogSink};
+use crate::sync::UPSafeCell;
+
+use super::ROOT_INODE;
+
+const LOG_DIR: &str = "log";
+const LOG_FILE: &str = "kernel.log";
+const ROTATED_LOG_FILE: &str = "kernel.log.1";
+/// 内存中最多缓存的日志字节数，超过时丢弃新的记录
+const MAX_PENDING: usize = 16 * 1024;
+
+/// the log file and the one it was last rotated to
+struct LogFiles {
+    current: Arc<Inode>,
+    rotated: Arc<Inode>,
+}
+
+/// records not written to the log file yet
+///
+/// 使用定长数组而不是 `String` ，堆分配中输出的日志不会再次进入堆分配器
+struct PendingLog {
+    buf: [u8; MAX_PENDING],
+    len: usize,
+}
+
+impl Write for PendingLog {
+    /// 缓冲区满了之后丢弃后面的内容
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let n = s.len().min(MAX_PENDING - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// 日志文件，没有成功打开时为 `None`
+static LOG_FILES: UPSafeCell<Option<LogFiles>> = unsafe { UPSafeCell::new(None) };
+static PENDING: UPSafeCell<PendingLog> = unsafe {
+    UPSafeCell::new(PendingLog {
+        buf: [0; MAX_PENDING],
+        len: 0,
+    })
+};
+/// 因为缓冲区已满或者正在被访问而丢弃的记录数
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// buffer records of level `Info` and above for the log file
+struct FileSink;
+
+static FILE_SINK: FileSink = FileSink;
+
+impl LogSink for FileSink {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Info
+    }
+
+    /// 嵌套输出的日志（例如写日志文件时产生的日志）被丢弃
+    fn log(&self, record: &Record) {
+        let Some(mut pending) = PENDING.try_exclusive_access() else {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        if pending.len == MAX_PENDING {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let _ = writeln!(pending, "[{}] {}", record.level(), record.args());
+    }
+}
+
+/// the log files, created if needed
+fn open_files() -> Option<LogFiles> {
+    let dir = ROOT_INODE
+        .find(LOG_DIR)
+        .or_else(|| ROOT_INODE.create_dir(LOG_DIR, 0o755));
+    let open = |name| {
+        let dir = dir.as_ref()?;
+        dir.find(name).or_else(|| dir.create(name, 0o644))
+    };
+    let (Some(current), Some(rotated)) = (open(LOG_FILE), open(ROTATED_LOG_FILE)) else {
+        log::warn!("[kernel] fs: can not create /{}/{}", LOG_DIR, LOG_FILE);
+        return None;
+    };
+    Some(LogFiles { current, rotated })
+}
+
+/// Open the log files, creating them if needed, and start logging to them.
+pub fn init() {
+    let Some(files) = open_files() else {
+        return;
+    };
+    *LOG_FILES.exclusive_access() = Some(files);
+    logging::add_sink(&FILE_SINK);
+}
+
+/// Open the log files again after the file system is rolled back, which frees
+/// the inodes of the ones created since boot.
+///
+/// 原来的 inode 可能已经分配给了其他文件，不能再写入
+#[cfg_attr(not(feature = "fs_overlay"), allow(unused))]
+pub fn reopen() {
+    let mut files = LOG_FILES.exclusive_access();
+    if files.is_some() {
+        *files = open_files();
+    }
+}
+
+/// Move the content of the log file to the rotated one and empty it.
+fn rotate(files: &LogFiles) {
+    files.rotated.clear();
+    let mut buf = [0u8; BLOCK_SZ];
+    let mut offset = 0;
+    loop {
+        let len = files.current.read_at(offset, &mut buf);
+        if len == 0 || files.rotated.write_at(offset, &buf[..len]) < len {
+            break;
+        }
+        offset += len;
+    }
+    files.current.clear();
+}
+
+/// Append the pending records to the log file, rotating it first if it would
+/// grow beyond [`config::LOG_FILE_SIZE`].
+pub fn write_pending() {
+    let files = LOG_FILES.exclusive_access();
+    let Some(files) = files.as_ref() else {
+        return;
+    };
+    let Some(mut pending) = PENDING.try_exclusive_access() else {
+        return;
+    };
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let _ = writeln!(pending, "[kernel] {} log records dropped", dropped);
+    }
+    if pending.len == 0 {
+        return;
+    }
+    let mut size = files.current.size() as usize;
+    if size + pending.len > config::LOG_FILE_SIZE {
+        rotate(files);
+        size = 0;
+    }
+    if files.current.write_at(size, &pending.buf[..pending.len]) < pending.len {
+        // 磁盘已满，只能放弃这些记录，不能再输出日志
+        crate::console::emergency_print(format_args!(
+            "[kernel] fs: {} bytes of log lost\n",
+            pending.len
+        ));
+    }
+    pending.len = 0;
+}
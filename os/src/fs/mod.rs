@@ -0,0 +1,206 @@
+//! File system
+//!
+//! 应用的可执行文件由 `easy-fs-fuse` 在构建时打包进 virtio 块设备上的 easy-fs 文件系统，
+//! 都在根目录下，文件名即应用名，根目录下的子目录不是应用。磁盘带有 MBR 分区表时，文件系统在其中的 easy-fs 分区上，
+//! 交换区分区（如果有）留给 [`crate::mm`] 换出页面。用 `easy-fs-fuse --checksum` 打包的镜像中
+//! 每个块都有校验和，读到损坏的块时内核立即 panic
+//!
+//! 任务通过文件描述符表中的 [`File`] 读写标准输入输出等各种文件
+//!
+//! 启用 `log_file` 特性时，内核日志还会写入文件系统中的日志文件，见 `log_file.rs`
+//!
+//! 启用 `fs_overlay` 特性时，文件系统建立在块设备之上的 [`OverlayDevice`] 上，写入只保存在内存中，
+//! 每次启动都从同一个磁盘镜像开始，测试还可以用 [`rollback`] 随时回到启动时的状态
+
+mod fd;
+mod inode;
+#[cfg(feature = "log_file")]
+mod log_file;
+mod stdio;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use abi::{Dirent, Stat};
+
+#[cfg(feature = "fs_overlay")]
+use easy_fs::OverlayDevice;
+use easy_fs::{
+    read_partition_table, BlockDevice, ChecksumDevice, EasyFileSystem, Inode, Partition,
+    PartitionTableError, PARTITION_TYPE_EASY_FS, PARTITION_TYPE_SWAP,
+};
+
+use crate::config;
+use crate::drivers::{VirtIOBlock, BLOCK_SZ};
+use crate::mm::UserBuffer;
+use crate::sync::LazyInit;
+use crate::timer;
+
+pub use fd::FileDesc;
+pub use inode::{make_dir, open_file, OpenFlags};
+pub use stdio::{Stdin, Stdout};
+
+/// anything a task can read or write through a file descriptor
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Block until a read would not block, return whether current task was suspended meanwhile.
+    ///
+    /// 在翻译用户缓冲区之前调用，[`File::read`] 因此不会在持有用户缓冲区时切换任务
+    fn wait_readable(&self) -> bool {
+        false
+    }
+    /// Read into `buf`, return the number of bytes read.
+    fn read(&self, buf: UserBuffer) -> usize;
+    /// Write `buf`, return the number of bytes written.
+    fn write(&self, buf: UserBuffer) -> usize;
+    /// the status of the file, like `fstat`
+    fn stat(&self) -> Stat;
+    /// the next `count` entries of a directory, `None` if this is not a directory
+    fn dirents(&self, _count: usize) -> Option<Vec<Dirent>> {
+        None
+    }
+}
+
+/// the root directory, opened by [`init`]
+static ROOT_INODE: LazyInit<Arc<Inode>> = LazyInit::new();
+
+/// the swap partition on the disk of the file system, found by [`init`]
+static SWAP_PARTITION: LazyInit<Arc<Partition>> = LazyInit::new();
+
+/// the overlay the file system is on, set by [`init`]
+#[cfg(feature = "fs_overlay")]
+static OVERLAY: LazyInit<Arc<OverlayDevice>> = LazyInit::new();
+
+/// 上次把块缓存写回磁盘的时间，单位为 `ms`
+static LAST_SYNC_MS: AtomicUsize = AtomicUsize::new(0);
+
+/// open the file system on the virtio block device at [`config::VIRTIO_FS`], or on
+/// its easy-fs partition if it has a partition table
+pub fn init() {
+    let disk: Arc<dyn BlockDevice> = Arc::new(VirtIOBlock::new(config::VIRTIO_FS));
+    let device: Arc<dyn BlockDevice> = match read_partition_table(&disk) {
+        Ok(partitions) => {
+            let find = |kind| partitions.iter().find(|entry| entry.kind == kind);
+            if let Some(entry) = find(PARTITION_TYPE_SWAP) {
+                SWAP_PARTITION.init(Arc::new(Partition::new(disk.clone(), entry)));
+            }
+            let entry = find(PARTITION_TYPE_EASY_FS).unwrap_or_else(|| {
+                panic!(
+                    "no easy-fs partition on the disk at {:#x}",
+                    config::VIRTIO_FS
+                )
+            });
+            println!(
+                "[kernel] fs: easy-fs partition at block {}, {} blocks",
+                entry.start_block, entry.blocks
+            );
+            Arc::new(Partition::new(disk, entry))
+        }
+        Err(PartitionTableError::NoMbr) => disk,
+        Err(err) => panic!(
+            "invalid partition table on the disk at {:#x}: {:?}",
+            config::VIRTIO_FS,
+            err
+        ),
+    };
+    let device: Arc<dyn BlockDevice> = match ChecksumDevice::open(device.clone()) {
+        Some(checksummed) => {
+            println!(
+                "[kernel] fs: verifying checksums of {} blocks",
+                checksummed.blocks()
+            );
+            Arc::new(checksummed)
+        }
+        None => device,
+    };
+    #[cfg(feature = "fs_overlay")]
+    let device: Arc<dyn BlockDevice> = {
+        let overlay = Arc::new(OverlayDevice::new(device));
+        OVERLAY.init(overlay.clone());
+        overlay
+    };
+    let efs = EasyFileSystem::open(device)
+        .unwrap_or_else(|| panic!("no easy-fs on the block device at {:#x}", config::VIRTIO_FS));
+    ROOT_INODE.init(Arc::new(EasyFileSystem::root_inode(&efs)));
+    #[cfg(feature = "log_file")]
+    log_file::init();
+}
+
+/// the swap partition on the disk of the file system, if there is one
+#[cfg_attr(not(feature = "swap"), allow(unused))]
+pub fn swap_partition() -> Option<Arc<Partition>> {
+    SWAP_PARTITION.get().cloned()
+}
+
+/// the names of the files in the root directory, directories excluded
+pub fn list_root() -> Vec<String> {
+    ROOT_INODE
+        .ls()
+        .into_iter()
+        .filter(|name| ROOT_INODE.find(name).is_some_and(|inode| !inode.is_dir()))
+        .collect()
+}
+
+/// Read the beginning of file `name` in the root directory into `buf`, return the
+/// number of bytes read, 0 if there is no such file.
+pub fn read_file_head(name: &str, buf: &mut [u8]) -> usize {
+    ROOT_INODE
+        .find(name)
+        .map_or(0, |inode| inode.read_at(0, buf))
+}
+
+/// Read the whole file `name` in the root directory, `None` if there is no such file.
+pub fn read_file(name: &str) -> Option<Vec<u8>> {
+    let inode = ROOT_INODE.find(name)?;
+    let mut data = Vec::new();
+    let mut buffer = [0u8; BLOCK_SZ];
+    loop {
+        let len = inode.read_at(data.len(), &mut buffer);
+        if len == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..len]);
+    }
+    Some(data)
+}
+
+/// Write the modified blocks in the block cache back to the disk, after the
+/// pending records of the log file if there is one.
+pub fn sync() {
+    #[cfg(feature = "log_file")]
+    log_file::write_pending();
+    easy_fs::sync_all();
+    LAST_SYNC_MS.store(timer::get_time_ms(), Ordering::Relaxed);
+}
+
+/// [`sync`] if it has not been done for [`config::FS_SYNC_INTERVAL_MS`], called on timer interrupts
+///
+/// 时钟中断只发生在用户态，此时内核没有持有块缓存的锁
+pub fn sync_if_due() {
+    if timer::get_time_ms() - LAST_SYNC_MS.load(Ordering::Relaxed) >= config::FS_SYNC_INTERVAL_MS {
+        sync();
+    }
+}
+
+/// Discard every write to the file system since boot, return `false` if the
+/// `fs_overlay` feature is not enabled.
+///
+/// 回滚之前打开的文件不能再使用，内核自己的日志文件在回滚之后重新打开
+pub fn rollback() -> bool {
+    #[cfg(feature = "fs_overlay")]
+    {
+        log::info!(
+            "[kernel] fs: rolled back {} written blocks",
+            OVERLAY.delta_blocks()
+        );
+        OVERLAY.rollback();
+        #[cfg(feature = "log_file")]
+        log_file::reopen();
+        true
+    }
+    #[cfg(not(feature = "fs_overlay"))]
+    false
+}
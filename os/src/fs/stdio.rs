@@ -0,0 +1,80 @@
+//! Standard input and output on the terminal
+
+use abi::{Stat, S_IFCHR};
+
+use crate::console;
+use crate::mm::UserBuffer;
+use crate::tty;
+
+use super::File;
+
+/// the terminal input, read through the line discipline in [`tty`]
+pub struct Stdin;
+
+/// the terminal output, on the user channel of the console
+pub struct Stdout;
+
+/// the status of the terminal, a character device not in the file system
+fn terminal_stat() -> Stat {
+    Stat {
+        mode: S_IFCHR | 0o620,
+        nlink: 1,
+        ..Default::default()
+    }
+}
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn wait_readable(&self) -> bool {
+        tty::wait_readable()
+    }
+
+    /// 只读入用户缓冲区的第一个连续物理片段，与其他部分读取一样由用户程序再次读取剩下的部分
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+        tty::read(buf.buffers[0])
+    }
+
+    fn write(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot write to stdin!");
+    }
+
+    fn stat(&self) -> Stat {
+        terminal_stat()
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot read from stdout!");
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        let len = buf.len();
+        for buffer in buf.buffers {
+            console::user_print(format_args!("{}", core::str::from_utf8(buffer).unwrap()));
+        }
+        len
+    }
+
+    fn stat(&self) -> Stat {
+        terminal_stat()
+    }
+}
@@ -0,0 +1,36 @@
+//! Harts of the machine
+//!
+//! 内核目前只在 SBI 启动的核上运行，其余核不会被唤醒；核的总数以设备树为准，
+//! 供用户程序按核的个数安排并行的工作
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::dtb::DeviceTree;
+
+/// 启动核的编号
+static BOOT_HART: AtomicUsize = AtomicUsize::new(0);
+/// 核的总数，设备树中找不到时认为只有启动核
+static HART_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+/// record the boot hart `hartid` and count the harts in the device tree at `dtb_pa`
+///
+/// 只能在开启分页之前调用，见 [`crate::dtb`]
+pub fn init(hartid: usize, dtb_pa: usize) {
+    BOOT_HART.store(hartid, Ordering::Relaxed);
+    let count = DeviceTree::from_pa(dtb_pa)
+        .map(|dt| dt.hart_count())
+        .unwrap_or(0)
+        .max(hartid + 1);
+    HART_COUNT.store(count, Ordering::Relaxed);
+    println!("[kernel] booted on hart {} of {}", hartid, count);
+}
+
+/// id of the hart the kernel is running on
+pub fn hart_id() -> usize {
+    BOOT_HART.load(Ordering::Relaxed)
+}
+
+/// number of harts of the machine
+pub fn hart_count() -> usize {
+    HART_COUNT.load(Ordering::Relaxed)
+}
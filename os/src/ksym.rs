@@ -0,0 +1,57 @@
+//! Kernel symbol table for address-to-name resolution
+//!
+//! The table is generated by `build.rs` from the symbols of the previous link
+//! (see `make kernel`), sorted by address. It is empty on the first pass, in
+//! which case [`resolve()`] always returns `None`.
+
+use core::arch::global_asm;
+
+global_asm!(include_str!(concat!(env!("OUT_DIR"), "/ksym.S")));
+
+extern "C" {
+    fn _ksym_num();
+    fn _ksym_addrs();
+    fn _ksym_names();
+}
+
+/// 符号表中符号的个数
+fn num_symbols() -> usize {
+    unsafe { (_ksym_num as usize as *const usize).read_volatile() }
+}
+
+fn symbol_addrs() -> &'static [usize] {
+    unsafe { core::slice::from_raw_parts(_ksym_addrs as usize as *const usize, num_symbols()) }
+}
+
+/// 第 `idx` 个符号的名字，以 `\0` 结尾存放在 .rodata 中
+fn symbol_name(idx: usize) -> &'static str {
+    unsafe {
+        let name_ptr = (_ksym_names as usize as *const *const u8)
+            .add(idx)
+            .read_volatile();
+        let mut len: usize = 0;
+        while name_ptr.add(len).read_volatile() != 0 {
+            len += 1;
+        }
+        core::str::from_utf8(core::slice::from_raw_parts(name_ptr, len)).unwrap_or("<invalid>")
+    }
+}
+
+/// Resolve a kernel text address into the name of the function containing it
+/// and the offset of `addr` from the start of that function.
+pub fn resolve(addr: usize) -> Option<(&'static str, usize)> {
+    let addrs = symbol_addrs();
+    // 最后一个起始地址不大于 `addr` 的符号
+    let idx = match addrs.binary_search(&addr) {
+        Ok(idx) => idx,
+        Err(0) => return None,
+        Err(idx) => idx - 1,
+    };
+    extern "C" {
+        fn etext();
+    }
+    if addr >= etext as usize {
+        return None;
+    }
+    Some((symbol_name(idx), addr - addrs[idx]))
+}
@@ -1,16 +1,67 @@
 use core::panic::PanicInfo;
 
+use crate::{config, ksym, task};
+
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     if let Some(location) = info.location() {
-        println!(
+        emergency_println!(
             "Panicked at {}:{} {}",
             location.file(),
             location.line(),
             info.message().unwrap()
         );
     } else {
-        println!("Panicked: {}", info.message().unwrap());
+        emergency_println!("Panicked: {}", info.message().unwrap());
+    }
+    if let Some(task) = task::try_current_desc() {
+        emergency_println!("current task: {}", task);
     }
+    crate::logging::dump_recent();
+    self::backtrace();
     crate::sbi::shutdown()
 }
+
+/// 最多回溯的栈帧数
+const MAX_BACKTRACE_DEPTH: usize = 32;
+
+/// 帧指针是否落在启动栈或者某个任务的内核栈中，避免沿着被破坏的帧指针访问未映射的地址
+fn on_kernel_stack(fp: usize) -> bool {
+    extern "C" {
+        fn sbss_with_stack();
+        fn sbss();
+    }
+    let (kernel_stacks_bottom, _) =
+        config::kernel_stack_position(task::task_count(), config::MAX_KERNEL_STACK_SIZE);
+    (sbss_with_stack as usize..=sbss as usize).contains(&fp)
+        || (kernel_stacks_bottom..=config::TRAMPOLINE).contains(&fp)
+}
+
+/// print the kernel call stack by walking the frame pointer chain
+///
+/// 内核以 `-Cforce-frame-pointers=yes` 编译，每个栈帧中 `fp - 8` 处保存返回地址，
+/// `fp - 16` 处保存上一个栈帧的帧指针
+fn backtrace() {
+    let mut fp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, s0", out(reg) fp);
+    }
+    emergency_println!("---START BACKTRACE---");
+    for depth in 0..MAX_BACKTRACE_DEPTH {
+        if fp % core::mem::size_of::<usize>() != 0 || !on_kernel_stack(fp) {
+            break;
+        }
+        let ra = unsafe { ((fp - 8) as *const usize).read_volatile() };
+        if ra == 0 {
+            break;
+        }
+        match ksym::resolve(ra) {
+            Some((name, offset)) => {
+                emergency_println!("#{} {:#x} {}+{:#x}", depth, ra, name, offset)
+            }
+            None => emergency_println!("#{} {:#x}", depth, ra),
+        }
+        fp = unsafe { ((fp - 16) as *const usize).read_volatile() };
+    }
+    emergency_println!("---END BACKTRACE---");
+}
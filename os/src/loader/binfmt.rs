@@ -0,0 +1,113 @@
+//! Executable formats understood by the loader
+//!
+//! 每种可执行文件格式实现 [`BinaryLoader`]，把文件解析为与格式无关的 [`BinaryImage`]，
+//! 再由 [`crate::mm::MemorySet::from_image`] 据此建立应用地址空间
+
+use alloc::vec::Vec;
+
+use crate::config;
+use crate::mm::{MapPermission, VirtAddr};
+
+use super::elf::ElfLoader;
+use super::flat::FlatLoader;
+
+/// error returned when an executable can not be loaded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LoadError {
+    /// 没有任何一种已知格式能识别该文件
+    UnknownFormat,
+    /// 文件格式已被识别，但内容不合法
+    Malformed(&'static str),
+    /// 文件合法，但用到了加载器尚不支持的特性
+    Unsupported(&'static str),
+}
+
+/// a piece of the executable to be mapped into user space
+pub struct Segment<'a> {
+    pub start_va: VirtAddr,
+    /// 逻辑段在内存中的终止地址，`data` 之后到此处的部分被清零
+    pub end_va: VirtAddr,
+    /// 逻辑段的访问权限，映射时总会加上 `U`
+    pub map_perm: MapPermission,
+    /// 从文件中拷贝到逻辑段开头的数据
+    pub data: &'a [u8],
+}
+
+/// sizes of the user stack and the kernel stack of an app
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StackSizes {
+    pub user: usize,
+    pub kernel: usize,
+}
+
+impl Default for StackSizes {
+    fn default() -> Self {
+        Self {
+            user: config::USER_STACK_SIZE,
+            kernel: config::KERNEL_STACK_SIZE,
+        }
+    }
+}
+
+impl StackSizes {
+    /// stack sizes requested by an app, 0 for the default size
+    ///
+    /// 申请的大小向上对齐到页，不能超过 `config` 中的上限
+    pub fn request(user: usize, kernel: usize) -> Result<Self, LoadError> {
+        fn pick(requested: usize, default: usize, max: usize) -> Result<usize, LoadError> {
+            match requested {
+                0 => Ok(default),
+                size if size <= max => Ok(size.div_ceil(config::PAGE_SIZE) * config::PAGE_SIZE),
+                _ => Err(LoadError::Unsupported("requested stack too large")),
+            }
+        }
+        Ok(Self {
+            user: pick(user, config::USER_STACK_SIZE, config::MAX_USER_STACK_SIZE)?,
+            kernel: pick(
+                kernel,
+                config::KERNEL_STACK_SIZE,
+                config::MAX_KERNEL_STACK_SIZE,
+            )?,
+        })
+    }
+}
+
+/// format independent description of a loaded executable
+pub struct BinaryImage<'a> {
+    pub segments: Vec<Segment<'a>>,
+    pub entry_point: usize,
+    /// 应用申请的栈大小，没有申请时为默认大小
+    pub stack_sizes: StackSizes,
+}
+
+/// an executable format
+pub trait BinaryLoader: Sync {
+    /// 格式的名字，用于输出日志
+    fn name(&self) -> &'static str;
+    /// whether `data` looks like an executable of this format
+    fn probe(&self, data: &[u8]) -> bool;
+    /// parse `data` into the segments to map and the entry point
+    fn load<'a>(&self, data: &'a [u8]) -> Result<BinaryImage<'a>, LoadError>;
+}
+
+/// 所有已知的可执行文件格式，按顺序探测
+static LOADERS: [&dyn BinaryLoader; 2] = [&ElfLoader, &FlatLoader];
+
+/// the number of bytes at the beginning of a file [`probe`] needs, enough for the
+/// magic of every format
+pub const PROBE_LEN: usize = 16;
+
+/// Whether some known format recognizes `data`, the beginning of a file.
+pub fn probe(data: &[u8]) -> bool {
+    LOADERS.iter().any(|loader| loader.probe(data))
+}
+
+/// Parse an executable with the first format that recognizes it.
+pub fn load(data: &[u8]) -> Result<BinaryImage<'_>, LoadError> {
+    let loader = LOADERS
+        .iter()
+        .find(|loader| loader.probe(data))
+        .ok_or(LoadError::UnknownFormat)?;
+    log::debug!("[loader] loading a {} executable", loader.name());
+    loader.load(data)
+}
@@ -0,0 +1,215 @@
+//! ELF executables
+
+use alloc::vec::Vec;
+
+use xmas_elf::program::Type;
+use xmas_elf::ElfFile;
+
+use crate::config;
+use crate::mm::{MapPermission, VirtAddr};
+
+use super::binfmt::{BinaryImage, BinaryLoader, LoadError, Segment, StackSizes};
+
+/// ELF 文件开头的魔数
+const ELF_MAGIC: [u8; 4] = [0x7f, 0x45, 0x4c, 0x46];
+/// 存放栈大小申请的 note 所在的节，见用户库中的 `stack_size!`
+const STACK_NOTE_SECTION: &str = ".note.rcore.stack";
+/// 内核识别的 note 的所有者名字
+const NOTE_OWNER: &[u8] = b"rCore\0";
+/// note 类型：申请的用户栈和内核栈大小，内容为两个小端序 u64 ，0 表示使用默认大小
+const NT_RCORE_STACK_SIZE: u32 = 1;
+
+/// 读取 `data` 中偏移为 `offset` 的小端序整数
+fn read_le<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], LoadError> {
+    data.get(offset..offset + N)
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or(LoadError::Malformed("note out of section"))
+}
+
+/// the stack sizes requested by the notes in `notes`, default if there is no such note
+///
+/// 每个 note 依次为 `namesz` 、 `descsz` 、 `type` 三个 u32 ，然后是各自按 4 字节对齐的名字和内容
+fn parse_stack_note(mut notes: &[u8]) -> Result<StackSizes, LoadError> {
+    let mut stack_sizes = StackSizes::default();
+    while !notes.is_empty() {
+        let name_size = u32::from_le_bytes(read_le(notes, 0)?) as usize;
+        let desc_size = u32::from_le_bytes(read_le(notes, 4)?) as usize;
+        let note_type = u32::from_le_bytes(read_le(notes, 8)?);
+        let desc_start = 12 + name_size.next_multiple_of(4);
+        let desc_end = desc_start + desc_size;
+        if desc_end > notes.len() {
+            return Err(LoadError::Malformed("note out of section"));
+        }
+        if &notes[12..12 + name_size] == NOTE_OWNER && note_type == NT_RCORE_STACK_SIZE {
+            if desc_size != 16 {
+                return Err(LoadError::Malformed("bad stack size note"));
+            }
+            let desc = &notes[desc_start..desc_end];
+            stack_sizes = StackSizes::request(
+                u64::from_le_bytes(read_le(desc, 0)?) as usize,
+                u64::from_le_bytes(read_le(desc, 8)?) as usize,
+            )?;
+        }
+        notes = &notes[desc_end.next_multiple_of(4).min(notes.len())..];
+    }
+    Ok(stack_sizes)
+}
+
+/// loader mapping every `PT_LOAD` program header of an ELF file
+pub struct ElfLoader;
+
+impl BinaryLoader for ElfLoader {
+    fn name(&self) -> &'static str {
+        "ELF"
+    }
+
+    fn probe(&self, data: &[u8]) -> bool {
+        data.starts_with(&ELF_MAGIC)
+    }
+
+    fn load<'a>(&self, data: &'a [u8]) -> Result<BinaryImage<'a>, LoadError> {
+        let elf = ElfFile::new(data).map_err(LoadError::Malformed)?;
+        let mut segments: Vec<Segment> = Vec::new();
+        for i in 0..elf.header.pt2.ph_count() {
+            let ph = elf.program_header(i).map_err(LoadError::Malformed)?;
+            match ph.get_type().map_err(LoadError::Malformed)? {
+                Type::Load => {}
+                // 没有文件系统可以从中加载动态链接器和共享库，只支持静态链接的应用
+                Type::Interp | Type::Dynamic => {
+                    return Err(LoadError::Unsupported("dynamically linked executable"))
+                }
+                _ => continue,
+            }
+            if ph.mem_size() == 0 {
+                continue;
+            }
+            let start = ph.offset() as usize;
+            let end = start
+                .checked_add(ph.file_size() as usize)
+                .filter(|&end| ph.file_size() <= ph.mem_size() && end <= data.len())
+                .ok_or(LoadError::Malformed("program header out of file"))?;
+            let start_va = VirtAddr::from(ph.virtual_addr() as usize);
+            let end_va = (ph.virtual_addr() as usize)
+                .checked_add(ph.mem_size() as usize)
+                .filter(|&end_va| end_va <= config::TRAP_CONTEXT)
+                .map(VirtAddr::from)
+                .ok_or(LoadError::Malformed("segment out of user space"))?;
+            // 段按页映射，两个段不能共用同一个页面
+            if segments.iter().any(|segment| {
+                segment.start_va.floor() < end_va.ceil() && start_va.floor() < segment.end_va.ceil()
+            }) {
+                return Err(LoadError::Malformed("overlapping segments"));
+            }
+            let mut map_perm = MapPermission::empty();
+            let ph_flags = ph.flags();
+            if ph_flags.is_read() {
+                map_perm |= MapPermission::R;
+            }
+            if ph_flags.is_write() {
+                map_perm |= MapPermission::W;
+            }
+            if ph_flags.is_execute() {
+                map_perm |= MapPermission::X;
+            }
+            segments.push(Segment {
+                start_va,
+                end_va,
+                map_perm,
+                data: &data[start..end],
+            });
+        }
+        let stack_sizes = match elf.find_section_by_name(STACK_NOTE_SECTION) {
+            Some(section) => parse_stack_note(section.raw_data(&elf))?,
+            None => StackSizes::default(),
+        };
+        let entry_point = elf.header.pt2.entry_point() as usize;
+        if !segments.iter().any(|segment| {
+            (usize::from(segment.start_va)..usize::from(segment.end_va)).contains(&entry_point)
+        }) {
+            return Err(LoadError::Malformed("entry point out of segments"));
+        }
+        Ok(BinaryImage {
+            segments,
+            entry_point,
+            stack_sizes,
+        })
+    }
+}
+
+/// check that malformed program headers are rejected by [`ElfLoader`], and that
+/// overlapping segments make [`crate::mm::MemorySet::from_image`] fail instead of panicking
+#[cfg(feature = "kernel_selftest")]
+pub fn elf_loader_test() {
+    use alloc::vec;
+
+    use super::binfmt::BinaryImage;
+    use crate::mm::{MapError, MemorySet};
+
+    /// 一个 `PT_LOAD` 段：文件偏移、文件中的大小、虚拟地址、内存中的大小
+    type LoadSegment = (u64, u64, u64, u64);
+    /// 只有 ELF 头部和程序头部的 RISC-V 可执行文件，补齐到 `0x200` 字节
+    fn elf(segments: &[LoadSegment], entry: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 0x200];
+        data[..8].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        data[16..18].copy_from_slice(&2u16.to_le_bytes());
+        data[18..20].copy_from_slice(&0xf3u16.to_le_bytes());
+        data[20..24].copy_from_slice(&1u32.to_le_bytes());
+        data[24..32].copy_from_slice(&entry.to_le_bytes());
+        data[32..40].copy_from_slice(&64u64.to_le_bytes());
+        data[52..54].copy_from_slice(&64u16.to_le_bytes());
+        data[54..56].copy_from_slice(&56u16.to_le_bytes());
+        data[56..58].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+        data[58..60].copy_from_slice(&64u16.to_le_bytes());
+        for (i, &(offset, file_size, vaddr, mem_size)) in segments.iter().enumerate() {
+            let ph = &mut data[64 + i * 56..64 + (i + 1) * 56];
+            ph[0..4].copy_from_slice(&1u32.to_le_bytes());
+            ph[4..8].copy_from_slice(&5u32.to_le_bytes());
+            ph[8..16].copy_from_slice(&offset.to_le_bytes());
+            ph[16..24].copy_from_slice(&vaddr.to_le_bytes());
+            ph[32..40].copy_from_slice(&file_size.to_le_bytes());
+            ph[40..48].copy_from_slice(&mem_size.to_le_bytes());
+        }
+        data
+    }
+
+    let base = 0x10000u64;
+    let page = config::PAGE_SIZE as u64;
+    let trap_context = config::TRAP_CONTEXT as u64;
+    assert!(ElfLoader
+        .load(&elf(&[(0, 0x100, base, 0x100)], base))
+        .is_ok());
+    let malformed = [
+        elf(&[(u64::MAX, 2, base, 0x100)], base),
+        elf(&[(0, 0x100, u64::MAX - 0x10, 0x100)], base),
+        elf(
+            &[(0, 0x100, trap_context - 0x80, 0x100)],
+            trap_context - 0x80,
+        ),
+        elf(
+            &[(0, 0x100, base, 0x100), (0, 0x100, base + 0x800, 0x100)],
+            base,
+        ),
+        elf(&[(0, 0x100, base, 0x100)], base + page),
+    ];
+    for data in malformed.iter() {
+        assert!(matches!(ElfLoader.load(data), Err(LoadError::Malformed(_))));
+    }
+
+    let data = [0u8; 0x100];
+    let segment = || Segment {
+        start_va: (base as usize).into(),
+        end_va: ((base + 0x100) as usize).into(),
+        map_perm: MapPermission::R | MapPermission::X,
+        data: &data,
+    };
+    let image = BinaryImage {
+        segments: vec![segment(), segment()],
+        entry_point: base as usize,
+        stack_sizes: StackSizes::default(),
+    };
+    assert!(matches!(
+        MemorySet::from_image(&image),
+        Err(MapError::Overlap { .. })
+    ));
+    println!("elf_loader_test passed!");
+}
@@ -0,0 +1,74 @@
+//! Flat binaries for tiny test payloads
+//!
+//! 文件由一个 32 字节的头部和紧随其后的原始代码数据组成，头部各字段均为小端序：
+//!
+//! | offset | field       |                                        |
+//! |--------|-------------|----------------------------------------|
+//! | 0      | `magic`     | [`FLAT_MAGIC`]                         |
+//! | 8      | `load_addr` | 数据被加载到的虚拟地址，必须页对齐      |
+//! | 16     | `entry`     | 入口地址                               |
+//! | 24     | `mem_size`  | 占用的内存大小，数据之后的部分被清零    |
+//!
+//! 整个逻辑段以 `R W X` 权限映射。
+
+use alloc::vec;
+
+use crate::config;
+use crate::mm::MapPermission;
+
+use super::binfmt::{BinaryImage, BinaryLoader, LoadError, Segment, StackSizes};
+
+/// flat 格式文件开头的魔数
+pub const FLAT_MAGIC: [u8; 8] = *b"rCoreFLT";
+/// 头部的长度
+const HEADER_SIZE: usize = 32;
+
+/// loader mapping the payload of a flat binary as a single segment
+pub struct FlatLoader;
+
+/// 读取头部中偏移为 `offset` 的字段
+fn header_field(data: &[u8], offset: usize) -> usize {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes) as usize
+}
+
+impl BinaryLoader for FlatLoader {
+    fn name(&self) -> &'static str {
+        "flat"
+    }
+
+    fn probe(&self, data: &[u8]) -> bool {
+        data.starts_with(&FLAT_MAGIC)
+    }
+
+    fn load<'a>(&self, data: &'a [u8]) -> Result<BinaryImage<'a>, LoadError> {
+        if data.len() < HEADER_SIZE {
+            return Err(LoadError::Malformed("truncated flat header"));
+        }
+        let load_addr = header_field(data, 8);
+        let entry = header_field(data, 16);
+        let mem_size = header_field(data, 24);
+        let payload = &data[HEADER_SIZE..];
+        if load_addr % config::PAGE_SIZE != 0 {
+            return Err(LoadError::Malformed("flat load address not page aligned"));
+        }
+        let end = load_addr
+            .checked_add(mem_size)
+            .filter(|&end| payload.len() <= mem_size && end <= config::TRAP_CONTEXT)
+            .ok_or(LoadError::Malformed("flat payload out of user space"))?;
+        if !(load_addr..end).contains(&entry) {
+            return Err(LoadError::Malformed("flat entry out of payload"));
+        }
+        Ok(BinaryImage {
+            segments: vec![Segment {
+                start_va: load_addr.into(),
+                end_va: end.into(),
+                map_perm: MapPermission::R | MapPermission::W | MapPermission::X,
+                data: payload,
+            }],
+            entry_point: entry,
+            stack_sizes: StackSizes::default(),
+        })
+    }
+}
@@ -0,0 +1,80 @@
+//! Loading user applications into memory
+//!
+//! Applications are the executable files in the root directory of the file system,
+//! and parsed by the `BinaryLoader` of their executable format. Other files, like
+//! the ones written by the apps, are not applications.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::fs;
+use crate::sync::UPRwCell;
+
+pub use self::binfmt::{load, BinaryImage, LoadError};
+
+use self::binfmt::PROBE_LEN;
+#[cfg(feature = "kernel_selftest")]
+pub use self::elf::elf_loader_test;
+
+mod binfmt;
+mod elf;
+mod flat;
+
+/// names of all applications, indexed by app id
+///
+/// 每次查询应用名时都只需要读访问，只在启动时写入一次
+static APP_NAMES: UPRwCell<Vec<String>> = unsafe { UPRwCell::new(Vec::new()) };
+
+/// list the executable files in the root directory of the file system into [`APP_NAMES`]
+///
+/// 只看文件开头的魔数，不是任何已知格式的文件不作为应用，否则启动时会因为无法加载而 panic
+pub fn init() {
+    let mut app_names: Vec<String> = fs::list_root()
+        .into_iter()
+        .filter(|name| {
+            let mut head = [0u8; PROBE_LEN];
+            let len = fs::read_file_head(name, &mut head);
+            let executable = binfmt::probe(&head[..len]);
+            if !executable {
+                log::debug!("[loader] {} is not an executable, skipped", name);
+            }
+            executable
+        })
+        .collect();
+    app_names.sort();
+    *APP_NAMES.write() = app_names;
+}
+
+/// Get the name of application `app_id`.
+pub fn get_app_name(app_id: usize) -> String {
+    APP_NAMES.read()[app_id].clone()
+}
+
+/// Find the application named `name`, return its app id.
+pub fn find_app(name: &str) -> Option<usize> {
+    APP_NAMES
+        .read()
+        .iter()
+        .position(|app_name| app_name == name)
+}
+
+/// Get the names of all applications, one per line in the order of app ids.
+pub fn list_apps() -> String {
+    let mut list = String::new();
+    for name in APP_NAMES.read().iter() {
+        list.push_str(name);
+        list.push('\n');
+    }
+    list
+}
+
+/// Get the total number of applications.
+pub fn get_num_app() -> usize {
+    APP_NAMES.read().len()
+}
+
+/// Read the executable of application `app_id` from the file system.
+pub fn get_app_data(app_id: usize) -> Vec<u8> {
+    let name = get_app_name(app_id);
+    fs::read_file(&name).unwrap_or_else(|| panic!("app `{}` is missing from the file system", name))
+}
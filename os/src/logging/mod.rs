@@ -0,0 +1,268 @@
+//! Kernel logging
+//!
+//! Records go through [`SimpleLogger`] to every registered [`LogSink`], each of
+//! which filters them with its own level.
+//!
+//! 连续重复出现的相同警告和错误只输出第一条，其余的在出现任何其他记录或者下一次时钟中断时
+//! 合并为一条 "last message repeated N times" ，避免用户程序反复触发同一条警告时刷屏。
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use log::{Level, LevelFilter, Metadata, Record};
+
+use crate::sync::{UPRwCell, UPSafeCell};
+
+pub use self::sink::LogSink;
+use self::sink::{ConsoleSink, RingBufferSink};
+
+mod sink;
+
+/// 最多同时注册的日志输出目标个数
+const MAX_SINKS: usize = 4;
+
+/// registered sinks, read on every log record and written rarely
+///
+/// 日志可能在输出日志的过程中被再次触发，因此使用允许嵌套读访问的 [`UPRwCell`]；
+/// 注册发生在堆初始化之前，因此使用定长数组保存
+static SINKS: UPRwCell<[Option<&'static dyn LogSink>; MAX_SINKS]> =
+    unsafe { UPRwCell::new([None; MAX_SINKS]) };
+
+static CONSOLE_SINK: ConsoleSink = ConsoleSink;
+static RING_BUFFER_SINK: RingBufferSink = RingBufferSink::new();
+
+/// 不低于这个级别的记录连续重复时被合并
+const RATE_LIMITED_LEVEL: Level = Level::Warn;
+
+/// 记录的文本只保存开头的这些字节用于比较
+const MESSAGE_PREFIX_LEN: usize = 256;
+
+/// 上一条被限流的记录
+static LAST_MESSAGE: UPSafeCell<MessageText> = unsafe { UPSafeCell::new(MessageText::new()) };
+/// 上一条被限流的记录之后被合并掉的重复次数
+static REPEATS: AtomicUsize = AtomicUsize::new(0);
+
+/// the formatted text of a record, kept to tell whether the next record repeats it
+///
+/// 哈希值不同的记录一定不同，相同时再比较文本本身。使用定长数组而不是 `String` ，不会进入堆分配器；
+/// 超过 [`MESSAGE_PREFIX_LEN`] 的部分只参与 FNV-1a 哈希和长度的比较
+#[derive(Clone, Copy)]
+struct MessageText {
+    hash: u64,
+    len: usize,
+    prefix: [u8; MESSAGE_PREFIX_LEN],
+}
+
+impl MessageText {
+    const fn new() -> Self {
+        Self {
+            hash: 0xcbf2_9ce4_8422_2325,
+            len: 0,
+            prefix: [0; MESSAGE_PREFIX_LEN],
+        }
+    }
+
+    fn of(record: &Record) -> Self {
+        let mut text = Self::new();
+        let _ = write!(text, "{}{}", record.level(), record.args());
+        text
+    }
+}
+
+impl Write for MessageText {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100_0000_01b3);
+            if self.len < MESSAGE_PREFIX_LEN {
+                self.prefix[self.len] = byte;
+            }
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for MessageText {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && self.len == other.len
+            && self.prefix[..self.len.min(MESSAGE_PREFIX_LEN)]
+                == other.prefix[..other.len.min(MESSAGE_PREFIX_LEN)]
+    }
+}
+
+struct SimpleLogger;
+
+impl SimpleLogger {
+    fn dispatch(&self, record: &Record) {
+        for sink in SINKS.read().iter().flatten() {
+            if record.level() <= sink.level() {
+                sink.log(record);
+            }
+        }
+    }
+
+    /// log how many times the last rate limited record was repeated, if it was
+    fn report_repeats(&self) {
+        let repeats = REPEATS.swap(0, Ordering::Relaxed);
+        if repeats > 0 {
+            self.dispatch(
+                &Record::builder()
+                    .level(RATE_LIMITED_LEVEL)
+                    .args(format_args!(
+                        "[kernel] last message repeated {} times",
+                        repeats
+                    ))
+                    .build(),
+            );
+        }
+    }
+}
+
+impl log::Log for SimpleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        SINKS
+            .read()
+            .iter()
+            .flatten()
+            .any(|sink| metadata.level() <= sink.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= RATE_LIMITED_LEVEL {
+            let text = MessageText::of(record);
+            // 输出日志的过程中嵌套产生的记录不参与合并
+            let repeated = LAST_MESSAGE
+                .try_exclusive_access()
+                .is_some_and(|mut last| core::mem::replace(&mut *last, text) == text);
+            if repeated {
+                REPEATS.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        // 级别较低的记录之前也要报告，重复次数因此紧跟在被合并的记录之后
+        self.report_repeats();
+        self.dispatch(record);
+    }
+
+    fn flush(&self) {
+        self.report_repeats();
+        for sink in SINKS.read().iter().flatten() {
+            sink.flush();
+        }
+    }
+}
+
+/// Log how many times the last rate limited record was repeated, called on timer
+/// interrupts so that the count is not held back until the next record.
+pub fn report_repeats() {
+    SimpleLogger.report_repeats();
+}
+
+/// Register a log sink, return `false` if there are too many sinks.
+pub fn add_sink(sink: &'static dyn LogSink) -> bool {
+    let mut sinks = SINKS.write();
+    let Some(slot) = sinks.iter_mut().find(|slot| slot.is_none()) else {
+        return false;
+    };
+    *slot = Some(sink);
+    // 只要有一个目标需要某个级别的日志，就不能在 `log` 宏中过滤掉它
+    let max_level = sinks
+        .iter()
+        .flatten()
+        .map(|sink| sink.level())
+        .max()
+        .unwrap_or(LevelFilter::Off);
+    log::set_max_level(max_level);
+    true
+}
+
+/// print the records kept in the in-memory ring buffer, used when panicking
+pub fn dump_recent() {
+    crate::console::emergency_print(format_args!("---START RECENT LOG---\n"));
+    RING_BUFFER_SINK.for_each_chunk(|chunk| {
+        crate::console::emergency_print(format_args!(
+            "{}",
+            core::str::from_utf8(chunk).unwrap_or("")
+        ))
+    });
+    crate::console::emergency_print(format_args!("---END RECENT LOG---\n"));
+}
+
+/// the most recent `max` bytes of the records kept in the in-memory ring buffer,
+/// with the repeats of the last rate limited record reported first
+pub fn recent(max: usize) -> Vec<u8> {
+    log::logger().flush();
+    RING_BUFFER_SINK.recent(max)
+}
+
+/// 早期启动日志缓冲区的大小
+const EARLY_LOG_SIZE: usize = 2048;
+
+/// messages printed by `earlyprintln!` before the logger is set up
+struct EarlyLog {
+    buf: [u8; EARLY_LOG_SIZE],
+    len: usize,
+}
+
+impl core::fmt::Write for EarlyLog {
+    /// 缓冲区满了之后丢弃后面的消息
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut n = s.len().min(EARLY_LOG_SIZE - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+// 下面两个变量放在 .data 段中，不会被 `clear_bss` 清零，在清零之前访问它们也是安全的
+#[link_section = ".data.early_log"]
+static mut EARLY_LOG: EarlyLog = EarlyLog {
+    buf: [0; EARLY_LOG_SIZE],
+    len: 0,
+};
+#[link_section = ".data.early_log"]
+static LOGGER_READY: AtomicBool = AtomicBool::new(false);
+
+/// Print through SBI before the heap and the logger are set up,
+/// and keep a copy to be replayed into the ring buffer by [`init`].
+pub fn early_print(args: core::fmt::Arguments) {
+    crate::console::emergency_print(args);
+    if !LOGGER_READY.load(Ordering::Acquire) {
+        unsafe {
+            let _ = (*core::ptr::addr_of_mut!(EARLY_LOG)).write_fmt(args);
+        }
+    }
+}
+
+/// second phase of logging initialization: set up the logger and
+/// replay the messages recorded by `earlyprintln!`
+///
+/// 这些消息已经打印到控制台上了，只重放到环形缓冲区里，让 `dmesg` 能看到完整的启动日志
+pub fn init() {
+    static LOGGER: SimpleLogger = SimpleLogger;
+    match log::set_logger(&LOGGER) {
+        Ok(_) => println!("[logging] set LOGGER success"),
+        Err(err) => panic!("set LOGGER ERROR, {}", err),
+    };
+    self::add_sink(&CONSOLE_SINK);
+    self::add_sink(&RING_BUFFER_SINK);
+    LOGGER_READY.store(true, Ordering::Release);
+    let early_log = unsafe { &*core::ptr::addr_of!(EARLY_LOG) };
+    for line in core::str::from_utf8(&early_log.buf[..early_log.len])
+        .unwrap_or_default()
+        .lines()
+    {
+        RING_BUFFER_SINK.log(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("[early] {}", line))
+                .build(),
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Destinations of kernel log records
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use log::{Level, LevelFilter, Record};
+
+use crate::sync::UPSafeCell;
+
+/// a destination of kernel log records, with its own level filter
+///
+/// Sinks are registered with [`super::add_sink`], e.g. the file-backed sink in
+/// `fs/log_file.rs` once the file system has been mounted.
+pub trait LogSink: Sync {
+    /// the most verbose level this sink wants to receive
+    fn level(&self) -> LevelFilter;
+    /// write a record whose level passes [`LogSink::level`]
+    fn log(&self, record: &Record);
+    fn flush(&self) {}
+}
+
+/// print colored records to the console, filtered by the `LOG` environment variable at build time
+pub struct ConsoleSink;
+
+impl LogSink for ConsoleSink {
+    fn level(&self) -> LevelFilter {
+        match option_env!("LOG") {
+            Some("error") => LevelFilter::Error,
+            Some("warn") => LevelFilter::Warn,
+            Some("info") => LevelFilter::Info,
+            Some("debug") => LevelFilter::Debug,
+            Some("trace") => LevelFilter::Trace,
+            _ => LevelFilter::Off,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        println!(
+            "\x1b[{}m[{}] {}\x1b[0m",
+            level_to_color_code(record.level()),
+            record.level(),
+            record.args()
+        );
+    }
+}
+
+fn level_to_color_code(level: Level) -> u8 {
+    match level {
+        Level::Error => 31, // Red
+        Level::Warn => 93,  // BrightYellow
+        Level::Info => 34,  // Blue
+        Level::Debug => 32, // Green
+        Level::Trace => 90, // BrightBlack
+    }
+}
+
+/// 环形日志缓冲区的大小
+const RING_BUFFER_SIZE: usize = 16 * 1024;
+
+struct RingBufferInner {
+    buf: [u8; RING_BUFFER_SIZE],
+    /// 下一个字节写入的位置
+    head: usize,
+    /// 缓冲区是否已经写满过一轮
+    wrapped: bool,
+}
+
+impl core::fmt::Write for RingBufferInner {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            self.buf[self.head] = byte;
+            self.head += 1;
+            if self.head == RING_BUFFER_SIZE {
+                self.head = 0;
+                self.wrapped = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// keep the most recent records of every level in memory, overwriting the oldest ones
+///
+/// 相当于一个“黑匣子”，控制台上被过滤掉的日志在 panic 时仍然可以看到
+pub struct RingBufferSink {
+    inner: UPSafeCell<RingBufferInner>,
+}
+
+impl RingBufferSink {
+    pub const fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(RingBufferInner {
+                    buf: [0; RING_BUFFER_SIZE],
+                    head: 0,
+                    wrapped: false,
+                })
+            },
+        }
+    }
+
+    /// the buffered records, oldest first, as two byte slices, nothing if the
+    /// buffer is being written, e.g. when panicking while logging
+    pub fn for_each_chunk(&self, mut f: impl FnMut(&[u8])) {
+        let Some(inner) = self.inner.try_exclusive_access() else {
+            return;
+        };
+        if inner.wrapped {
+            f(&inner.buf[inner.head..]);
+        }
+        f(&inner.buf[..inner.head]);
+    }
+
+    /// the most recent `max` bytes of the buffered records, oldest first
+    pub fn recent(&self, max: usize) -> Vec<u8> {
+        // 先分配好空间，借用缓冲区期间不再进入堆分配器
+        let max = max.min(RING_BUFFER_SIZE);
+        let mut recent = Vec::with_capacity(max);
+        let mut skip = 0;
+        self.for_each_chunk(|chunk| skip += chunk.len());
+        skip = skip.saturating_sub(max);
+        self.for_each_chunk(|chunk| {
+            let n = skip.min(chunk.len());
+            skip -= n;
+            recent.extend_from_slice(&chunk[n..]);
+        });
+        recent
+    }
+}
+
+impl LogSink for RingBufferSink {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Trace
+    }
+
+    /// 输出日志时再次输出的日志（例如格式化记录时 panic）被丢弃，而不是重复借用
+    fn log(&self, record: &Record) {
+        let Some(mut inner) = self.inner.try_exclusive_access() else {
+            return;
+        };
+        let _ = writeln!(inner, "[{}] {}", record.level(), record.args());
+    }
+}
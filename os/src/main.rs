@@ -14,32 +14,94 @@ mod board;
 #[macro_use]
 mod console;
 
+mod boot;
 mod config;
+mod drivers;
+mod dtb;
+mod fs;
+mod hart;
+mod ksym;
 mod lang_items;
 mod loader;
 mod logging;
 mod mm;
 mod sbi;
+#[cfg(feature = "kernel_selftest")]
+mod selftest;
 mod sync;
 mod syscall;
 mod task;
 mod timer;
 mod trap;
+mod tty;
+#[cfg(feature = "split_console")]
+mod uart;
+
+// 本分支的加载器把每个应用放在独立的地址空间中，批处理/多道程序内核在 ch2/ch3 分支上
+#[cfg(not(feature = "paging"))]
+compile_error!(
+    "this kernel loads every app into its own address space, the `paging` feature is required"
+);
+// `UPSafeCell` 只在单核下是安全的
+#[cfg(feature = "smp")]
+compile_error!("the kernel is uniprocessor only, the `smp` feature is not supported yet");
 
 core::arch::global_asm!(include_str!("entry.asm"));
-core::arch::global_asm!(include_str!("link_app.S"));
 
 #[no_mangle]
 /// the rust entry-point of os
-pub fn rust_main() -> ! {
+///
+/// SBI 通过 `a0` 和 `a1` 传入当前核的编号和设备树的物理地址
+pub fn rust_main(hartid: usize, dtb_pa: usize) -> ! {
+    let boot_start = timer::get_time();
+    earlyprintln!("[kernel] early console is up");
     clear_bss();
+    boot::init(boot_start);
+    boot::stage_done("clear bss");
+    earlyprintln!("[kernel] .bss cleared");
     logging::init();
+    boot::banner();
     println!("[kernel] Hello, world!");
-    mm::init();
+    boot::stage_done("logging");
+    timer::init(dtb_pa);
+    hart::init(hartid, dtb_pa);
+    boot::stage_done("timer");
+    #[cfg(feature = "split_console")]
+    let serial_ports = dtb::DeviceTree::from_pa(dtb_pa)
+        .map(|dt| dt.serial_ports())
+        .unwrap_or_default();
+    mm::init(dtb_pa);
+    #[cfg(feature = "split_console")]
+    {
+        console::split(&serial_ports);
+        boot::stage_done("console");
+    }
+    fs::init();
+    boot::stage_done("fs");
+    #[cfg(feature = "swap")]
+    {
+        // 交换区在文件系统所在磁盘的分区上，镜像没有交换区分区时不换出页面
+        match fs::swap_partition() {
+            Some(partition) => {
+                let size = partition.blocks() * drivers::BLOCK_SZ;
+                mm::init_swap(partition, size);
+            }
+            None => log::warn!("[kernel] no swap partition on the fs image, swap disabled"),
+        }
+        boot::stage_done("swap");
+    }
+    loader::init();
+    boot::stage_done("loader");
     println!("[kernel] back to world!");
     mm::remap_test();
+    #[cfg(feature = "kernel_selftest")]
+    selftest::run();
     trap::init();
     trap::enable_timer_interrupt();
+    boot::stage_done("trap");
+    task::init();
+    boot::stage_done("tasks");
+    boot::report();
     timer::set_next_trigger();
     task::run_first_task();
     panic!("Unreachable in rust_main!");
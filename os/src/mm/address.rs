@@ -272,6 +272,19 @@ where
     pub fn end(&self) -> T {
         self.end
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// 两个左闭右开区间是否有交集
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value < self.end
+    }
 }
 
 impl<T> IntoIterator for SimpleInterval<T>
@@ -0,0 +1,128 @@
+//! Fault injection for kernel allocation paths
+//!
+//! 只在 `fault_injection` 特性下编译。每个注入点可以配置为每 `every` 次分配失败一次，
+//! 或是在成功 `after` 次之后让所有分配都失败，用于检验分配失败时的处理路径。
+//!
+//! 启动时的配置来自构建时的环境变量 `FAULT_INJECT`，例如
+//! `FAULT_INJECT="frame:every=16 heap:after=100000"` ，运行时可通过系统调用修改。
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// where a fault can be injected
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FaultSite {
+    /// 物理页帧分配 `frame_alloc`
+    Frame = 0,
+    /// 内核堆分配
+    Heap = 1,
+}
+
+impl FaultSite {
+    pub fn from_usize(site: usize) -> Option<Self> {
+        match site {
+            0 => Some(FaultSite::Frame),
+            1 => Some(FaultSite::Heap),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "frame" => Some(FaultSite::Frame),
+            "heap" => Some(FaultSite::Heap),
+            _ => None,
+        }
+    }
+}
+
+/// 表示不在成功若干次之后让分配失败
+pub const NEVER: usize = usize::MAX;
+
+/// fault injection policy and counter of a site
+///
+/// 堆分配器中也会检查注入点，因此只使用原子变量，不能借用 `UPSafeCell`
+struct Injector {
+    /// 每 `every` 次分配失败一次，为 0 时不启用
+    every: AtomicUsize,
+    /// 成功 `after` 次之后所有分配都失败，为 [`NEVER`] 时不启用
+    after: AtomicUsize,
+    /// 重新配置以来的分配次数
+    count: AtomicUsize,
+    /// 重新配置以来注入的失败次数
+    injected: AtomicUsize,
+}
+
+impl Injector {
+    const fn new() -> Self {
+        Self {
+            every: AtomicUsize::new(0),
+            after: AtomicUsize::new(NEVER),
+            count: AtomicUsize::new(0),
+            injected: AtomicUsize::new(0),
+        }
+    }
+
+    fn configure(&self, every: usize, after: usize) {
+        self.every.store(every, Ordering::Relaxed);
+        self.after.store(after, Ordering::Relaxed);
+        self.count.store(0, Ordering::Relaxed);
+        self.injected.store(0, Ordering::Relaxed);
+    }
+
+    fn should_fail(&self) -> bool {
+        let every = self.every.load(Ordering::Relaxed);
+        let after = self.after.load(Ordering::Relaxed);
+        if every == 0 && after == NEVER {
+            return false;
+        }
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        let fail = count >= after || (every != 0 && (count + 1) % every == 0);
+        if fail {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+        }
+        fail
+    }
+}
+
+static INJECTORS: [Injector; 2] = [Injector::new(), Injector::new()];
+
+/// Whether the allocation at `site` should be failed on purpose.
+pub fn should_fail(site: FaultSite) -> bool {
+    INJECTORS[site as usize].should_fail()
+}
+
+/// Make every `every`-th allocation at `site` fail (0 to disable), and all of
+/// them fail after `after` successes ([`NEVER`] to disable), return the number of
+/// faults injected under the previous configuration.
+pub fn configure(site: FaultSite, every: usize, after: usize) -> usize {
+    let injector = &INJECTORS[site as usize];
+    let injected = injector.injected.load(Ordering::Relaxed);
+    injector.configure(every, after);
+    injected
+}
+
+/// apply the boot-time configuration from `FAULT_INJECT`
+pub fn init() {
+    let Some(config) = option_env!("FAULT_INJECT") else {
+        return;
+    };
+    for item in config.split_whitespace() {
+        // `<site>:every=<n>` 或 `<site>:after=<n>`
+        let parsed = item.split_once(':').and_then(|(site, rule)| {
+            let site = FaultSite::from_name(site)?;
+            let (key, value) = rule.split_once('=')?;
+            let value: usize = value.parse().ok()?;
+            let injector = &INJECTORS[site as usize];
+            match key {
+                "every" => injector.every.store(value, Ordering::Relaxed),
+                "after" => injector.after.store(value, Ordering::Relaxed),
+                _ => return None,
+            }
+            Some(())
+        });
+        match parsed {
+            Some(()) => println!("[kernel] fault injection: {}", item),
+            None => println!("[kernel] fault injection: invalid rule `{}` ignored", item),
+        }
+    }
+}
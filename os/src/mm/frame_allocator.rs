@@ -1,9 +1,9 @@
-use lazy_static::*;
-
 use ::alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::{config, sync};
+use crate::sync::{LazyInit, UPSafeCell};
+use crate::{config, timer};
 
 use super::address::{PhysAddr, PhysPageNum};
 
@@ -23,78 +23,291 @@ impl<T: FrameAllocator> FrameAllocatorImpl<T> {
     }
 }
 
+/// a contiguous range of physical frames managed by [`StackFrameAllocator`]
+#[derive(Copy, Clone, Debug)]
+pub struct FrameRange {
+    /// 起始物理页号
+    pub start: usize,
+    /// 尚未分配过的页帧的起始物理页号，`[start, current)` 中的页帧都分配过
+    pub current: usize,
+    /// 结束物理页号
+    pub end: usize,
+    /// 正在使用的页帧数
+    pub in_use: usize,
+    /// 保留而不能分配的页帧数
+    pub reserved: usize,
+}
+
+impl FrameRange {
+    fn contains(&self, ppn: usize) -> bool {
+        (self.start..self.end).contains(&ppn)
+    }
+
+    /// 可以分配的页帧总数
+    pub fn usable(&self) -> usize {
+        self.end - self.start - self.reserved
+    }
+}
+
 /// an implementation for frame allocator
 // 栈式物理页帧管理策略
 pub struct StackFrameAllocator {
-    /// 空闲内存的起始物理页号
-    current: usize,
-    /// 空闲内存的结束物理页号
-    end: usize,
+    /// 互不相交的物理内存区间，按起始页号排序，新页帧从各区间的 `current` 依次分配
+    ranges: Vec<FrameRange>,
     /// 保存被回收的物理页号
     recycled: Vec<usize>,
+    /// 不能分配的物理页号区间 `[start, end)` ，按起始页号排序
+    reserved: Vec<(usize, usize)>,
 }
 
 impl StackFrameAllocator {
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.into();
-        self.end = r.into();
+    /// manage the frames in `[l, r)` as well, merging with a range ending at `l`
+    pub fn add_range(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        let (start, end): (usize, usize) = (l.into(), r.into());
+        assert!(
+            self.ranges
+                .iter()
+                .all(|range| end <= range.start || range.end <= start),
+            "frames [{:#x}, {:#x}) are already managed",
+            start,
+            end
+        );
+        let reserved = (start..end).filter(|&ppn| self.is_reserved(ppn)).count();
+        if let Some(range) = self.ranges.iter_mut().find(|range| range.end == start) {
+            // `current` 之后的页帧都未分配过，扩展后依然如此
+            range.end = end;
+            range.reserved += reserved;
+            return;
+        }
+        let idx = self.ranges.partition_point(|range| range.start < start);
+        self.ranges.insert(
+            idx,
+            FrameRange {
+                start,
+                current: start,
+                end,
+                in_use: 0,
+                reserved,
+            },
+        );
+    }
+
+    /// never hand out the frames in `[l, r)`
+    ///
+    /// 必须在加入包含这些页帧的区间之前调用
+    pub fn reserve(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        let range: (usize, usize) = (l.into(), r.into());
+        let idx = self.reserved.partition_point(|&reserved| reserved < range);
+        self.reserved.insert(idx, range);
+    }
+
+    fn is_reserved(&self, ppn: usize) -> bool {
+        self.reserved
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&ppn))
+    }
+
+    fn range_of(&mut self, ppn: usize) -> Option<&mut FrameRange> {
+        self.ranges.iter_mut().find(|range| range.contains(ppn))
+    }
+
+    /// 从各区间中尚未分配过的页帧中分配一个，跳过保留的页帧
+    fn alloc_fresh(&mut self) -> Option<usize> {
+        for range in self.ranges.iter_mut() {
+            // 保留区间按起始页号排序，因此一次遍历即可
+            for &(start, end) in self.reserved.iter() {
+                if (start..end).contains(&range.current) {
+                    range.current = end.min(range.end);
+                }
+            }
+            if range.current < range.end {
+                range.current += 1;
+                return Some(range.current - 1);
+            }
+        }
+        None
     }
 }
 
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
-            current: 0,
-            end: 0,
+            ranges: Vec::new(),
             recycled: Vec::new(),
+            reserved: Vec::new(),
         }
     }
 
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
-        }
+        let ppn = self.recycled.pop().or_else(|| self.alloc_fresh())?;
+        self.range_of(ppn).unwrap().in_use += 1;
+        Some(ppn.into())
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn: usize = ppn.into();
         // validity check
-        if ppn >= self.current || self.recycled.iter().any(|v| *v == ppn) {
+        let allocated = self
+            .ranges
+            .iter()
+            .any(|range| (range.start..range.current).contains(&ppn));
+        if !allocated || self.is_reserved(ppn) || self.recycled.iter().any(|v| *v == ppn) {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
         // recycle
+        self.range_of(ppn).unwrap().in_use -= 1;
         self.recycled.push(ppn);
     }
 }
 
-lazy_static! {
-    /// frame allocator instance through lazy_static!
-    pub static ref FRAME_ALLOCATOR: sync::UPSafeCell<StackFrameAllocator> =
-        unsafe { sync::UPSafeCell::new(FrameAllocatorImpl::monomorphize()) };
-}
+/// frame allocator instance, created by [`init_frame_allocator`]
+pub static FRAME_ALLOCATOR: LazyInit<UPSafeCell<StackFrameAllocator>> = LazyInit::new();
 
-/// initiate the frame allocator using `ekernel` and `MEMORY_END`
-pub fn init_frame_allocator() {
+/// initiate the frame allocator using `ekernel` and `MEMORY_END`, except for the
+/// physical ranges `[start, end)` in `reserved`
+pub fn init_frame_allocator(reserved: &[(usize, usize)]) {
     extern "C" {
         fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.init(unsafe { UPSafeCell::new(FrameAllocatorImpl::monomorphize()) });
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    for &(reserved_start, reserved_end) in reserved {
+        // 与保留区间有交集的页帧都不能分配
+        allocator.reserve(
+            PhysAddr::from(reserved_start).floor(),
+            PhysAddr::from(reserved_end).ceil(),
+        );
+    }
+    drop(allocator);
+    add_frames(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(config::MEMORY_END).floor(),
     );
 }
 
-/// allocate a frame
+/// hand the frames in `[l, r)` to the frame allocator
+///
+/// 在释放时清零的策略下，分配出去的页帧不再清零，因此先清零这些页帧
+pub fn add_frames(l: PhysPageNum, r: PhysPageNum) {
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    #[cfg(feature = "frame_zero_on_free")]
+    {
+        let zero_start = timer::get_time_us();
+        let mut zeroed: usize = 0;
+        for ppn in (usize::from(l)..usize::from(r)).filter(|&ppn| !allocator.is_reserved(ppn)) {
+            PhysPageNum::from(ppn).as_bytes_mut().fill(0);
+            zeroed += 1;
+        }
+        println!(
+            "[kernel] zeroed {} free frames in {}us",
+            zeroed,
+            timer::get_time_us() - zero_start
+        );
+    }
+    allocator.add_range(l, r);
+}
+
+/// how fragmented the free frames are
+#[derive(Copy, Clone, Debug)]
+pub struct FrameFragmentation {
+    /// 空闲页帧总数
+    pub free: usize,
+    /// 物理页号连续的空闲页帧的最大个数
+    pub largest_free_run: usize,
+    /// 被回收而等待再次分配的页帧数
+    pub recycled: usize,
+}
+
+impl StackFrameAllocator {
+    /// 统计空闲页帧的碎片情况：回收的页帧和各区间中未分配过的页帧都是空闲的
+    fn fragmentation(&self) -> FrameFragmentation {
+        let mut free_runs: Vec<(usize, usize)> =
+            self.recycled.iter().map(|&ppn| (ppn, ppn + 1)).collect();
+        for range in self.ranges.iter() {
+            let mut start = range.current;
+            for &(reserved_start, reserved_end) in self.reserved.iter() {
+                if reserved_end <= start || reserved_start >= range.end {
+                    continue;
+                }
+                if reserved_start > start {
+                    free_runs.push((start, reserved_start));
+                }
+                start = reserved_end.min(range.end);
+            }
+            if start < range.end {
+                free_runs.push((start, range.end));
+            }
+        }
+        free_runs.sort_unstable();
+        let mut free = 0;
+        let mut largest_free_run = 0;
+        let mut run: Option<(usize, usize)> = None;
+        for (start, end) in free_runs {
+            free += end - start;
+            run = match run {
+                Some((run_start, run_end)) if run_end == start => Some((run_start, end)),
+                _ => Some((start, end)),
+            };
+            let (run_start, run_end) = run.unwrap();
+            largest_free_run = largest_free_run.max(run_end - run_start);
+        }
+        FrameFragmentation {
+            free,
+            largest_free_run,
+            recycled: self.recycled.len(),
+        }
+    }
+}
+
+/// how fragmented the free frames currently are
+pub fn frame_fragmentation() -> FrameFragmentation {
+    FRAME_ALLOCATOR.exclusive_access().fragmentation()
+}
+
+/// every range of physical frames managed by the frame allocator
+pub fn frame_ranges() -> Vec<FrameRange> {
+    FRAME_ALLOCATOR.exclusive_access().ranges.clone()
+}
+
+/// 启动之后清零的页帧数
+static ZEROED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+/// 启动之后清零页帧所用的时间，单位为 `mtime` 的计数
+static ZEROING_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// 清零一个页帧，并计入清零的统计
+fn zero_frame(ppn: PhysPageNum) {
+    let start = timer::get_time();
+    ppn.as_bytes_mut().fill(0);
+    ZEROED_FRAMES.fetch_add(1, Ordering::Relaxed);
+    ZEROING_TICKS.fetch_add(timer::get_time() - start, Ordering::Relaxed);
+}
+
+/// frames zeroed since boot and the time spent zeroing them in `us`,
+/// to compare the zeroing policies on the same workload
+pub fn zeroing_stats() -> (usize, usize) {
+    let ticks = ZEROING_TICKS.load(Ordering::Relaxed);
+    (
+        ZEROED_FRAMES.load(Ordering::Relaxed),
+        ticks / (timer::clock_freq() / timer::MICRO_PER_SEC),
+    )
+}
+
+/// allocate a frame, swapping a page out to free one if there is no free frame
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(FrameTracker::new)
+    #[cfg(feature = "fault_injection")]
+    if super::fault_inject::should_fail(super::fault_inject::FaultSite::Frame) {
+        return None;
+    }
+    loop {
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+        if let Some(ppn) = ppn {
+            return Some(FrameTracker::new(ppn));
+        }
+        // 换出页面时会释放页帧，不能持有物理页帧管理器
+        if !super::swap::evict_one() {
+            return None;
+        }
+    }
 }
 
 /// deallocate a frame
@@ -103,19 +316,25 @@ fn frame_dealloc(ppn: PhysPageNum) {
 }
 
 /// manage a frame which has the same lifecycle as the tracker
+///
+/// 新分配的页帧总是全零：默认在分配时清零，`frame_zero_on_free` 特性下改为在释放时清零，
+/// 把清零的开销从分配路径（如创建地址空间）移到释放路径（如应用退出）
 pub struct FrameTracker {
     pub ppn: PhysPageNum,
 }
 
 impl FrameTracker {
     pub fn new(ppn: PhysPageNum) -> Self {
-        ppn.as_bytes_mut().fill(0);
+        #[cfg(not(feature = "frame_zero_on_free"))]
+        self::zero_frame(ppn);
         Self { ppn }
     }
 }
 
 impl Drop for FrameTracker {
     fn drop(&mut self) {
+        #[cfg(feature = "frame_zero_on_free")]
+        self::zero_frame(self.ppn);
         self::frame_dealloc(self.ppn);
     }
 }
@@ -1,29 +1,132 @@
 use buddy_system_allocator::LockedHeap;
 
 use core;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::config::KERNEL_HEAP_SIZE;
+use crate::config::{KERNEL_HEAP_RESERVE_SIZE, KERNEL_HEAP_SIZE};
+
+use super::slab::SlabAllocator;
+
+/// 伙伴系统的阶数，最大的块为 `2^(HEAP_ORDER - 1)` 字节
+const HEAP_ORDER: usize = 32;
 
-#[global_allocator]
 /// heap allocator instance
-static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
+static HEAP_ALLOCATOR: LockedHeap<HEAP_ORDER> = LockedHeap::empty();
+
+/// kernel allocator instance, small objects are served by its slab caches
+static KERNEL_ALLOCATOR: SlabAllocator<LockedHeap<HEAP_ORDER>> =
+    SlabAllocator::new(&HEAP_ALLOCATOR);
 
 /// heap space ([u8; KERNEL_HEAP_SIZE])
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
+/// 内核堆耗尽时使用的后备堆
+static RESERVE_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
+
+/// reserve space ([u8; KERNEL_HEAP_RESERVE_SIZE])
+static mut RESERVE_SPACE: [u8; KERNEL_HEAP_RESERVE_SIZE] = [0; KERNEL_HEAP_RESERVE_SIZE];
+
+/// 是否有分配请求由后备堆满足，且还没有被 [`take_heap_exhausted`] 处理
+static HEAP_EXHAUSTED: AtomicBool = AtomicBool::new(false);
+
+/// the kernel heap falling back to a small reserve when it is exhausted
+///
+/// 内核堆分配失败时改为从后备堆中分配，使得正在进行的内核操作能够完成（例如输出诊断信息），
+/// 然后在返回用户态之前杀死当前应用，而不是立即 panic ；后备堆也耗尽时才会 panic
+struct ReservedHeap;
+
+#[global_allocator]
+static GLOBAL_ALLOCATOR: ReservedHeap = ReservedHeap;
+
+fn in_reserve(ptr: *mut u8) -> bool {
+    let start = unsafe { RESERVE_SPACE.as_ptr() } as usize;
+    (start..start + KERNEL_HEAP_RESERVE_SIZE).contains(&(ptr as usize))
+}
+
+unsafe impl GlobalAlloc for ReservedHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = KERNEL_ALLOCATOR.alloc(layout);
+        if !ptr.is_null() {
+            return ptr;
+        }
+        let ptr = RESERVE_ALLOCATOR.alloc(layout);
+        if !ptr.is_null() {
+            HEAP_EXHAUSTED.store(true, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if in_reserve(ptr) {
+            RESERVE_ALLOCATOR.dealloc(ptr, layout);
+        } else {
+            KERNEL_ALLOCATOR.dealloc(ptr, layout);
+        }
+    }
+}
+
 /// initiate heap allocator
 pub fn init_heap() {
     unsafe {
         HEAP_ALLOCATOR
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+        RESERVE_ALLOCATOR
+            .lock()
+            .init(RESERVE_SPACE.as_ptr() as usize, KERNEL_HEAP_RESERVE_SIZE);
+    }
+}
+
+/// whether the kernel heap has been exhausted and the reserve used since the last call
+pub fn take_heap_exhausted() -> bool {
+    HEAP_EXHAUSTED.swap(false, Ordering::Relaxed)
+}
+
+/// bytes of the reserve in use
+pub fn reserve_in_use() -> usize {
+    RESERVE_ALLOCATOR.lock().stats_alloc_actual()
+}
+
+/// count the free blocks of the kernel heap, `2^k` bytes ones at index `k`
+///
+/// 伙伴系统不公开空闲链表，因此从大到小依次分配各阶的块直到失败：更大的块都已分配完时，
+/// 某一阶的分配只会取走恰好该阶的空闲块，分配到的个数即该阶空闲块的个数。
+/// 随后全部释放，相邻的伙伴重新合并，堆恢复原状
+pub fn heap_free_blocks() -> [usize; HEAP_ORDER] {
+    let mut heap = HEAP_ALLOCATOR.lock();
+    let mut histogram = [0; HEAP_ORDER];
+    // 各阶分配到的块串成链表，每个块的开头保存下一个块的地址，探测时不能再使用堆
+    let mut taken: [Option<NonNull<u8>>; HEAP_ORDER] = [None; HEAP_ORDER];
+    let min_order = size_of::<usize>().trailing_zeros() as usize;
+    for order in (min_order..HEAP_ORDER).rev() {
+        let layout = Layout::from_size_align(1 << order, size_of::<usize>()).unwrap();
+        while let Ok(block) = heap.alloc(layout) {
+            unsafe { (block.as_ptr() as *mut Option<NonNull<u8>>).write(taken[order]) };
+            taken[order] = Some(block);
+            histogram[order] += 1;
+        }
     }
+    for (order, head) in taken.into_iter().enumerate() {
+        let layout = Layout::from_size_align(1 << order, size_of::<usize>()).unwrap();
+        let mut next = head;
+        while let Some(block) = next {
+            next = unsafe { (block.as_ptr() as *const Option<NonNull<u8>>).read() };
+            heap.dealloc(block, layout);
+        }
+    }
+    histogram
 }
 
 #[alloc_error_handler]
-/// panic when heap allocation error occurs
+/// panic when heap allocation error occurs, even the reserve is exhausted
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
-    panic!("Heap allocation error, layout = {:?}", layout);
+    panic!(
+        "Heap allocation error, reserve exhausted too, layout = {:?}",
+        layout
+    );
 }
 
 #[allow(unused)]
@@ -48,5 +151,71 @@ pub fn heap_test() {
     }
     assert!(bss_range.contains(&(v.as_ptr() as usize)));
     drop(v);
+    for (obj_size, stats) in KERNEL_ALLOCATOR.stats() {
+        println!(
+            "slab-{:<4} allocs {:>6} frees {:>6} in use {:>6} slabs {:>4}",
+            obj_size, stats.allocs, stats.frees, stats.in_use, stats.slabs
+        );
+    }
     println!("heap_test passed!");
 }
+
+/// allocate and free blocks of random sizes and alignments, checking that
+/// live blocks never overlap and every slab object is returned at the end
+#[cfg(feature = "kernel_selftest")]
+pub fn heap_fuzz_test(rng: &mut crate::selftest::Rng) {
+    use ::alloc::alloc::{alloc, dealloc, Layout};
+    use ::alloc::vec::Vec;
+
+    /// 同时存活的块的最大个数
+    const MAX_LIVE: usize = 64;
+
+    let in_use_before = KERNEL_ALLOCATOR.stats().map(|(_, stats)| stats.in_use);
+    // (块的起始地址, 布局, 填充的字节)
+    let mut live: Vec<(*mut u8, Layout, u8)> = Vec::with_capacity(MAX_LIVE);
+    let check_and_free = |(ptr, layout, fill): (*mut u8, Layout, u8)| unsafe {
+        let block = core::slice::from_raw_parts(ptr, layout.size());
+        assert!(
+            block.iter().all(|&byte| byte == fill),
+            "block {:#x} {:?} was overwritten",
+            ptr as usize,
+            layout
+        );
+        dealloc(ptr, layout);
+    };
+    for round in 0..4000 {
+        if live.len() == MAX_LIVE || (!live.is_empty() && rng.below(3) == 0) {
+            let victim = live.swap_remove(rng.below(live.len()));
+            check_and_free(victim);
+            continue;
+        }
+        // 大多数是会由 slab 满足的小块，少数交给伙伴系统
+        let size = match rng.below(8) {
+            0 => 2049 + rng.below(32 * 1024),
+            _ => 1 + rng.below(2048),
+        };
+        let align = 1 << rng.below(13);
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let ptr = unsafe { alloc(layout) };
+        assert!(
+            !ptr.is_null(),
+            "out of heap at round {} for {:?}",
+            round,
+            layout
+        );
+        assert_eq!(
+            ptr as usize % align,
+            0,
+            "{:?} misaligned at {:#x}",
+            layout,
+            ptr as usize
+        );
+        let fill = round as u8;
+        unsafe { ptr.write_bytes(fill, size) };
+        live.push((ptr, layout, fill));
+    }
+    live.drain(..).for_each(check_and_free);
+    let in_use_after = KERNEL_ALLOCATOR.stats().map(|(_, stats)| stats.in_use);
+    assert_eq!(in_use_before, in_use_after, "slab objects leaked");
+    println!("heap_fuzz_test passed!");
+}
@@ -0,0 +1,168 @@
+//! Boot-time validation of the kernel memory layout
+//!
+//! 在物理页帧分配器接管内存之前，检查内核各段、MMIO 区间、跳板页面以及物理页帧分配器
+//! 管理的内存范围是否页对齐、是否相互重叠，打印内存布局表，发现冲突时拒绝启动
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::config;
+use crate::dtb::DeviceTree;
+
+extern "C" {
+    fn stext();
+    fn etext();
+    fn srodata();
+    fn erodata();
+    fn sdata();
+    fn edata();
+    fn sbss_with_stack();
+    fn ebss();
+    fn ekernel();
+    fn strampoline();
+}
+
+/// 一段左闭右开的物理地址区间 `[start, end)`
+struct Region {
+    name: &'static str,
+    start: usize,
+    end: usize,
+}
+
+impl Region {
+    fn new(name: &'static str, start: usize, end: usize) -> Self {
+        Self { name, start, end }
+    }
+
+    fn aligned(&self) -> bool {
+        self.start % config::PAGE_SIZE == 0 && self.end % config::PAGE_SIZE == 0
+    }
+
+    fn overlaps(&self, other: &Region) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// 所有在内核地址空间中被恒等映射、彼此之间不允许重叠的物理内存区间
+fn kernel_regions() -> Vec<Region> {
+    let mut regions: Vec<Region> = vec![
+        Region::new(".text", stext as usize, etext as usize),
+        Region::new(".rodata", srodata as usize, erodata as usize),
+        Region::new(".data", sdata as usize, edata as usize),
+        Region::new(".bss", sbss_with_stack as usize, ebss as usize),
+        Region::new("frames", ekernel as usize, config::MEMORY_END),
+    ];
+    for &(start, len) in config::MMIO {
+        regions.push(Region::new("MMIO", start, start + len));
+    }
+    regions
+}
+
+/// print the kernel memory map and refuse to boot if any region is misaligned or overlaps another
+pub fn check_layout() {
+    let regions: Vec<Region> = kernel_regions();
+    println!("[kernel] memory layout:");
+    for region in regions.iter() {
+        println!(
+            "{:>12} [{:#x}, {:#x}) {:>8} KiB",
+            region.name,
+            region.start,
+            region.end,
+            region.end.saturating_sub(region.start) / 1024
+        );
+    }
+    println!(
+        "{:>12} [{:#x}, {:#x}) mapped at {:#x}",
+        "trampoline",
+        strampoline as usize,
+        strampoline as usize + config::PAGE_SIZE,
+        config::TRAMPOLINE
+    );
+
+    let mut conflicts: usize = 0;
+    for (i, region) in regions.iter().enumerate() {
+        if region.start > region.end {
+            println!("[kernel] {} ends before it starts", region.name);
+            conflicts += 1;
+        }
+        if !region.aligned() {
+            println!("[kernel] {} is not page aligned", region.name);
+            conflicts += 1;
+        }
+        for other in regions[i + 1..].iter() {
+            if region.overlaps(other) {
+                println!(
+                    "[kernel] {} [{:#x}, {:#x}) overlaps {} [{:#x}, {:#x})",
+                    region.name, region.start, region.end, other.name, other.start, other.end
+                );
+                conflicts += 1;
+            }
+        }
+    }
+    // 跳板页面必须是 .text 段中单独的一个物理页
+    let trampoline = strampoline as usize;
+    if trampoline % config::PAGE_SIZE != 0
+        || trampoline < stext as usize
+        || trampoline + config::PAGE_SIZE > etext as usize
+    {
+        println!(
+            "[kernel] trampoline {:#x} is not an aligned page of .text",
+            trampoline
+        );
+        conflicts += 1;
+    }
+    if conflicts != 0 {
+        panic!(
+            "{} conflicts found in memory layout, refuse to boot!",
+            conflicts
+        );
+    }
+    println!("layout check passed!");
+}
+
+/// physical ranges `[start, end)` the frame allocator must not hand out: the regions
+/// reserved by the device tree at `dtb_pa` (including the device tree itself) and
+/// [`config::RESERVED_MEMORY`]
+///
+/// 只能在开启分页之前调用，见 [`crate::dtb`]
+pub fn reserved_regions(dtb_pa: usize) -> Vec<(usize, usize)> {
+    let mut reserved: Vec<(usize, usize)> = DeviceTree::from_pa(dtb_pa)
+        .map(|dt| dt.reserved_regions())
+        .unwrap_or_default();
+    reserved.extend(
+        config::RESERVED_MEMORY
+            .iter()
+            .map(|&(start, len)| (start, start + len)),
+    );
+    reserved.sort();
+    for &(start, end) in reserved.iter() {
+        println!(
+            "{:>12} [{:#x}, {:#x}) {:>8} KiB",
+            "reserved",
+            start,
+            end,
+            (end - start) / 1024
+        );
+    }
+    reserved
+}
+
+/// physical memory ranges `[start, end)` reported by the device tree at `dtb_pa`,
+/// empty if the device tree can not be found
+///
+/// 只能在开启分页之前调用，见 [`crate::dtb`]
+pub fn memory_regions(dtb_pa: usize) -> Vec<(usize, usize)> {
+    let regions: Vec<(usize, usize)> = DeviceTree::from_pa(dtb_pa)
+        .map(|dt| dt.memory_regions())
+        .unwrap_or_default();
+    for &(start, end) in regions.iter() {
+        println!(
+            "{:>12} [{:#x}, {:#x}) {:>8} KiB",
+            "memory",
+            start,
+            end,
+            (end - start) / 1024
+        );
+    }
+    regions
+}
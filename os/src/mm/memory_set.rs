@@ -1,20 +1,23 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
 use bitflags::*;
-use lazy_static::*;
 use riscv::register::satp;
 
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch;
+use core::fmt::{self, Write};
 
 use crate::config;
-use crate::sync::UPSafeCell;
+use crate::loader::BinaryImage;
+use crate::sync::{LazyInit, UPRefMut, UPSafeCell};
 
 use super::address::{PhysAddr, PhysPageNum, VPNInterval, VirtAddr, VirtPageNum};
 use super::frame_allocator::{frame_alloc, FrameTracker};
 use super::page_table::{PTEFlags, PageTable, PageTableEntry};
+use super::swap::{self, PinnedPage, SwapPage};
 
 extern "C" {
     fn stext();
@@ -29,10 +32,14 @@ extern "C" {
     fn strampoline();
 }
 
-lazy_static! {
-    /// a memory set instance through lazy_static! managing kernel space
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+/// the memory set managing kernel space, created by [`init_kernel_space`]
+pub static KERNEL_SPACE: LazyInit<Arc<UPSafeCell<MemorySet>>> = LazyInit::new();
+
+/// create kernel space, the frame allocator must have been initialized
+pub fn init_kernel_space() {
+    KERNEL_SPACE.init(Arc::new(unsafe {
+        UPSafeCell::new(MemorySet::new_kernel())
+    }));
 }
 
 /// memory set structure, controls virtual-memory space
@@ -41,6 +48,8 @@ pub struct MemorySet {
     page_table: PageTable,
     /// 逻辑段中的数据所在的物理页帧
     areas: Vec<MapArea>,
+    /// 当前系统调用的用户缓冲区中被固定的页面，见 [`MemorySet::populate_range`]
+    pinned: Vec<PinnedPage>,
 }
 
 impl MemorySet {
@@ -49,28 +58,267 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            pinned: Vec::new(),
         }
     }
 
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    /// 映射逻辑段 `map_area` 并拷贝初始数据 `data` ，物理页帧耗尽时不留下任何映射
+    fn try_push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> Result<(), MapError> {
+        if !map_area.map(&mut self.page_table) {
+            return Err(MapError::OutOfFrames);
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        Ok(())
+    }
+
+    /// 用于内核地址空间，物理页帧耗尽时 panic
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) {
+        self.try_push(map_area, data)
+            .unwrap_or_else(|err| panic!("failed to map kernel area: {:?}", err));
+    }
+
+    /// Check that `vpn_interval` lies below the trampoline and overlaps no existing area.
+    ///
+    /// 跳板页面不在 `areas` 中，需要单独检查；应用地址空间中的 Trap 上下文是 `areas` 中的一个逻辑段，
+    /// 而内核地址空间没有映射 Trap 上下文，其虚拟地址可能被内核栈占用
+    fn check_free(&self, vpn_interval: &VPNInterval) -> Result<(), MapError> {
+        if vpn_interval.is_empty() {
+            return Err(MapError::Empty);
+        }
+        let trampoline_vpn: VirtPageNum = VirtAddr::from(config::TRAMPOLINE).floor();
+        if vpn_interval.end() > trampoline_vpn {
+            return Err(MapError::OutOfBounds);
+        }
+        match self
+            .areas
+            .iter()
+            .find(|area| area.vpn_interval.overlaps(vpn_interval))
+        {
+            Some(area) => Err(MapError::Overlap {
+                start: area.vpn_interval.start(),
+                end: area.vpn_interval.end(),
+            }),
+            None => Ok(()),
+        }
     }
 
-    /// Assume that no conflicts.
+    /// Insert a framed area, fail without mapping anything if it conflicts
+    /// with existing areas or the trampoline.
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
-        self.push(
-            MapArea::new(start_va, end_va, MapType::Framed, permission),
-            None,
+    ) -> Result<(), MapError> {
+        let map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        self.check_free(&map_area.vpn_interval)?;
+        self.try_push(map_area, None)
+    }
+
+    /// Map `[start_va, end_va)` to the physical memory at the same addresses,
+    /// fail without mapping anything if it conflicts with existing areas or the trampoline.
+    ///
+    /// 修改的若是当前正在使用的地址空间，需要再次调用 [`MemorySet::activate`] 刷新快表
+    pub fn insert_identical_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> Result<(), MapError> {
+        let map_area = MapArea::new(start_va, end_va, MapType::Identical, permission);
+        self.check_free(&map_area.vpn_interval)?;
+        self.try_push(map_area, None)
+    }
+
+    /// Unmap the area starting at `start_vpn` and free its frames,
+    /// return `false` if there is no such area.
+    ///
+    /// 修改的若是当前正在使用的地址空间，需要再次调用 [`MemorySet::activate`] 刷新快表
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) -> bool {
+        let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_interval.start() == start_vpn)
+        else {
+            return false;
+        };
+        let mut area = self.areas.swap_remove(idx);
+        area.unmap(&mut self.page_table);
+        true
+    }
+
+    /// Whether every byte of `[start, start + len)` lies in user areas allowing `access`.
+    ///
+    /// 按逻辑段而不是页表检查，页表项没有区分内核和用户可访问之外的信息
+    pub fn user_range_accessible(&self, start: usize, len: usize, access: UserAccess) -> bool {
+        if len == 0 {
+            return true;
+        }
+        let Some(end) = start
+            .checked_add(len)
+            .filter(|&end| end <= config::USER_SPACE_END)
+        else {
+            return false;
+        };
+        let required = match access {
+            UserAccess::Read => MapPermission::U | MapPermission::R,
+            UserAccess::Write => MapPermission::U | MapPermission::R | MapPermission::W,
+        };
+        let mut vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        while vpn < end_vpn {
+            match self
+                .areas
+                .iter()
+                .find(|area| area.vpn_interval.contains(vpn))
+            {
+                Some(area) if area.map_perm.contains(required) => vpn = area.vpn_interval.end(),
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Allocate the frames of the pages in lazy areas in `[start, start + len)` not
+    /// accessed yet, so that the kernel can access them through the page table,
+    /// return `false` if out of frames.
+    ///
+    /// 只用于已经由 [`MemorySet::user_range_accessible`] 检查过的范围。这些页面被固定在内存中，
+    /// 换入后面的页面时不会换出前面的，直到 [`MemorySet::unpin_all`]
+    pub fn populate_range(&mut self, start: usize, len: usize) -> bool {
+        if len == 0 {
+            return true;
+        }
+        let range = VPNInterval::new(
+            VirtAddr::from(start).floor(),
+            VirtAddr::from(start + len).ceil(),
         );
+        let page_table = &mut self.page_table;
+        let pinned = &mut self.pinned;
+        for area in self
+            .areas
+            .iter_mut()
+            .filter(|area| area.map_type == MapType::Lazy && area.vpn_interval.overlaps(&range))
+        {
+            let overlap = VPNInterval::new(
+                range.start().max(area.vpn_interval.start()),
+                range.end().min(area.vpn_interval.end()),
+            );
+            for vpn in overlap {
+                if !area.is_resident(vpn) && !area.populate(page_table, vpn) {
+                    return false;
+                }
+                pinned.push(SwapPage::pin(&area.lazy_pages[&vpn]));
+            }
+        }
+        true
+    }
+
+    /// Release the pages pinned by [`MemorySet::populate_range`].
+    pub fn unpin_all(&mut self) {
+        self.pinned.clear();
+    }
+
+    /// Handle a page fault at `va` lacking `permission` from user mode by allocating
+    /// the frame of a lazy page on first access or swapping it back in, return
+    /// `false` if the access is not allowed or out of frames.
+    ///
+    /// 新建立的页表项原本是无效的，返回用户态时 `__restore` 切换地址空间会刷新快表
+    pub fn handle_lazy_fault(&mut self, va: VirtAddr, permission: MapPermission) -> bool {
+        let vpn = va.floor();
+        let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_interval.contains(vpn))
+        else {
+            return false;
+        };
+        if area.map_type != MapType::Lazy
+            || !area.map_perm.contains(permission | MapPermission::U)
+            || area.is_resident(vpn)
+        {
+            return false;
+        }
+        area.populate(&mut self.page_table, vpn)
+    }
+
+    /// Map `[start_va, end_va)` for `mmap` with frames allocated on first access,
+    /// fail without mapping anything if it conflicts with existing areas or the trampoline.
+    pub fn mmap(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> Result<(), MapError> {
+        let map_area =
+            MapArea::new(start_va, end_va, MapType::Lazy, permission).with_kind(AreaKind::Mmap);
+        self.check_free(&map_area.vpn_interval)?;
+        self.try_push(map_area, None)
+    }
+
+    /// Move the end of the heap area starting at `start_va` to `end_va`, mapping the
+    /// added pages lazily or unmapping the dropped ones, fail without changing anything
+    /// if the grown heap would conflict with other areas or the trampoline.
+    pub fn resize_heap(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> Result<(), MapError> {
+        let start = start_va.floor();
+        let new_end = end_va.ceil();
+        let area = self
+            .areas
+            .iter()
+            .position(|area| area.kind == AreaKind::Heap && area.vpn_interval.start() == start)
+            .expect("no heap area");
+        let end = self.areas[area].vpn_interval.end();
+        if new_end > end {
+            self.check_free(&VPNInterval::new(end, new_end))?;
+        }
+        self.areas[area].resize(&mut self.page_table, new_end);
+        Ok(())
+    }
+
+    /// Unmap the areas mapped by [`MemorySet::mmap`] in `[start_va, end_va)` and
+    /// free their frames, return `false` without unmapping anything unless the
+    /// range is covered exactly by such areas.
+    ///
+    /// 不支持只解除一个逻辑段中的部分页面
+    pub fn munmap(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let range = VPNInterval::new(start_va.floor(), end_va.ceil());
+        if range.is_empty() {
+            return false;
+        }
+        let mut covered = 0;
+        for area in self
+            .areas
+            .iter()
+            .filter(|area| area.vpn_interval.overlaps(&range))
+        {
+            let (start, end) = (area.vpn_interval.start(), area.vpn_interval.end());
+            if area.kind != AreaKind::Mmap || start < range.start() || end > range.end() {
+                return false;
+            }
+            covered += usize::from(end) - usize::from(start);
+        }
+        if covered != usize::from(range.end()) - usize::from(range.start()) {
+            return false;
+        }
+        let (unmapped, kept) = core::mem::take(&mut self.areas)
+            .into_iter()
+            .partition(|area| area.vpn_interval.overlaps(&range));
+        self.areas = kept;
+        for mut area in unmapped {
+            area.unmap(&mut self.page_table);
+        }
+        true
+    }
+
+    /// Free the frames of every area, keeping the page table until the address
+    /// space is dropped.
+    ///
+    /// 用于已经退出的应用：它不会再运行，页表中的映射无需逐个删除
+    pub fn recycle_data_pages(&mut self) {
+        self.areas.clear();
     }
 
     /// 启用分页模式就
@@ -84,20 +332,20 @@ impl MemorySet {
     }
 
     /// Mention that trampoline is not collected by areas.
-    /// 跳板
-    fn map_trampoline(&mut self) {
+    /// 跳板，物理页帧耗尽时返回 `false`
+    fn map_trampoline(&mut self) -> bool {
         self.page_table.map(
             VirtAddr::from(config::TRAMPOLINE).into(),
             PhysAddr::from(self::strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )
     }
 
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
         // map trampoline
-        memory_set.map_trampoline();
+        assert!(memory_set.map_trampoline(), "out of frames");
         // map kernel sections
         println!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
         println!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
@@ -171,73 +419,131 @@ impl MemorySet {
         memory_set
     }
 
-    /// Include sections in elf and trampoline and TrapContext and user stack,
+    /// Include segments of an executable image and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set: MemorySet = Self::new_bare();
+    ///
+    /// 物理页帧耗尽时返回 [`MapError::OutOfFrames`] ，段互相重叠或者用户栈放不下时返回相应的错误，
+    /// 已经分配的页帧随地址空间一起回收
+    pub fn from_image(image: &BinaryImage) -> Result<(Self, usize, usize), MapError> {
+        let mut memory_set = MemorySet {
+            page_table: PageTable::try_new().ok_or(MapError::OutOfFrames)?,
+            areas: Vec::new(),
+            pinned: Vec::new(),
+        };
         // map trampoline
-        memory_set.map_trampoline();
-        // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic: [u8; 4] = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
+        if !memory_set.map_trampoline() {
+            return Err(MapError::OutOfFrames);
+        }
+        // map segments of the executable, with U flag
         let mut max_end_vpn: VirtPageNum = 0usize.into();
-        for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let mut map_perm = MapPermission::U;
-                let ph_flags = ph.flags();
-                if ph_flags.is_read() {
-                    map_perm |= MapPermission::R;
-                }
-                if ph_flags.is_write() {
-                    map_perm |= MapPermission::W;
-                }
-                if ph_flags.is_execute() {
-                    map_perm |= MapPermission::X;
-                }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_interval.end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
-            }
+        for segment in image.segments.iter() {
+            let map_area = MapArea::new(
+                segment.start_va,
+                segment.end_va,
+                MapType::Framed,
+                segment.map_perm | MapPermission::U,
+            )
+            .with_kind(AreaKind::Image);
+            max_end_vpn = max_end_vpn.max(map_area.vpn_interval.end());
+            memory_set.check_free(&map_area.vpn_interval)?;
+            memory_set.try_push(map_area, Some(segment.data))?;
         }
         // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
         // guard page
         user_stack_bottom += config::PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + config::USER_STACK_SIZE;
-        memory_set.push(
+        let user_stack_top = user_stack_bottom + image.stack_sizes.user;
+        if user_stack_top > config::TRAP_CONTEXT {
+            return Err(MapError::OutOfBounds);
+        }
+        memory_set.try_push(
             MapArea::new(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
-            ),
+            )
+            .with_kind(AreaKind::Stack),
             None,
-        );
+        )?;
+        // 堆紧接在用户栈之上，初始为空，由 `sbrk` 调整其大小
+        memory_set.try_push(
+            MapArea::new(
+                user_stack_top.into(),
+                user_stack_top.into(),
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            )
+            .with_kind(AreaKind::Heap),
+            None,
+        )?;
         // map TrapContext
-        memory_set.push(
+        memory_set.try_push(
             MapArea::new(
                 config::TRAP_CONTEXT.into(),
                 config::TRAMPOLINE.into(),
                 MapType::Framed,
                 MapPermission::R | MapPermission::W,
-            ),
+            )
+            .with_kind(AreaKind::TrapContext),
             None,
-        );
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
+        )?;
+        Ok((memory_set, user_stack_top, image.entry_point))
+    }
+
+    /// the layout of the address space, one `start-end perms kind` line per area
+    /// sorted by address, the trampoline included
+    pub fn maps(&self) -> String {
+        let mut areas: Vec<&MapArea> = self.areas.iter().collect();
+        areas.sort_by_key(|area| area.vpn_interval.start());
+        let mut maps = String::new();
+        for area in areas {
+            writeln!(maps, "{}", area).unwrap();
+        }
+        writeln!(
+            maps,
+            "{:08x}-{:08x} {} trampoline",
+            config::TRAMPOLINE,
+            config::TRAMPOLINE + config::PAGE_SIZE,
+            MapPermission::R | MapPermission::X
         )
+        .unwrap();
+        maps
+    }
+
+    /// print every area of the address space, and why accessing `fault_va` faulted:
+    /// a guard page, an unmapped hole or a permission mismatch
+    pub fn dump_areas(&self, fault_va: VirtAddr) {
+        let fault_vpn: VirtPageNum = fault_va.floor();
+        emergency_println!("[kernel] memory areas:");
+        for line in self.maps().lines() {
+            emergency_println!("    {}", line);
+        }
+        let fault_addr: usize = fault_va.into();
+        match self
+            .areas
+            .iter()
+            .find(|area| area.vpn_interval.contains(fault_vpn))
+        {
+            Some(area) => emergency_println!(
+                "[kernel] {:#x} is mapped {}, the access is not permitted",
+                fault_addr,
+                area.map_perm
+            ),
+            // 用户栈下方留有一个不映射的保护页面
+            None if self
+                .areas
+                .iter()
+                .any(|area| area.vpn_interval.start() == fault_vpn + VirtPageNum::one()) =>
+            {
+                emergency_println!(
+                    "[kernel] {:#x} is in the guard page below an area, stack overflow?",
+                    fault_addr
+                )
+            }
+            None => emergency_println!("[kernel] {:#x} is in an unmapped hole", fault_addr),
+        }
     }
 
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
@@ -249,6 +555,64 @@ impl MemorySet {
     }
 }
 
+/// error returned when an area can not be inserted into a [`MemorySet`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MapError {
+    /// 逻辑段不包含任何虚拟页面
+    Empty,
+    /// 逻辑段超出了跳板页面以下的可用地址范围
+    OutOfBounds,
+    /// 逻辑段与地址空间中已有的逻辑段 `[start, end)` 重叠
+    Overlap {
+        start: VirtPageNum,
+        end: VirtPageNum,
+    },
+    /// 物理页帧耗尽
+    OutOfFrames,
+}
+
+/// how a syscall accesses a user buffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UserAccess {
+    /// 内核只读取缓冲区
+    Read,
+    /// 内核写入缓冲区
+    Write,
+}
+
+/// what a [`MapArea`] is used for
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AreaKind {
+    /// 可执行文件中的段
+    Image,
+    /// 用户栈
+    Stack,
+    /// 用户堆，由 `sbrk` 调整大小
+    Heap,
+    /// Trap 上下文
+    TrapContext,
+    /// 内核地址空间中恒等映射的区域
+    Kernel,
+    /// 其他匿名映射的区域
+    Anonymous,
+    /// 应用通过 `mmap` 映射的区域
+    Mmap,
+}
+
+impl fmt::Display for AreaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AreaKind::Image => "image",
+            AreaKind::Stack => "stack",
+            AreaKind::Heap => "heap",
+            AreaKind::TrapContext => "trap_context",
+            AreaKind::Kernel => "kernel",
+            AreaKind::Anonymous => "anonymous",
+            AreaKind::Mmap => "mmap",
+        })
+    }
+}
+
 /// map type for memory set: identical or framed
 /// 逻辑段内的所有虚拟页面映射到物理页帧的方式
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -257,6 +621,8 @@ pub enum MapType {
     Identical,
     /// 对于每个虚拟页面都有一个新分配的物理页帧与之对应，虚地址与物理地址的映射关系是相对随机的
     Framed,
+    /// 与 `Framed` 相同，但每个虚拟页面的物理页帧在第一次被访问时才分配
+    Lazy,
 }
 
 bitflags! {
@@ -269,13 +635,45 @@ bitflags! {
     }
 }
 
+impl fmt::Display for MapPermission {
+    /// `rwxu` style, `-` for a missing permission
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (perm, c) in [
+            (MapPermission::R, 'r'),
+            (MapPermission::W, 'w'),
+            (MapPermission::X, 'x'),
+            (MapPermission::U, 'u'),
+        ] {
+            f.write_char(if self.contains(perm) { c } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     /// 一段虚拟页号的连续区间，表示该逻辑段在地址区间中的位置和长度
     vpn_interval: VPNInterval,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// 懒分配逻辑段中访问过的页面，可能已被换出
+    lazy_pages: BTreeMap<VirtPageNum, Arc<SwapPage>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// 逻辑段的用途，只用于展示地址空间布局
+    kind: AreaKind,
+}
+
+impl fmt::Display for MapArea {
+    /// `start-end perms kind` , like a line of `/proc/<pid>/maps`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start: usize = VirtAddr::from(self.vpn_interval.start()).into();
+        let end: usize = VirtAddr::from(self.vpn_interval.end()).into();
+        write!(
+            f,
+            "{:08x}-{:08x} {} {}",
+            start, end, self.map_perm, self.kind
+        )
+    }
 }
 
 impl MapArea {
@@ -291,12 +689,24 @@ impl MapArea {
         Self {
             vpn_interval: VPNInterval::new(start_vpn, end_vpn),
             data_frames: BTreeMap::new(),
+            lazy_pages: BTreeMap::new(),
             map_type,
             map_perm,
+            kind: match map_type {
+                MapType::Identical => AreaKind::Kernel,
+                MapType::Framed | MapType::Lazy => AreaKind::Anonymous,
+            },
         }
     }
 
+    /// mark what the area is used for
+    pub fn with_kind(mut self, kind: AreaKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     /// 在 `page_table` 中建立传入的虚拟页 `vpn` 到相应的物理页的映射
+    #[allow(unused)]
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -308,29 +718,110 @@ impl MapArea {
                 ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
             }
+            MapType::Lazy => {
+                assert!(self.populate(page_table, vpn), "out of frames");
+                return;
+            }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        assert!(page_table.map(vpn, ppn, pte_flags), "out of frames");
     }
 
     /// 在 `page_table` 中删除传入的虚拟页 `vpn` 到相应的物理页的映射
-    #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        match self.map_type {
+            MapType::Identical => {}
+            MapType::Framed => {
+                self.data_frames.remove(&vpn);
+            }
+            // 懒分配的页面可能从未被访问，也就没有映射；换出的页面只剩交换项
+            MapType::Lazy => {
+                if self.lazy_pages.remove(&vpn).is_none() {
+                    return;
+                }
+            }
         }
         page_table.unmap(vpn);
     }
 
-    /// 将当前逻辑段到物理内存的映射加入传入的该逻辑段所属的地址空间的多级页表中
-    pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_interval {
-            self.map_one(page_table, vpn);
+    /// 将懒分配逻辑段的终止虚拟页号改为 `new_end` ，新增的页面在第一次被访问时才分配，超出的页面被解除映射
+    fn resize(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        debug_assert_eq!(self.map_type, MapType::Lazy);
+        let (start, end) = (self.vpn_interval.start(), self.vpn_interval.end());
+        if new_end < end {
+            for vpn in VPNInterval::new(new_end, end) {
+                self.unmap_one(page_table, vpn);
+            }
+        }
+        self.vpn_interval = VPNInterval::new(start, new_end);
+    }
+
+    /// 懒分配逻辑段中的虚拟页 `vpn` 是否已经映射到物理页帧
+    fn is_resident(&self, vpn: VirtPageNum) -> bool {
+        self.lazy_pages
+            .get(&vpn)
+            .is_some_and(|page| page.ppn().is_some())
+    }
+
+    /// 为懒分配逻辑段中不在内存中的虚拟页 `vpn` 分配物理页帧并建立映射：第一次访问时分配全零的页帧，
+    /// 被换出的页面则从交换区读回，物理页帧耗尽时返回 `false`
+    fn populate(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        debug_assert_eq!(self.map_type, MapType::Lazy);
+        let page = match self.lazy_pages.get(&vpn) {
+            Some(page) => page.clone(),
+            None => {
+                let Some(frame) = frame_alloc() else {
+                    return false;
+                };
+                let page = SwapPage::new(frame);
+                self.lazy_pages.insert(vpn, page.clone());
+                page
+            }
+        };
+        let Some(ppn) = page.ppn().or_else(|| page.swap_in()) else {
+            return false;
+        };
+        // 新建立的映射视为刚被访问过：否则 A 位还没有被硬件置位的页面会在用到之前就被时钟算法选中
+        let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() | PTEFlags::A;
+        if pte_flags.contains(PTEFlags::W) {
+            pte_flags |= PTEFlags::D;
+        }
+        if !page_table.map(vpn, ppn, pte_flags) {
+            return false;
+        }
+        // 建立映射时可能分配页表节点而换出其他页面，映射建立之后页面才能被换出
+        swap::track(&page, page_table.token(), vpn);
+        true
+    }
+
+    /// 将当前逻辑段到物理内存的映射加入传入的该逻辑段所属的地址空间的多级页表中，
+    /// 物理页帧耗尽时不留下任何映射和页帧并返回 `false`
+    pub fn map(&mut self, page_table: &mut PageTable) -> bool {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        match self.map_type {
+            MapType::Identical => {
+                let ppn_start = PhysPageNum::from(usize::from(self.vpn_interval.start()));
+                page_table.map_range(self.vpn_interval, ppn_start, pte_flags)
+            }
+            MapType::Framed => {
+                let data_frames = &mut self.data_frames;
+                let mapped = page_table.map_range_with(self.vpn_interval, pte_flags, |vpn| {
+                    let frame: FrameTracker = frame_alloc()?;
+                    let ppn = frame.ppn;
+                    data_frames.insert(vpn, frame);
+                    Some(ppn)
+                });
+                if !mapped {
+                    self.data_frames.clear();
+                }
+                mapped
+            }
+            // 第一次访问时由 `MapArea::populate` 逐页映射
+            MapType::Lazy => true,
         }
     }
 
     /// 将当前逻辑段到物理内存的映射从传入的该逻辑段所属的地址空间的多级页表中删除
-    #[allow(unused)]
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_interval {
             self.unmap_one(page_table, vpn);
@@ -366,9 +857,7 @@ impl MapArea {
 // 通过手动查内核多级页表的方式验证代码段和只读数据段不允许被写入，同时不允许从数据段上取指执行
 // #[allow(unused)]
 pub fn remap_test() {
-    use core::cell::RefMut;
-
-    let kernel_space: RefMut<MemorySet> = KERNEL_SPACE.exclusive_access();
+    let kernel_space: UPRefMut<MemorySet> = KERNEL_SPACE.exclusive_access();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
@@ -7,19 +7,163 @@
 //! Every task or process has a memory_set to control its virtual memory.
 
 pub(crate) use address::{PhysPageNum, VirtAddr};
+pub(crate) use frame_allocator::zeroing_stats;
+pub(crate) use frame_allocator::{frame_alloc, FrameTracker};
+pub(crate) use heap_allocator::{reserve_in_use, take_heap_exhausted};
 pub(crate) use memory_set::remap_test;
-pub(crate) use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub(crate) use page_table::translated_byte_buffer;
+pub(crate) use memory_set::{MapError, MapPermission, MemorySet, UserAccess, KERNEL_SPACE};
+pub(crate) use page_table::{
+    copy_struct_from_user, copy_struct_to_user, translated_byte_buffer, translated_c_bytes,
+    translated_str, UserBuffer,
+};
+#[cfg(feature = "swap")]
+pub(crate) use swap::init_swap;
+#[cfg(feature = "kernel_selftest")]
+pub(crate) use {
+    frame_allocator::frame_allocator_test,
+    heap_allocator::{heap_fuzz_test, heap_test},
+    page_table::{page_table_stress_test, translated_byte_buffer_fuzz_test},
+};
+
+use abi::MemReport;
+use riscv::register::satp;
+
+use crate::config;
 
 mod address;
+#[cfg(feature = "fault_injection")]
+pub(crate) mod fault_inject;
 mod frame_allocator;
 mod heap_allocator;
+mod layout;
 mod memory_set;
 mod page_table;
+mod slab;
+mod swap;
 
-/// initiate heap allocator, frame allocator and kernel space
-pub(crate) fn init() {
+/// initiate heap allocator, frame allocator and kernel space,
+/// excluding the memory reserved by the device tree at `dtb_pa`
+pub(crate) fn init(dtb_pa: usize) {
+    extern "C" {
+        fn ekernel();
+    }
     heap_allocator::init_heap();
-    frame_allocator::init_frame_allocator();
+    #[cfg(feature = "fault_injection")]
+    fault_inject::init();
+    crate::boot::stage_done("heap");
+    layout::check_layout();
+    let reserved = layout::reserved_regions(dtb_pa);
+    let regions = layout::memory_regions(dtb_pa);
+    frame_allocator::init_frame_allocator(&reserved);
+    crate::boot::stage_done("frame allocator");
+    memory_set::init_kernel_space();
     memory_set::KERNEL_SPACE.exclusive_access().activate();
+    for (start, end) in regions {
+        // 包含内核的区间中 `MEMORY_END` 以下的部分已经由物理页帧分配器管理
+        let start = if (start..end).contains(&(ekernel as usize)) {
+            start.max(config::MEMORY_END)
+        } else {
+            start
+        };
+        let end = end.min(config::MAX_MEMORY_END);
+        if start >= end {
+            continue;
+        }
+        if let Err(err) = add_memory(start, end) {
+            println!(
+                "[kernel] failed to add physical memory [{:#x}, {:#x}): {:?}",
+                start, end, err
+            );
+        }
+    }
+    print_frame_stats();
+    crate::boot::stage_done("kernel space");
+}
+
+/// Add the physical memory `[start, end)` to the kernel: map it identically in
+/// kernel space, then hand its frames to the frame allocator.
+pub(crate) fn add_memory(start: usize, end: usize) -> Result<(), MapError> {
+    let start: PhysPageNum = address::PhysAddr::from(start).ceil();
+    let end: PhysPageNum = address::PhysAddr::from(end).floor();
+    if start >= end {
+        return Ok(());
+    }
+    map_identical(
+        usize::from(start) << config::PAGE_SIZE_BITS,
+        usize::from(end) << config::PAGE_SIZE_BITS,
+    )?;
+    frame_allocator::add_frames(start, end);
+    Ok(())
+}
+
+/// Map the physical pages covering `[start, end)` identically in kernel space as
+/// readable and writable, e.g. for memory or MMIO found in the device tree.
+pub(crate) fn map_identical(start: usize, end: usize) -> Result<(), MapError> {
+    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    kernel_space.insert_identical_area(
+        VirtAddr::from(start),
+        VirtAddr::from(end),
+        MapPermission::R | MapPermission::W,
+    )?;
+    kernel_space.activate();
+    Ok(())
+}
+
+/// the physical address `va` in kernel space is mapped to, e.g. for DMA
+///
+/// 内核中 `satp` 总是指向内核地址空间的页表，直接按它查找而不借用 [`KERNEL_SPACE`] ：调用者
+/// 可能正在修改内核地址空间，例如为新应用分配内核栈时物理页帧耗尽，需要把页面换出到块设备
+pub(crate) fn kernel_virt_to_phys(va: usize) -> Option<usize> {
+    let va = VirtAddr::from(va);
+    let pte = page_table::PageTable::from_token(satp::read().bits()).translate(va.floor())?;
+    pte.is_valid()
+        .then(|| (usize::from(pte.ppn()) << config::PAGE_SIZE_BITS) + va.page_offset())
+}
+
+/// how fragmented the free frames and the free blocks of the kernel heap are
+pub(crate) fn mem_report() -> MemReport {
+    let frames = frame_allocator::frame_fragmentation();
+    let mut report = MemReport {
+        free_frames: frames.free as u64,
+        largest_free_run: frames.largest_free_run as u64,
+        recycled_frames: frames.recycled as u64,
+        ..MemReport::default()
+    };
+    for (order, &count) in heap_allocator::heap_free_blocks().iter().enumerate() {
+        if count > 0 {
+            report.heap_free_bytes += (count << order) as u64;
+            report.heap_free_blocks += count as u64;
+            report.heap_largest_block = 1 << order;
+        }
+    }
+    report
+}
+
+/// print how fragmented the free frames and the free blocks of the kernel heap are
+pub(crate) fn print_fragmentation() {
+    let frames = frame_allocator::frame_fragmentation();
+    println!(
+        "[kernel] free frames: {} in total, largest contiguous run {}, {} recycled",
+        frames.free, frames.largest_free_run, frames.recycled
+    );
+    println!("[kernel] free kernel heap blocks:");
+    for (order, &count) in heap_allocator::heap_free_blocks().iter().enumerate() {
+        if count > 0 {
+            println!("{:>12} bytes x {}", 1usize << order, count);
+        }
+    }
+}
+
+/// print the usage of every range of physical frames managed by the frame allocator
+pub(crate) fn print_frame_stats() {
+    for range in frame_allocator::frame_ranges() {
+        println!(
+            "[kernel] frames [{:#x}, {:#x}): {} usable, {} in use, {} never allocated",
+            range.start << config::PAGE_SIZE_BITS,
+            range.end << config::PAGE_SIZE_BITS,
+            range.usable(),
+            range.in_use,
+            range.end - range.current
+        );
+    }
 }
@@ -1,9 +1,16 @@
 use bitflags::*;
 
+use ::alloc::string::String;
 use ::alloc::vec;
 use ::alloc::vec::Vec;
+use core::mem::{size_of, MaybeUninit};
+use core::slice;
 
-use super::address::{PhysPageNum, VirtAddr, VirtPageNum};
+use abi::{
+    Dirent, IoStats, MemReport, PerfCounters, RLimit, SignalAction, Stat, TaskInfo, TimeVal,
+};
+
+use super::address::{PhysPageNum, VPNInterval, VirtAddr, VirtPageNum};
 use super::frame_allocator::{frame_alloc, FrameTracker};
 
 bitflags! {
@@ -20,6 +27,9 @@ bitflags! {
     }
 }
 
+/// 页表项中留给软件使用的 RSW 位之一，标记交换项
+const PTE_SWAPPED: usize = 1 << 8;
+
 /// page table structure
 pub struct PageTable {
     /// 根节点的物理页号
@@ -31,32 +41,39 @@ pub struct PageTable {
 /// Assume that it won't oom when creating/mapping.
 impl PageTable {
     pub fn new() -> Self {
-        let frame: FrameTracker = frame_alloc().unwrap();
-        PageTable {
+        Self::try_new().expect("out of frames for the root page table")
+    }
+
+    /// 新建只有根节点的页表，物理页帧耗尽时返回 `None`
+    pub fn try_new() -> Option<Self> {
+        let frame: FrameTracker = frame_alloc()?;
+        Some(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
-    /// 在多级页表找到一个虚拟页号对应的页表项的可变引用。如果在遍历的过程中发现有节点尚未创建则会新建一个节点
-    fn find_pte_or_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// 找到一个虚拟页号所在的叶子节点（第三级页表）的物理页号。如果在遍历的过程中发现有节点尚未创建则会新建一个节点，
+    /// 物理页帧耗尽时返回 `None`
+    fn find_leaf_or_create(&mut self, vpn: VirtPageNum) -> Option<PhysPageNum> {
         let idxs: [usize; 3] = vpn.indexes();
         let mut ppn: PhysPageNum = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
-        for (i, &vpn) in idxs.iter().enumerate() {
-            let pte: &mut PageTableEntry = &mut ppn.as_mut_slice()[vpn];
-            if i == 2 {
-                result = Some(pte);
-                break;
-            }
+        for &idx in &idxs[..2] {
+            let pte: &mut PageTableEntry = &mut ppn.as_mut_slice()[idx];
             if !pte.is_valid() {
-                let frame: FrameTracker = frame_alloc().unwrap();
+                let frame: FrameTracker = frame_alloc()?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
             ppn = pte.ppn();
         }
-        result
+        Some(ppn)
+    }
+
+    /// 在多级页表找到一个虚拟页号对应的页表项的可变引用。如果在遍历的过程中发现有节点尚未创建则会新建一个节点
+    fn find_pte_or_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+        let leaf_ppn: PhysPageNum = self.find_leaf_or_create(vpn)?;
+        Some(&mut leaf_ppn.as_mut_slice()[vpn.indexes()[2]])
     }
 
     /// 当找不到合法叶子节点的时候不会新建叶子节点而是直接返回 `None` 即查找失败
@@ -80,20 +97,124 @@ impl PageTable {
         result
     }
 
-    /// 建立虚实地址映射关系
-    #[allow(unused)]
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte: &mut PageTableEntry = self.find_pte_or_create(vpn).unwrap();
+    /// 建立虚实地址映射关系，新建页表节点时物理页帧耗尽则返回 `false`
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
+        let Some(pte) = self.find_pte_or_create(vpn) else {
+            return false;
+        };
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        true
+    }
+
+    /// 为一段连续的虚拟页面建立映射，每个虚拟页面映射到的物理页号由 `ppn_of` 给出
+    ///
+    /// 每个叶子节点只从根节点遍历一次，而不是对每个虚拟页面都重新遍历。新建页表节点时物理页帧耗尽
+    /// 或者 `ppn_of` 返回 `None` 时，删除这一次已经建立的映射并返回 `false`
+    pub fn map_range_with<F>(
+        &mut self,
+        vpn_interval: VPNInterval,
+        flags: PTEFlags,
+        mut ppn_of: F,
+    ) -> bool
+    where
+        F: FnMut(VirtPageNum) -> Option<PhysPageNum>,
+    {
+        let mut leaf_ppn: Option<PhysPageNum> = None;
+        for vpn in vpn_interval {
+            let idx: usize = vpn.indexes()[2];
+            // 进入了一个新的叶子节点
+            if leaf_ppn.is_none() || idx == 0 {
+                leaf_ppn = self.find_leaf_or_create(vpn);
+            }
+            let ppn = leaf_ppn.and_then(|leaf_ppn| Some((leaf_ppn, ppn_of(vpn)?)));
+            let Some((leaf_ppn, ppn)) = ppn else {
+                for mapped in VPNInterval::new(vpn_interval.start(), vpn) {
+                    self.unmap(mapped);
+                }
+                return false;
+            };
+            let pte: &mut PageTableEntry = &mut leaf_ppn.as_mut_slice()[idx];
+            assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+            *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        }
+        true
+    }
+
+    /// 将一段连续的虚拟页面映射到从 `ppn_start` 开始的一段连续的物理页帧上，物理页帧耗尽时返回 `false`
+    pub fn map_range(
+        &mut self,
+        vpn_interval: VPNInterval,
+        ppn_start: PhysPageNum,
+        flags: PTEFlags,
+    ) -> bool {
+        let vpn_start: usize = vpn_interval.start().into();
+        let ppn_start: usize = ppn_start.into();
+        self.map_range_with(vpn_interval, flags, |vpn| {
+            Some(PhysPageNum::from(ppn_start + usize::from(vpn) - vpn_start))
+        })
     }
 
     /// 拆除虚实地址映射关系
+    ///
+    /// 清空叶子页表项后自底向上检查沿途的页表节点，若某个非根节点中已没有合法的页表项，
+    /// 则清空父节点中指向它的页表项并回收该节点所在的物理页帧，避免反复映射/解映射时泄漏页表内存
     #[allow(unused)]
     pub fn unmap(&mut self, vpn: VirtPageNum) {
-        let pte: &mut PageTableEntry = self.find_pte(vpn).unwrap();
-        assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        let idxs: [usize; 3] = vpn.indexes();
+        // 遍历路径上每一级页表节点的物理页号
+        let mut ppns: [PhysPageNum; 3] = [self.root_ppn; 3];
+        for i in 0..2 {
+            let pte: &PageTableEntry = &ppns[i].as_mut_slice()[idxs[i]];
+            assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+            ppns[i + 1] = pte.ppn();
+        }
+        let pte: &mut PageTableEntry = &mut ppns[2].as_mut_slice()[idxs[2]];
+        assert!(
+            pte.is_valid() || pte.is_swapped(),
+            "vpn {:?} is invalid before unmapping",
+            vpn
+        );
         *pte = PageTableEntry::empty();
+        for i in (1..3).rev() {
+            // 换出的页面的交换项也要保留
+            if ppns[i]
+                .as_mut_slice()
+                .iter()
+                .any(|pte| pte.is_valid() || pte.is_swapped())
+            {
+                break;
+            }
+            ppns[i - 1].as_mut_slice()[idxs[i - 1]] = PageTableEntry::empty();
+            self.free_node(ppns[i]);
+        }
+    }
+
+    /// 回收一个已经从页表中摘下的非根节点所在的物理页帧
+    fn free_node(&mut self, ppn: PhysPageNum) {
+        // `FrameTracker` 被 drop 时会自动归还物理页帧
+        self.frames.retain(|frame| frame.ppn != ppn);
+    }
+
+    /// Clear the accessed bit of the entry of the mapped `vpn`, return whether it was set.
+    ///
+    /// 清除后需要刷新快表，硬件才会在下次访问时重新设置
+    pub fn take_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        let pte: &mut PageTableEntry = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is not mapped", vpn);
+        let accessed = pte.flags().contains(PTEFlags::A);
+        pte.bits &= !(PTEFlags::A.bits as usize);
+        accessed
+    }
+
+    /// Replace the entry of the mapped `vpn` with a swap entry recording slot `slot`
+    /// of the swap area.
+    ///
+    /// 修改后需要刷新快表
+    pub fn mark_swapped(&mut self, vpn: VirtPageNum, slot: usize) {
+        let pte: &mut PageTableEntry = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is not mapped", vpn);
+        *pte = PageTableEntry::swapped(slot);
     }
 
     /// 如果能够找到页表项，那么它会将页表项拷贝一份并返回，否则就返回一个 `None`
@@ -137,6 +258,18 @@ impl PageTableEntry {
         PageTableEntry { bits: 0 }
     }
 
+    /// 交换项：无效的页表项，物理页号的位置记录页面在交换区中的槽位
+    pub fn swapped(slot: usize) -> Self {
+        PageTableEntry {
+            bits: slot << 10 | PTE_SWAPPED,
+        }
+    }
+
+    /// 是否是交换项
+    pub fn is_swapped(&self) -> bool {
+        !self.is_valid() && self.bits & PTE_SWAPPED != 0
+    }
+
     /// 物理页号
     pub fn ppn(&self) -> PhysPageNum {
         (self.bits >> 10 & ((1usize << 44) - 1)).into()
@@ -177,14 +310,33 @@ impl PageTableEntry {
 ///
 /// 返回一组可以在内核空间中直接访问的字节数组切片（注：这个缓冲区的内核虚拟地址范围有可能是不连续的）
 pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+    try_translated_byte_buffer(token, ptr, len).unwrap_or_else(|| {
+        panic!(
+            "user buffer [{:#x}, {:#x}) is not mapped",
+            ptr as usize,
+            (ptr as usize).wrapping_add(len)
+        )
+    })
+}
+
+/// like [`translated_byte_buffer`], but returns `None` instead of panicking
+/// if any page of the buffer is unmapped or the buffer wraps around the address space
+pub fn try_translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
     let mut v: Vec<&mut [u8]> = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn: VirtPageNum = start_va.floor();
-        let ppn: PhysPageNum = page_table.translate(vpn).unwrap().ppn();
+        let ppn: PhysPageNum = page_table
+            .translate(vpn)
+            .filter(PageTableEntry::is_valid)?
+            .ppn();
         vpn += VirtPageNum::one();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -195,5 +347,271 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Some(v)
+}
+
+/// a user buffer translated by [`translated_byte_buffer`], passed to [`crate::fs::File`]
+pub struct UserBuffer {
+    /// 缓冲区在各个物理页中的片段，按用户虚拟地址顺序排列
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
+        Self { buffers }
+    }
+
+    /// the total length in bytes
+    pub fn len(&self) -> usize {
+        self.buffers.iter().map(|buffer| buffer.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.iter().all(|buffer| buffer.is_empty())
+    }
+}
+
+/// read the NUL-terminated string at `ptr` in the address space of `token`,
+/// `None` if it is unmapped, longer than `max_len` bytes or not UTF-8
+///
+/// 页面不在内存中时的处理与 [`translated_c_bytes`] 相同
+pub fn translated_str<F>(
+    token: usize,
+    ptr: *const u8,
+    max_len: usize,
+    make_resident: F,
+) -> Option<String>
+where
+    F: FnMut(usize) -> bool,
+{
+    let bytes = translated_c_bytes(token, ptr, max_len + 1, make_resident)?;
+    if bytes.len() > max_len {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// read the bytes of the NUL-terminated string at `ptr` in the address space of
+/// `token`, stopping after `limit` bytes, `None` if they are unmapped
+///
+/// 读到 `limit` 字节后不再访问后面的页面，即使字符串还没有结束。字符串的长度事先未知，
+/// 所在的页面可能还没有分配或者已经被换出，此时调用 `make_resident(va)` 让 `va` 所在的页面
+/// 进入内存，它返回 `false` 时放弃。逐页复制，换入后面的页面时前面的页面即使被换出也没有关系
+pub fn translated_c_bytes<F>(
+    token: usize,
+    ptr: *const u8,
+    limit: usize,
+    mut make_resident: F,
+) -> Option<Vec<u8>>
+where
+    F: FnMut(usize) -> bool,
+{
+    let page_table = PageTable::from_token(token);
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut va = ptr as usize;
+    while bytes.len() < limit {
+        let vpn = VirtAddr::from(va).floor();
+        let translate = || page_table.translate(vpn).filter(PageTableEntry::is_valid);
+        let ppn: PhysPageNum = translate()
+            .or_else(|| make_resident(va).then(translate).flatten())?
+            .ppn();
+        // 逐页查找结尾的 0 ，字符串可能跨越多个页面
+        let page = &ppn.as_bytes_mut()[VirtAddr::from(va).page_offset()..];
+        let page = &page[..page.len().min(limit - bytes.len())];
+        let nul = page.iter().position(|&c| c == 0);
+        bytes.extend_from_slice(&page[..nul.unwrap_or(page.len())]);
+        if nul.is_some() {
+            break;
+        }
+        va = va.checked_add(page.len())?;
+    }
+    Some(bytes)
+}
+
+/// plain old data, which can be copied between kernel and user space byte by byte
+///
+/// # Safety
+///
+/// 任意字节组合都必须是 `Self` 的合法值，且 `Self` 不含填充字节，否则复制到用户空间时
+/// 会泄露内核栈上未初始化的字节。只由整数构成的 `#[repr(C)]` 结构体满足这些条件
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+impl_pod!(
+    TimeVal,
+    RLimit,
+    TaskInfo,
+    PerfCounters,
+    Stat,
+    Dirent,
+    SignalAction,
+    IoStats,
+    MemReport
+);
+
+/// copy a `T` out of the address space of `token` at `ptr`, which must have been
+/// checked by the syscall validator
+pub fn copy_struct_from_user<T: Pod>(token: usize, ptr: *const T) -> T {
+    let mut value = MaybeUninit::<T>::uninit();
+    let dst = unsafe { slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    let mut offset: usize = 0;
+    for buffer in translated_byte_buffer(token, ptr as *const u8, size_of::<T>()) {
+        dst[offset..offset + buffer.len()].copy_from_slice(buffer);
+        offset += buffer.len();
+    }
+    unsafe { value.assume_init() }
+}
+
+/// copy `value` into the address space of `token` at `ptr`, which must have been
+/// checked by the syscall validator
+pub fn copy_struct_to_user<T: Pod>(token: usize, ptr: *mut T, value: &T) {
+    let src = unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    let mut offset: usize = 0;
+    for buffer in translated_byte_buffer(token, ptr as *const u8, size_of::<T>()) {
+        let len = buffer.len();
+        buffer.copy_from_slice(&src[offset..offset + len]);
+        offset += len;
+    }
+}
+
+/// map pages spread over many page table nodes, check that every mapping
+/// translates back, then unmap them all and check that the emptied nodes are freed
+#[allow(unused)]
+pub fn page_table_stress_test() {
+    let mut page_table = PageTable::new();
+    // 相邻的虚拟页号落在不同的三级节点中，并分散在多个二级节点中
+    let vpns: Vec<VirtPageNum> = (0..256usize)
+        .map(|i| VirtPageNum::from(i * 513 + (i % 7) * (1 << 18)))
+        .collect();
+    for &vpn in vpns.iter() {
+        // 只检查映射关系而不会通过它访问内存，因此可以映射到任意物理页号
+        assert!(page_table.map(
+            vpn,
+            PhysPageNum::from(usize::from(vpn)),
+            PTEFlags::R | PTEFlags::W,
+        ));
+    }
+    for &vpn in vpns.iter() {
+        let pte = page_table.translate(vpn).unwrap();
+        assert!(pte.is_valid() && pte.readable() && pte.writable());
+        assert_eq!(usize::from(pte.ppn()), usize::from(vpn));
+    }
+    assert!(page_table.frames.len() > vpns.len());
+    for &vpn in vpns.iter() {
+        page_table.unmap(vpn);
+    }
+    assert!(vpns.iter().all(|&vpn| page_table.translate(vpn).is_none()));
+    assert_eq!(page_table.frames.len(), 1, "page table nodes leaked");
+    println!("page_table_stress_test passed!");
+}
+
+/// run [`try_translated_byte_buffer`] over random user ranges, some of which
+/// straddle pages or touch unmapped holes, and check the returned slices
+#[cfg(feature = "kernel_selftest")]
+pub fn translated_byte_buffer_fuzz_test(rng: &mut crate::selftest::Rng) {
+    use crate::config::PAGE_SIZE;
+
+    /// 被测试的窗口中的页，`false` 表示没有映射的空洞
+    const MAPPED: [bool; 8] = [true, true, true, false, true, true, false, true];
+    /// 虚拟地址 `va` 处的字节内容，不同页中同一偏移处的内容不同
+    fn pattern(va: usize) -> u8 {
+        (va ^ (va >> 12)) as u8
+    }
+
+    let mut page_table = PageTable::new();
+    let mut data_frames: Vec<FrameTracker> = Vec::new();
+    let base_vpn = VirtPageNum::from(0x10000);
+    let base_va = usize::from(VirtAddr::from(base_vpn));
+    for (idx, _) in MAPPED.iter().enumerate().filter(|(_, &mapped)| mapped) {
+        let frame = frame_alloc().unwrap();
+        let va = base_va + idx * PAGE_SIZE;
+        for (offset, byte) in frame.ppn.as_bytes_mut().iter_mut().enumerate() {
+            *byte = pattern(va + offset);
+        }
+        assert!(page_table.map(
+            VirtPageNum::from(usize::from(base_vpn) + idx),
+            frame.ppn,
+            PTEFlags::R | PTEFlags::W | PTEFlags::U,
+        ));
+        data_frames.push(frame);
+    }
+    let token = page_table.token();
+    let is_mapped = |va: usize| {
+        va >= base_va
+            && MAPPED
+                .get((va - base_va) / PAGE_SIZE)
+                .copied()
+                .unwrap_or(false)
+    };
+
+    let (mut hits, mut misses) = (0usize, 0usize);
+    for _ in 0..2000 {
+        // 起始地址从窗口前半页开始，长度最多跨越三页
+        let start = base_va - PAGE_SIZE / 2 + rng.below((MAPPED.len() + 1) * PAGE_SIZE);
+        let len = match rng.below(4) {
+            0 => rng.below(16),
+            _ => rng.below(3 * PAGE_SIZE + 1),
+        };
+        let end = start + len;
+        let expect_mapped =
+            (start / PAGE_SIZE..end.div_ceil(PAGE_SIZE)).all(|page| is_mapped(page * PAGE_SIZE));
+        match try_translated_byte_buffer(token, start as *const u8, len) {
+            Some(buffers) => {
+                assert!(
+                    expect_mapped,
+                    "[{:#x}, {:#x}) translated across a hole",
+                    start, end
+                );
+                let mut va = start;
+                for buffer in buffers.iter() {
+                    assert!(
+                        !buffer.is_empty(),
+                        "empty slice for [{:#x}, {:#x})",
+                        start,
+                        end
+                    );
+                    assert!(
+                        va % PAGE_SIZE + buffer.len() <= PAGE_SIZE,
+                        "slice at {:#x} crosses a page",
+                        va
+                    );
+                    for (offset, &byte) in buffer.iter().enumerate() {
+                        assert_eq!(
+                            byte,
+                            pattern(va + offset),
+                            "wrong byte at {:#x}",
+                            va + offset
+                        );
+                    }
+                    va += buffer.len();
+                }
+                assert_eq!(va, end, "[{:#x}, {:#x}) sliced short", start, end);
+                hits += 1;
+            }
+            None => {
+                assert!(!expect_mapped, "[{:#x}, {:#x}) is mapped", start, end);
+                misses += 1;
+            }
+        }
+    }
+    assert!(hits > 0 && misses > 0);
+    // 超出地址空间末尾的缓冲区
+    assert!(try_translated_byte_buffer(token, usize::MAX as *const u8, 2).is_none());
+    assert!(try_translated_byte_buffer(token, base_va as *const u8, 0)
+        .unwrap()
+        .is_empty());
+    drop(data_frames);
+    println!(
+        "translated_byte_buffer_fuzz_test passed! ({} mapped, {} rejected)",
+        hits, misses
+    );
 }
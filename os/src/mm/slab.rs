@@ -0,0 +1,163 @@
+//! Slab caches for small fixed-size kernel objects
+//!
+//! Small allocations (task control blocks, `BTreeMap`/`Vec` nodes holding
+//! `FrameTracker`s, ...) are served from per-size-class caches carved out of
+//! page-sized slabs, and only large allocations go to the buddy heap directly.
+//! Freed objects go back to the free list of their cache and slabs are never
+//! returned to the heap, so fork/exec-heavy workloads don't fragment it.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+
+use crate::config;
+use crate::sync::UPSafeCell;
+
+/// 每个 slab 缓存中对象的大小，大于最后一项的分配请求直接交给伙伴系统堆分配器
+const SIZE_CLASSES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+const NUM_SIZE_CLASSES: usize = SIZE_CLASSES.len();
+
+/// statistics of a slab cache
+#[derive(Copy, Clone, Default)]
+pub struct SlabStats {
+    /// 分配的次数
+    pub allocs: usize,
+    /// 释放的次数
+    pub frees: usize,
+    /// 正在使用的对象个数
+    pub in_use: usize,
+    /// 从堆中申请的 slab 个数
+    pub slabs: usize,
+}
+
+/// 空闲对象中存放的链表节点
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+/// a cache of objects with the same size
+struct SlabCache {
+    obj_size: usize,
+    free_list: *mut FreeObject,
+    stats: SlabStats,
+}
+
+impl SlabCache {
+    const fn new(obj_size: usize) -> Self {
+        Self {
+            obj_size,
+            free_list: ptr::null_mut(),
+            stats: SlabStats {
+                allocs: 0,
+                frees: 0,
+                in_use: 0,
+                slabs: 0,
+            },
+        }
+    }
+
+    /// 从堆中申请一个新的 slab 并将其切分为空闲对象
+    fn grow<H: GlobalAlloc>(&mut self, heap: &H) -> bool {
+        let layout = Layout::from_size_align(config::PAGE_SIZE, config::PAGE_SIZE).unwrap();
+        let slab = unsafe { heap.alloc(layout) };
+        if slab.is_null() {
+            return false;
+        }
+        for offset in (0..config::PAGE_SIZE).step_by(self.obj_size) {
+            let obj = unsafe { slab.add(offset) } as *mut FreeObject;
+            unsafe {
+                obj.write(FreeObject {
+                    next: self.free_list,
+                })
+            };
+            self.free_list = obj;
+        }
+        self.stats.slabs += 1;
+        true
+    }
+
+    fn alloc<H: GlobalAlloc>(&mut self, heap: &H) -> *mut u8 {
+        if self.free_list.is_null() && !self.grow(heap) {
+            return ptr::null_mut();
+        }
+        let obj = self.free_list;
+        self.free_list = unsafe { (*obj).next };
+        self.stats.allocs += 1;
+        self.stats.in_use += 1;
+        obj as *mut u8
+    }
+
+    fn dealloc(&mut self, obj: *mut u8) {
+        let obj = obj as *mut FreeObject;
+        unsafe {
+            obj.write(FreeObject {
+                next: self.free_list,
+            })
+        };
+        self.free_list = obj;
+        self.stats.frees += 1;
+        self.stats.in_use -= 1;
+    }
+}
+
+/// global allocator which serves small allocations from slab caches
+/// and falls back to `heap` for the others
+pub struct SlabAllocator<H: GlobalAlloc + 'static> {
+    heap: &'static H,
+    caches: UPSafeCell<[SlabCache; NUM_SIZE_CLASSES]>,
+}
+
+/// 对象在 slab 中按其大小对齐，因此对齐要求不超过对象大小的请求都可以由 slab 满足
+fn size_class(layout: &Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    SIZE_CLASSES.iter().position(|&obj_size| size <= obj_size)
+}
+
+impl<H: GlobalAlloc + 'static> SlabAllocator<H> {
+    pub const fn new(heap: &'static H) -> Self {
+        Self {
+            heap,
+            caches: unsafe {
+                UPSafeCell::new([
+                    SlabCache::new(SIZE_CLASSES[0]),
+                    SlabCache::new(SIZE_CLASSES[1]),
+                    SlabCache::new(SIZE_CLASSES[2]),
+                    SlabCache::new(SIZE_CLASSES[3]),
+                    SlabCache::new(SIZE_CLASSES[4]),
+                    SlabCache::new(SIZE_CLASSES[5]),
+                    SlabCache::new(SIZE_CLASSES[6]),
+                    SlabCache::new(SIZE_CLASSES[7]),
+                ])
+            },
+        }
+    }
+
+    /// statistics of every slab cache, as `(object size, stats)`
+    pub fn stats(&self) -> [(usize, SlabStats); NUM_SIZE_CLASSES] {
+        let caches = self.caches.exclusive_access();
+        let mut stats = [(0, SlabStats::default()); NUM_SIZE_CLASSES];
+        for (stat, cache) in stats.iter_mut().zip(caches.iter()) {
+            *stat = (cache.obj_size, cache.stats);
+        }
+        stats
+    }
+}
+
+unsafe impl<H: GlobalAlloc + 'static> GlobalAlloc for SlabAllocator<H> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "fault_injection")]
+        if super::fault_inject::should_fail(super::fault_inject::FaultSite::Heap) {
+            return ptr::null_mut();
+        }
+        match size_class(&layout) {
+            Some(idx) => self.caches.exclusive_access()[idx].alloc(self.heap),
+            None => self.heap.alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match size_class(&layout) {
+            Some(idx) => self.caches.exclusive_access()[idx].dealloc(ptr),
+            None => self.heap.dealloc(ptr, layout),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Swapping pages of lazy areas out to a block device
+//!
+//! 物理页帧耗尽时，[`frame_alloc`] 调用 [`evict_one`] 用时钟算法从驻留内存的懒分配页面中选出一个
+//! 写入交换区，页表项改为记录交换槽位的交换项并释放其页帧；应用再次访问该页面时触发缺页异常，
+//! 由 [`SwapPage::swap_in`] 重新分配页帧并读回内容。没有初始化交换区时不会换出任何页面。
+//!
+//! 系统调用访问的用户缓冲区中的页面被 [`SwapPage::pin`] 固定，系统调用返回之前不会被换出
+
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::arch;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config;
+use crate::drivers::{BlockDevice, BLOCK_SZ};
+use crate::sync::{LazyInit, UPSafeCell};
+
+use super::address::{PhysPageNum, VirtPageNum};
+use super::frame_allocator::{frame_alloc, FrameTracker};
+use super::page_table::PageTable;
+
+/// 每个页面在交换区中占用的块数
+const BLOCKS_PER_PAGE: usize = config::PAGE_SIZE / BLOCK_SZ;
+
+/// the swap area at the start of a block device, divided into page-sized slots
+struct SwapArea {
+    device: Arc<dyn BlockDevice>,
+    /// 槽位总数
+    slots: usize,
+    /// 尚未使用过的槽位的起始编号
+    next: usize,
+    /// 被释放而等待再次分配的槽位
+    recycled: Vec<usize>,
+}
+
+/// the swap area, created by [`init_swap`]
+static SWAP_AREA: LazyInit<UPSafeCell<SwapArea>> = LazyInit::new();
+
+/// use the first `size` bytes of `device` as the swap area
+#[cfg_attr(not(feature = "swap"), allow(unused))]
+pub fn init_swap(device: Arc<dyn BlockDevice>, size: usize) {
+    let slots = size / config::PAGE_SIZE;
+    SWAP_AREA.init(unsafe {
+        UPSafeCell::new(SwapArea {
+            device,
+            slots,
+            next: 0,
+            recycled: Vec::new(),
+        })
+    });
+    println!("[kernel] swap area: {} slots", slots);
+}
+
+/// a slot of the swap area holding the content of a page, freed when dropped
+struct SwapSlot(usize);
+
+impl SwapSlot {
+    /// `None` if there is no swap area or it is full
+    fn alloc() -> Option<Self> {
+        let mut area = SWAP_AREA.get()?.exclusive_access();
+        let slot = match area.recycled.pop() {
+            Some(slot) => slot,
+            None if area.next < area.slots => {
+                area.next += 1;
+                area.next - 1
+            }
+            None => return None,
+        };
+        Some(Self(slot))
+    }
+
+    fn device() -> Arc<dyn BlockDevice> {
+        SWAP_AREA.exclusive_access().device.clone()
+    }
+
+    /// write the content of the frame `ppn` into the slot
+    fn write(&self, ppn: PhysPageNum) {
+        let device = Self::device();
+        for (i, block) in ppn.as_bytes_mut().chunks(BLOCK_SZ).enumerate() {
+            device.write_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+
+    /// read the content of the slot into the frame `ppn`
+    fn read(&self, ppn: PhysPageNum) {
+        let device = Self::device();
+        for (i, block) in ppn.as_bytes_mut().chunks_mut(BLOCK_SZ).enumerate() {
+            device.read_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_AREA.exclusive_access().recycled.push(self.0);
+    }
+}
+
+/// where the content of a [`SwapPage`] is
+enum PageState {
+    Resident(FrameTracker),
+    Swapped(SwapSlot),
+}
+
+/// a page of a lazy area, which may be swapped out when frames run out
+///
+/// 由所属的逻辑段持有，被释放时归还其页帧或交换槽位
+pub struct SwapPage {
+    state: UPSafeCell<PageState>,
+    /// 被固定的次数，不为 0 时不会被换出
+    pins: AtomicUsize,
+}
+
+/// a [`SwapPage`] kept from being swapped out until dropped
+pub struct PinnedPage(Arc<SwapPage>);
+
+impl Drop for PinnedPage {
+    fn drop(&mut self) {
+        self.0.pins.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 驻留内存的 [`SwapPage`] 的个数
+static RESIDENT: AtomicUsize = AtomicUsize::new(0);
+
+impl SwapPage {
+    /// a page resident in `frame`
+    pub fn new(frame: FrameTracker) -> Arc<Self> {
+        RESIDENT.fetch_add(1, Ordering::Relaxed);
+        Arc::new(Self {
+            state: unsafe { UPSafeCell::new(PageState::Resident(frame)) },
+            pins: AtomicUsize::new(0),
+        })
+    }
+
+    /// Keep the resident `page` from being swapped out until the returned guard is dropped.
+    pub fn pin(page: &Arc<Self>) -> PinnedPage {
+        page.pins.fetch_add(1, Ordering::Relaxed);
+        PinnedPage(page.clone())
+    }
+
+    /// the frame holding the page, `None` if it is swapped out
+    pub fn ppn(&self) -> Option<PhysPageNum> {
+        match &*self.state.exclusive_access() {
+            PageState::Resident(frame) => Some(frame.ppn),
+            PageState::Swapped(_) => None,
+        }
+    }
+
+    /// Read the swapped-out page back into a new frame and return the frame,
+    /// `None` if out of frames.
+    ///
+    /// 调用者建立映射后还需调用 [`track`] ，页面才会再次被换出
+    pub fn swap_in(&self) -> Option<PhysPageNum> {
+        // 先分配页帧：分配时可能换出其他页面
+        let frame = frame_alloc()?;
+        let ppn = frame.ppn;
+        let mut state = self.state.exclusive_access();
+        let PageState::Swapped(slot) = &*state else {
+            panic!("swapping in a resident page");
+        };
+        slot.read(ppn);
+        // 交换槽位随之释放
+        *state = PageState::Resident(frame);
+        RESIDENT.fetch_add(1, Ordering::Relaxed);
+        Some(ppn)
+    }
+}
+
+impl Drop for SwapPage {
+    fn drop(&mut self) {
+        if let PageState::Resident(_) = &*self.state.exclusive_access() {
+            RESIDENT.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// a resident page on the clock of [`evict_one`]
+struct ClockEntry {
+    page: Weak<SwapPage>,
+    /// 页面所在地址空间的 `token`
+    token: usize,
+    vpn: VirtPageNum,
+}
+
+/// 驻留内存的懒分配页面排成的环，队首是时钟指针指向的页面
+///
+/// 页面被释放后留下的失效项在指针扫过时丢弃
+static CLOCK: UPSafeCell<VecDeque<ClockEntry>> = unsafe { UPSafeCell::new(VecDeque::new()) };
+
+/// Put `page`, which has just been mapped at `vpn` of the address space `token`,
+/// on the clock so that it can be swapped out.
+pub fn track(page: &Arc<SwapPage>, token: usize, vpn: VirtPageNum) {
+    let mut clock = CLOCK.exclusive_access();
+    // 失效项多于驻留的页面时一并清理，环的长度因此不会无限增长
+    if clock.len() > 2 * RESIDENT.load(Ordering::Relaxed) {
+        clock.retain(|entry| entry.page.strong_count() > 0);
+    }
+    clock.push_back(ClockEntry {
+        page: Arc::downgrade(page),
+        token,
+        vpn,
+    });
+}
+
+/// Swap out a resident page chosen by the clock algorithm and free its frame,
+/// return `false` if there is no free swap slot or no page to swap out.
+///
+/// 指针扫过的页面若被访问过（页表项的 A 位被硬件置位），清除 A 位并跳过，给它第二次机会；
+/// 被固定的页面直接跳过。每个页面最多被扫过两次
+pub fn evict_one() -> bool {
+    let Some(slot) = SwapSlot::alloc() else {
+        return false;
+    };
+    let victim = {
+        let mut clock = CLOCK.exclusive_access();
+        let mut victim = None;
+        for _ in 0..2 * clock.len() {
+            let Some(entry) = clock.pop_front() else {
+                break;
+            };
+            let Some(page) = entry.page.upgrade() else {
+                continue;
+            };
+            if page.pins.load(Ordering::Relaxed) > 0 {
+                clock.push_back(entry);
+                continue;
+            }
+            if PageTable::from_token(entry.token).take_accessed(entry.vpn) {
+                clock.push_back(entry);
+            } else {
+                victim = Some((page, entry));
+                break;
+            }
+        }
+        victim
+    };
+    let evicted = match victim {
+        Some((page, entry)) => {
+            let mut state = page.state.exclusive_access();
+            let PageState::Resident(frame) = &*state else {
+                panic!("swapping out a page not resident");
+            };
+            slot.write(frame.ppn);
+            PageTable::from_token(entry.token).mark_swapped(entry.vpn, slot.0);
+            // 页帧随之释放
+            *state = PageState::Swapped(slot);
+            RESIDENT.fetch_sub(1, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    };
+    // 清除的 A 位和换出页面的映射可能还在快表中
+    unsafe {
+        arch::asm!("sfence.vma");
+    }
+    evicted
+}
@@ -2,6 +2,7 @@ use core::arch::asm;
 
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
+const SBI_CONSOLE_GETCHAR: usize = 2;
 
 ///  handle SBI call with `which` SBI_id and other arguments
 #[inline(always)]
@@ -25,6 +26,11 @@ pub fn console_putchar(c: usize) {
     sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
 }
 
+/// use sbi call to getchar from console (qemu uart handler), return `usize::MAX` if there is no input
+pub fn console_getchar() -> usize {
+    sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
+}
+
 #[cfg(feature = "board_qemu")]
 use crate::board::QEMUExit;
 /// use sbi call to shutdown the kernel
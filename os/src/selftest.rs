@@ -0,0 +1,70 @@
+//! Boot-time kernel self tests
+//!
+//! 在 `kernel_selftest` 特性下，内核完成初始化后依次运行所有自检，然后退出 QEMU ，
+//! 不再运行任何应用。自检失败时 panic ，panic 处理函数以失败状态退出 QEMU 。
+//!
+//! 随机化的测试使用的种子在启动时打印，构建时设置环境变量 `SELFTEST_SEED` 可以复现某次运行。
+
+use crate::{loader, mm, timer};
+
+/// xorshift64* pseudo random number generator for randomized tests
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // 状态不能为 0
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// 在 `[0, bound)` 中均匀（忽略取模带来的微小偏差）地取一个数
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// 自检的名字和函数，不需要随机数的自检忽略传入的 `Rng`
+type SelfTest = (&'static str, fn(&mut Rng));
+
+/// 所有自检，按顺序运行
+const TESTS: &[SelfTest] = &[
+    ("heap", |_| mm::heap_test()),
+    ("heap_fuzz", mm::heap_fuzz_test),
+    ("frame_allocator", |_| mm::frame_allocator_test()),
+    ("remap", |_| mm::remap_test()),
+    ("page_table_stress", |_| mm::page_table_stress_test()),
+    (
+        "translated_byte_buffer_fuzz",
+        mm::translated_byte_buffer_fuzz_test,
+    ),
+    ("elf_loader", |_| loader::elf_loader_test()),
+    ("timer", |_| timer::timer_test()),
+];
+
+/// run every self test and exit QEMU with success, a failed test panics
+pub fn run() -> ! {
+    let seed: u64 = option_env!("SELFTEST_SEED")
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(timer::get_time() as u64);
+    println!("[selftest] seed {}", seed);
+    let mut rng = Rng::new(seed);
+    for (idx, (name, test)) in TESTS.iter().enumerate() {
+        println!("[selftest] ({}/{}) {}", idx + 1, TESTS.len(), name);
+        test(&mut rng);
+    }
+    println!("[selftest] all {} tests passed", TESTS.len());
+    #[cfg(feature = "board_qemu")]
+    {
+        use crate::board::QEMUExit;
+        crate::board::QEMU_EXIT_HANDLE.exit_success();
+    }
+    #[cfg(not(feature = "board_qemu"))]
+    crate::sbi::shutdown()
+}
@@ -0,0 +1,65 @@
+//! Explicitly initialized global data
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A global value initialized exactly once by an explicit call to [`LazyInit::init`].
+///
+/// 与 `lazy_static!` 在第一次访问时隐式初始化不同，初始化的时机由 `rust_main` 中的调用顺序决定；
+/// 初始化之前访问或者重复初始化都会 panic ，子系统之间的依赖顺序因此不会被悄悄打乱
+pub struct LazyInit<T> {
+    initialized: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// 值由调用 `init` 的上下文放入，却可以被任何上下文通过共享引用访问，因此还要求 `T: Send`
+unsafe impl<T: Send + Sync> Sync for LazyInit<T> {}
+
+impl<T> LazyInit<T> {
+    pub const fn new() -> Self {
+        Self {
+            initialized: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initialize the value, panic if it has been initialized.
+    #[track_caller]
+    pub fn init(&self, value: T) {
+        if self.initialized.load(Ordering::Acquire) {
+            panic!(
+                "LazyInit<{}> initialized twice",
+                core::any::type_name::<T>()
+            );
+        }
+        unsafe { (*self.value.get()).write(value) };
+        self.initialized.store(true, Ordering::Release);
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    /// the value, `None` before initialization
+    pub fn get(&self) -> Option<&T> {
+        self.is_initialized()
+            .then(|| unsafe { (*self.value.get()).assume_init_ref() })
+    }
+}
+
+impl<T> Deref for LazyInit<T> {
+    type Target = T;
+
+    /// Panic if the value has not been initialized.
+    #[track_caller]
+    fn deref(&self) -> &T {
+        self.get().unwrap_or_else(|| {
+            panic!(
+                "LazyInit<{}> used before initialization",
+                core::any::type_name::<T>()
+            )
+        })
+    }
+}
@@ -0,0 +1,174 @@
+//! Lock ordering checker
+//!
+//! 只在 `lockdep` 特性下编译。每次获取 [`super::UPSafeCell`] 或 [`super::UPRwCell`] 时，
+//! 记录「已持有的锁 -> 新获取的锁」这一获取顺序，若新的顺序与已记录的顺序构成环，
+//! 说明在多核或可抢占的内核中可能发生死锁，输出两处获取的代码位置。
+//!
+//! 内核在内核态不响应中断，且任务切换前会释放所有锁，因此只需一个全局的持有锁栈。
+//! 检查器本身不能使用被检查的锁或堆，因此所有状态保存在定长数组中。
+
+use core::cell::UnsafeCell;
+use core::panic::Location;
+
+/// 最多同时持有的锁的个数
+const MAX_HELD: usize = 16;
+/// 最多记录的获取顺序的个数
+const MAX_EDGES: usize = 256;
+/// 查找获取顺序中的环时最多访问的锁的个数
+const MAX_SEARCH: usize = 32;
+
+/// a lock being held, identified by its address
+#[derive(Copy, Clone)]
+struct HeldLock {
+    lock: usize,
+    location: &'static Location<'static>,
+}
+
+/// `to` has been acquired while holding `from`
+#[derive(Copy, Clone)]
+struct Edge {
+    from: usize,
+    to: usize,
+    /// 第一次以这个顺序获取 `to` 的位置
+    location: &'static Location<'static>,
+}
+
+struct LockDep {
+    held: [Option<HeldLock>; MAX_HELD],
+    edges: [Option<Edge>; MAX_EDGES],
+    num_edges: usize,
+    /// 正在检查器内部，避免输出报告时再次进入检查器
+    busy: bool,
+    /// 表满之后只报告一次
+    overflow_reported: bool,
+}
+
+struct LockDepCell(UnsafeCell<LockDep>);
+
+unsafe impl Sync for LockDepCell {}
+
+static LOCKDEP: LockDepCell = LockDepCell(UnsafeCell::new(LockDep {
+    held: [None; MAX_HELD],
+    edges: [None; MAX_EDGES],
+    num_edges: 0,
+    busy: false,
+    overflow_reported: false,
+}));
+
+/// 在检查器状态上执行 `f` ，检查器已在执行时直接忽略
+fn with_lockdep(f: impl FnOnce(&mut LockDep)) {
+    // 单核且内核态不响应中断，只有 `f` 内部的输出才会重入这里，由 `busy` 排除
+    let lockdep = unsafe { &mut *LOCKDEP.0.get() };
+    if lockdep.busy {
+        return;
+    }
+    lockdep.busy = true;
+    f(lockdep);
+    lockdep.busy = false;
+}
+
+impl LockDep {
+    fn edge(&self, from: usize, to: usize) -> Option<&Edge> {
+        self.edges[..self.num_edges]
+            .iter()
+            .flatten()
+            .find(|edge| edge.from == from && edge.to == to)
+    }
+
+    /// 已记录的获取顺序中是否存在从 `from` 到 `to` 的路径，返回路径上的第一条边
+    fn path(&self, from: usize, to: usize) -> Option<Edge> {
+        // 广度优先搜索，`queue` 中记录到达的锁以及到达它的路径上第一条边的下标
+        let mut queue: [(usize, usize); MAX_SEARCH] = [(0, 0); MAX_SEARCH];
+        let mut len: usize = 0;
+        let mut head: usize = 0;
+        let mut node = from;
+        let mut first_edge: Option<usize> = None;
+        loop {
+            for (idx, edge) in self.edges[..self.num_edges].iter().enumerate() {
+                let Some(edge) = edge else {
+                    continue;
+                };
+                if edge.from != node || queue[..len].iter().any(|&(lock, _)| lock == edge.to) {
+                    continue;
+                }
+                let first = first_edge.unwrap_or(idx);
+                if edge.to == to {
+                    return self.edges[first];
+                }
+                if len < MAX_SEARCH {
+                    queue[len] = (edge.to, first);
+                    len += 1;
+                }
+            }
+            if head == len {
+                return None;
+            }
+            (node, first_edge) = (queue[head].0, Some(queue[head].1));
+            head += 1;
+        }
+    }
+
+    fn acquire(&mut self, lock: usize, location: &'static Location<'static>) {
+        for held in self.held.iter().flatten() {
+            // 同一把锁的嵌套获取由 `RefCell` 自己检查
+            if held.lock == lock || self.edge(held.lock, lock).is_some() {
+                continue;
+            }
+            if let Some(reverse) = self.path(lock, held.lock) {
+                emergency_println!(
+                    "[lockdep] possible deadlock: lock {:#x} acquired at {} while holding lock {:#x} acquired at {}, \
+                     but the reverse order was established at {}",
+                    lock,
+                    location,
+                    held.lock,
+                    held.location,
+                    reverse.location
+                );
+                continue;
+            }
+            match self.edges.get_mut(self.num_edges) {
+                Some(slot) => {
+                    *slot = Some(Edge {
+                        from: held.lock,
+                        to: lock,
+                        location,
+                    });
+                    self.num_edges += 1;
+                }
+                None if !self.overflow_reported => {
+                    self.overflow_reported = true;
+                    emergency_println!(
+                        "[lockdep] too many lock orderings, stop recording new ones"
+                    );
+                }
+                None => {}
+            }
+        }
+        match self.held.iter_mut().find(|held| held.is_none()) {
+            Some(slot) => *slot = Some(HeldLock { lock, location }),
+            None => emergency_println!("[lockdep] too many locks held at {}", location),
+        }
+    }
+
+    fn release(&mut self, lock: usize) {
+        // 锁不一定按获取的相反顺序释放，释放最近一次获取的那个
+        if let Some(slot) = self
+            .held
+            .iter_mut()
+            .rev()
+            .find(|held| held.map(|held| held.lock) == Some(lock))
+        {
+            *slot = None;
+        }
+    }
+}
+
+/// Record that the lock at address `lock` is acquired at `location`.
+pub fn acquire(lock: usize, location: &'static Location<'static>) {
+    with_lockdep(|lockdep| lockdep.acquire(lock, location));
+}
+
+/// Record that the lock at address `lock` is released.
+pub fn release(lock: usize) {
+    with_lockdep(|lockdep| lockdep.release(lock));
+}
@@ -1,5 +1,9 @@
 //! Synchronization and interior mutability primitives
 
+mod lazy;
+#[cfg(feature = "lockdep")]
+mod lockdep;
 mod up;
 
-pub use up::UPSafeCell;
+pub use lazy::LazyInit;
+pub use up::{UPRefMut, UPRwCell, UPSafeCell};
@@ -1,6 +1,25 @@
 //! Uniprocessor interior mutability primitives
 
-use core::cell::{RefCell, RefMut};
+#[cfg(feature = "borrow_tracking")]
+use core::cell::Cell;
+use core::cell::{Ref, RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+#[cfg(any(feature = "lockdep", feature = "borrow_tracking"))]
+use core::panic::Location;
+
+/// 最近一次借用的代码位置，借用失败时说明冲突的借用来自哪里
+#[cfg(feature = "borrow_tracking")]
+type BorrowLocation = Cell<Option<&'static Location<'static>>>;
+
+/// panic with the location of the conflicting borrow
+#[cfg(feature = "borrow_tracking")]
+#[track_caller]
+fn already_borrowed(kind: &str, borrowed_at: &BorrowLocation) -> ! {
+    match borrowed_at.get() {
+        Some(location) => panic!("already {} borrowed at {}", kind, location),
+        None => panic!("already {} borrowed", kind),
+    }
+}
 
 /// Wrap a static data structure inside it so that we are
 /// able to access it without any `unsafe`.
@@ -12,6 +31,8 @@ use core::cell::{RefCell, RefMut};
 pub struct UPSafeCell<T> {
     /// inner data
     inner: RefCell<T>,
+    #[cfg(feature = "borrow_tracking")]
+    borrowed_at: BorrowLocation,
 }
 
 unsafe impl<T> Sync for UPSafeCell<T> {}
@@ -19,13 +40,189 @@ unsafe impl<T> Sync for UPSafeCell<T> {}
 impl<T> UPSafeCell<T> {
     /// User is responsible to guarantee that inner struct is only used in
     /// uniprocessor.
-    pub unsafe fn new(value: T) -> Self {
+    pub const unsafe fn new(value: T) -> Self {
         Self {
             inner: RefCell::new(value),
+            #[cfg(feature = "borrow_tracking")]
+            borrowed_at: Cell::new(None),
         }
     }
     /// Exclusive access inner data in UPSafeCell. Panic if the data has been borrowed.
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    ///
+    /// 在 `borrow_tracking` 特性下，panic 信息中包含已有借用的代码位置
+    #[track_caller]
+    pub fn exclusive_access(&self) -> UPRefMut<'_, T> {
+        #[cfg(feature = "borrow_tracking")]
+        let inner = match self.inner.try_borrow_mut() {
+            Ok(inner) => {
+                self.borrowed_at.set(Some(Location::caller()));
+                inner
+            }
+            Err(_) => already_borrowed("mutably", &self.borrowed_at),
+        };
+        #[cfg(not(feature = "borrow_tracking"))]
+        let inner = self.inner.borrow_mut();
+        UPRefMut::new(self as *const _ as usize, inner)
+    }
+    /// Exclusive access inner data in UPSafeCell, `None` if the data has been borrowed.
+    ///
+    /// 用于 panic 处理等不能再次 panic 的场合
+    #[track_caller]
+    pub fn try_exclusive_access(&self) -> Option<UPRefMut<'_, T>> {
+        let inner = self.inner.try_borrow_mut().ok()?;
+        #[cfg(feature = "borrow_tracking")]
+        self.borrowed_at.set(Some(Location::caller()));
+        Some(UPRefMut::new(self as *const _ as usize, inner))
+    }
+}
+
+/// Reader-writer variant of [`UPSafeCell`] for read-mostly global data.
+///
+/// Any number of shared borrows may be alive at the same time, e.g. when
+/// logging or tracing reads the data again while it is already being read.
+/// A `write` borrow is still exclusive and panics if the data has been borrowed.
+///
+/// We should only use it in uniprocessor.
+pub struct UPRwCell<T> {
+    /// inner data
+    inner: RefCell<T>,
+    #[cfg(feature = "borrow_tracking")]
+    read_at: BorrowLocation,
+    #[cfg(feature = "borrow_tracking")]
+    written_at: BorrowLocation,
+}
+
+unsafe impl<T> Sync for UPRwCell<T> {}
+
+impl<T> UPRwCell<T> {
+    /// User is responsible to guarantee that inner struct is only used in
+    /// uniprocessor.
+    pub const unsafe fn new(value: T) -> Self {
+        Self {
+            inner: RefCell::new(value),
+            #[cfg(feature = "borrow_tracking")]
+            read_at: Cell::new(None),
+            #[cfg(feature = "borrow_tracking")]
+            written_at: Cell::new(None),
+        }
+    }
+    /// Shared access inner data in UPRwCell. Panic if the data has been mutably borrowed.
+    #[track_caller]
+    pub fn read(&self) -> UPRef<'_, T> {
+        #[cfg(feature = "borrow_tracking")]
+        let inner = match self.inner.try_borrow() {
+            Ok(inner) => {
+                self.read_at.set(Some(Location::caller()));
+                inner
+            }
+            Err(_) => already_borrowed("mutably", &self.written_at),
+        };
+        #[cfg(not(feature = "borrow_tracking"))]
+        let inner = self.inner.borrow();
+        UPRef::new(self as *const _ as usize, inner)
+    }
+    /// Exclusive access inner data in UPRwCell. Panic if the data has been borrowed.
+    ///
+    /// 在 `borrow_tracking` 特性下，已有多个共享借用时只能报告其中最近的一个
+    #[track_caller]
+    pub fn write(&self) -> UPRefMut<'_, T> {
+        #[cfg(feature = "borrow_tracking")]
+        let inner = match self.inner.try_borrow_mut() {
+            Ok(inner) => {
+                self.written_at.set(Some(Location::caller()));
+                inner
+            }
+            Err(_) if self.inner.try_borrow().is_ok() => {
+                already_borrowed("immutably", &self.read_at)
+            }
+            Err(_) => already_borrowed("mutably", &self.written_at),
+        };
+        #[cfg(not(feature = "borrow_tracking"))]
+        let inner = self.inner.borrow_mut();
+        UPRefMut::new(self as *const _ as usize, inner)
+    }
+}
+
+/// Shared borrow of the data in a [`UPRwCell`].
+///
+/// 在 `lockdep` 特性下，获取和释放时通知锁顺序检查器
+pub struct UPRef<'a, T> {
+    inner: Ref<'a, T>,
+    #[cfg(feature = "lockdep")]
+    lock: usize,
+}
+
+impl<'a, T> UPRef<'a, T> {
+    #[track_caller]
+    fn new(lock: usize, inner: Ref<'a, T>) -> Self {
+        #[cfg(feature = "lockdep")]
+        super::lockdep::acquire(lock, Location::caller());
+        #[cfg(not(feature = "lockdep"))]
+        let _ = lock;
+        Self {
+            inner,
+            #[cfg(feature = "lockdep")]
+            lock,
+        }
+    }
+}
+
+impl<T> Deref for UPRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for UPRef<'_, T> {
+    fn drop(&mut self) {
+        super::lockdep::release(self.lock);
+    }
+}
+
+/// Exclusive borrow of the data in a [`UPSafeCell`] or [`UPRwCell`].
+///
+/// 在 `lockdep` 特性下，获取和释放时通知锁顺序检查器
+pub struct UPRefMut<'a, T> {
+    inner: RefMut<'a, T>,
+    #[cfg(feature = "lockdep")]
+    lock: usize,
+}
+
+impl<'a, T> UPRefMut<'a, T> {
+    #[track_caller]
+    fn new(lock: usize, inner: RefMut<'a, T>) -> Self {
+        #[cfg(feature = "lockdep")]
+        super::lockdep::acquire(lock, Location::caller());
+        #[cfg(not(feature = "lockdep"))]
+        let _ = lock;
+        Self {
+            inner,
+            #[cfg(feature = "lockdep")]
+            lock,
+        }
+    }
+}
+
+impl<T> Deref for UPRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UPRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for UPRefMut<'_, T> {
+    fn drop(&mut self) {
+        super::lockdep::release(self.lock);
     }
 }
@@ -1,22 +1,170 @@
 //! File and filesystem-related syscalls
 
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
+use crate::fs::{self, FileDesc, OpenFlags};
+use crate::mm::{copy_struct_to_user, translated_byte_buffer, UserAccess, UserBuffer};
+use crate::task::{self, current_user_token};
+use crate::tty::{self, TtyMode};
 
+use abi::{Dirent, IoStats, Stat, EFAULT};
+
+use super::validate::user_str;
+use super::MAX_PATH_LEN;
+
+const FD_STDIN: usize = 0;
 const FD_STDOUT: usize = 1;
 
-/// write buf of length `len`  to a file with `fd`
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
+/// ioctl request: get the line discipline mode of the terminal, 0 for cooked and 1 for raw
+const TTY_GET_MODE: usize = 1;
+/// ioctl request: set the line discipline mode of the terminal to `arg`
+const TTY_SET_MODE: usize = 2;
+
+/// open the file named by the NUL-terminated `path` with `flags`, return its fd,
+/// or -1 if `path` is invalid or the file can not be opened
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let Some(path) = user_str(path, MAX_PATH_LEN) else {
+        return -1;
+    };
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -1;
+    };
+    match fs::open_file(&path, flags) {
+        Some(inode) => task::add_current_file(FileDesc::new(&path, inode)) as isize,
+        None => -1,
+    }
+}
+
+/// create the directory named by the NUL-terminated `path`, return -1 if `path` is
+/// invalid, exists or its parent does not exist
+pub fn sys_mkdir(path: *const u8) -> isize {
+    let Some(path) = user_str(path, MAX_PATH_LEN) else {
+        return -1;
+    };
+    if fs::make_dir(&path) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// copy at most `count` entries of the directory with `fd` to `dirents`, continuing
+/// from where the last call stopped, return the number of entries copied, which is 0
+/// at the end of the directory, or -1 if `fd` is not an open directory
+pub fn sys_getdents(fd: usize, dirents: *mut Dirent, count: usize) -> isize {
+    let Some(entries) = task::current_file(fd).and_then(|desc| desc.dirents(count)) else {
+        return -1;
+    };
+    let token = current_user_token();
+    for (i, entry) in entries.iter().enumerate() {
+        copy_struct_to_user(token, dirents.wrapping_add(i), entry);
+    }
+    entries.len() as isize
+}
+
+/// close the file with `fd`, return -1 if `fd` is not open
+pub fn sys_close(fd: usize) -> isize {
+    match task::take_current_file(fd) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// read from a file with `fd` into buf of length `len`, return the number of bytes read,
+/// or -1 if `fd` is not open for reading
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let Some(file) = task::current_file(fd).filter(|file| file.readable()) else {
+        return -1;
+    };
+    // 读取可能阻塞并切换任务，此时只持有文件的引用计数。阻塞期间缓冲区所在的页面可能被换出，
+    // 因此醒来之后重新检查缓冲区并换入其中的页面，之后才翻译缓冲区，读取本身不会再阻塞
+    if file.wait_readable()
+        && !task::prepare_current_user_range(buf as usize, len, UserAccess::Write)
+    {
+        return -EFAULT;
+    }
+    file.read(UserBuffer::new(translated_byte_buffer(
+        current_user_token(),
+        buf,
+        len,
+    ))) as isize
+}
+
+/// device-specific control of a file with `fd`, only the terminal is supported
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    if fd != FD_STDIN && fd != FD_STDOUT {
+        return -1;
+    }
+    match request {
+        TTY_GET_MODE => tty::mode() as isize,
+        TTY_SET_MODE => match TtyMode::from_usize(arg) {
+            Some(mode) => {
+                tty::set_mode(mode);
+                0
             }
-            len as isize
-        }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
-        }
+            None => -1,
+        },
+        _ => -1,
     }
 }
+
+/// write buf of length `len` to a file with `fd`, return the number of bytes written,
+/// or -1 if `fd` is not open for writing
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    let Some(file) = task::current_file(fd).filter(|file| file.writable()) else {
+        return -1;
+    };
+    file.write(UserBuffer::new(translated_byte_buffer(
+        current_user_token(),
+        buf,
+        len,
+    ))) as isize
+}
+
+/// copy the status of the file with `fd` to `st`, return -1 if `fd` is not open
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    let Some(desc) = task::current_file(fd) else {
+        return -1;
+    };
+    copy_struct_to_user(current_user_token(), st, &desc.stat());
+    0
+}
+
+/// copy the I/O statistics of the file with `fd` to `stats`, return -1 if `fd` is not open
+pub fn sys_fd_stat(fd: usize, stats: *mut IoStats) -> isize {
+    let Some(desc) = task::current_file(fd) else {
+        return -1;
+    };
+    copy_struct_to_user(current_user_token(), stats, &desc.stats());
+    0
+}
+
+/// print the I/O statistics of every open fd of every task on the kernel console,
+/// like `iostat`, return the number of open fds
+pub fn sys_iostat() -> isize {
+    task::print_io_stats() as isize
+}
+
+/// discard every write to the file system since boot, return -1 if the kernel
+/// is built without the `fs_overlay` feature or another task has a file of the file
+/// system open
+///
+/// 回滚之后打开的文件不能再使用，只有调用者自己的文件可以由它自己负责不再使用
+pub fn sys_fs_rollback() -> isize {
+    if task::others_have_fs_files_open() {
+        log::warn!(
+            "[kernel] {} can not roll back the file system while other tasks have files open",
+            task::current_desc()
+        );
+        return -1;
+    }
+    if fs::rollback() {
+        0
+    } else {
+        -1
+    }
+}
+
+/// write all modified blocks of the file system back to the disk
+pub fn sys_sync() -> isize {
+    fs::sync();
+    0
+}
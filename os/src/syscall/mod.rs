@@ -12,26 +12,78 @@
 
 mod fs;
 mod process;
+mod validate;
 
-// use crate::task;
+use crate::task;
+use abi::*;
 
-// use self::process::{TaskInfo, TimeVal};
-// use self::process::TimeVal;
+/// 路径的最大长度，不包括结尾的 0
+const MAX_PATH_LEN: usize = 255;
 
-const SYSCALL_WRITE: usize = 64;
-const SYSCALL_EXIT: usize = 93;
-const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
-// const SYSCALL_TASK_INFO: usize = 410;
-
-/// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+/// handle syscall exception with `syscall_id` and the arguments passed in `a0`~`a5`
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     // task::update_current_syscall_times(syscall_id);
+    let ret = match self::validate::check_user_buffers(syscall_id, &args) {
+        Ok(()) => dispatch(syscall_id, args),
+        Err(err) => err,
+    };
+    // 用户缓冲区中的页面只在系统调用期间固定在内存中
+    task::unpin_current_user_pages();
+    ret
+}
+
+fn dispatch(syscall_id: usize, args: [usize; 6]) -> isize {
     match syscall_id {
+        SYSCALL_IOCTL => self::fs::sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_MKDIR => self::fs::sys_mkdir(args[0] as *const u8),
+        SYSCALL_OPEN => self::fs::sys_open(args[0] as *const u8, args[1] as u32),
+        SYSCALL_CLOSE => self::fs::sys_close(args[0]),
+        SYSCALL_GETDENTS => self::fs::sys_getdents(args[0], args[1] as *mut Dirent, args[2]),
+        SYSCALL_READ => self::fs::sys_read(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => self::fs::sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_FSTAT => self::fs::sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_SYNC => self::fs::sys_sync(),
         SYSCALL_EXIT => self::process::sys_exit(args[0] as i32),
+        SYSCALL_SLEEP => self::process::sys_sleep(args[0]),
+        SYSCALL_SCHED_SETSCHEDULER => self::process::sys_sched_setscheduler(args[0], args[1]),
+        SYSCALL_SCHED_GETSCHEDULER => self::process::sys_sched_getscheduler(args[0]),
         SYSCALL_YIELD => self::process::sys_yield(),
-        SYSCALL_GET_TIME => self::process::sys_get_time(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_SET_PRIORITY => self::process::sys_set_priority(args[0] as isize),
+        SYSCALL_GET_PRIORITY => self::process::sys_get_priority(),
+        SYSCALL_GETRLIMIT => self::process::sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => self::process::sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_UMASK => self::process::sys_umask(args[0] as u32),
+        SYSCALL_PRCTL => self::process::sys_prctl(args[0], args[1]),
+        SYSCALL_GETCPU => self::process::sys_getcpu(args[0] as *mut u32, args[1] as *mut u32),
+        SYSCALL_GET_TIME => self::process::sys_get_time(args[0] as *mut TimeVal),
+        SYSCALL_GETPID => self::process::sys_getpid(),
+        SYSCALL_GETPPID => self::process::sys_getppid(),
+        SYSCALL_SBRK => self::process::sys_sbrk(args[0] as isize),
+        SYSCALL_MUNMAP => self::process::sys_munmap(args[0], args[1]),
+        SYSCALL_MMAP => {
+            self::process::sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5])
+        }
+        SYSCALL_WAITPID => self::process::sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_SPAWN => self::process::sys_spawn(args[0] as *const u8),
+        SYSCALL_TASK_INFO => self::process::sys_task_info(args[0] as *mut TaskInfo),
+        SYSCALL_FAULT_INJECT => self::process::sys_fault_inject(args[0], args[1], args[2]),
+        SYSCALL_GET_MAPS => self::process::sys_get_maps(args[0] as *mut u8, args[1]),
+        SYSCALL_YIELD_TO => self::process::sys_yield_to(args[0]),
+        SYSCALL_PERF_READ => self::process::sys_perf_read(args[0] as *mut PerfCounters),
+        SYSCALL_HART_COUNT => self::process::sys_hart_count(),
+        SYSCALL_MEM_REPORT => self::process::sys_mem_report(args[0] as *mut MemReport),
+        SYSCALL_SET_TIME_SLICE => self::process::sys_set_time_slice(args[0]),
+        SYSCALL_GET_TIME_SLICE => self::process::sys_get_time_slice(),
+        SYSCALL_GET_APP_NAMES => self::process::sys_get_app_names(args[0] as *mut u8, args[1]),
+        SYSCALL_FIND_APP => self::process::sys_find_app(args[0] as *const u8),
+        SYSCALL_PS => self::process::sys_ps(),
+        SYSCALL_FD_STAT => self::fs::sys_fd_stat(args[0], args[1] as *mut IoStats),
+        SYSCALL_IOSTAT => self::fs::sys_iostat(),
+        SYSCALL_FS_ROLLBACK => self::fs::sys_fs_rollback(),
+        SYSCALL_DMESG => self::process::sys_dmesg(args[0] as *mut u8, args[1]),
+        _ => {
+            task::report_unsupported_syscall(syscall_id);
+            -ENOSYS
+        }
     }
 }
@@ -1,28 +1,425 @@
 //! Process management syscalls
 
-use crate::timer;
+use crate::config::{PAGE_SIZE, USER_SPACE_END};
+use crate::loader;
+use crate::mm::{
+    copy_struct_from_user, copy_struct_to_user, translated_byte_buffer, MapPermission,
+};
+use crate::task::{self, SchedClass, TaskName};
+use crate::{hart, logging, mm, timer};
+use abi::{
+    MemReport, PerfCounters, RLimit, TaskInfo, TimeVal, MAP_ANONYMOUS, MAP_PRIVATE, PROT_EXEC,
+    PROT_READ, PROT_WRITE, PR_GET_NAME, PR_SET_NAME, RLIMIT_CPU, TASK_NAME_LEN,
+};
+
+use super::validate::{user_c_bytes, user_str};
+use super::MAX_PATH_LEN;
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
-    println!("[kernel] Application exited with code {}", exit_code);
-    crate::task::exit_current_and_run_next();
+    println!(
+        "[kernel] {} exited with code {}",
+        task::current_desc(),
+        exit_code
+    );
+    crate::task::exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
+/// pid of current task
+pub fn sys_getpid() -> isize {
+    task::current_pid().0 as isize
+}
+
+/// pid of the parent of current task, 0 if it has none
+pub fn sys_getppid() -> isize {
+    task::current_pid().1.unwrap_or(0) as isize
+}
+
+/// block current task for at least `ms` milliseconds
+///
+/// 睡眠的任务不会被调度，由时钟中断在到期后唤醒，因此实际睡眠时间按时钟中断的周期向上取整
+pub fn sys_sleep(ms: usize) -> isize {
+    task::sleep_current_and_run_next(ms);
+    0
+}
+
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     crate::task::suspend_current_and_run_next();
     0
 }
 
-#[repr(C)]
-#[derive(Debug)]
-pub struct TimeVal {
-    pub sec: usize,
-    pub usec: usize,
+/// current task gives up resources to task `pid`, return -1 without yielding
+/// if `pid` is not a `Ready` task
+pub fn sys_yield_to(pid: usize) -> isize {
+    if task::yield_to(pid) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// move task `pid` into scheduling class `policy`: 0 for normal, 1 for interactive
+/// and 2 for idle, return -1 if `policy` is unknown
+///
+/// 目前 `pid` 只能为 0 ，表示当前任务
+pub fn sys_sched_setscheduler(pid: usize, policy: usize) -> isize {
+    if pid != 0 {
+        return -1;
+    }
+    match SchedClass::from_usize(policy) {
+        Some(class) => {
+            task::set_current_sched_class(class);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// get the scheduling class of task `pid`, `pid` can only be 0 for current task
+pub fn sys_sched_getscheduler(pid: usize) -> isize {
+    if pid != 0 {
+        return -1;
+    }
+    task::current_sched_class() as isize
+}
+
+/// set the priority of current task, return the effective priority (clamped
+/// to `MAX_PRIORITY`) or -1 if `prio` is below `MIN_PRIORITY`
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 0 {
+        return -1;
+    }
+    match crate::task::set_current_priority(prio as usize) {
+        Some(priority) => priority as isize,
+        None => -1,
+    }
+}
+
+/// get the priority of current task
+pub fn sys_get_priority() -> isize {
+    crate::task::current_priority() as isize
+}
+
+/// set the time slice of current task to `ticks` timer ticks, return the effective
+/// length (clamped to `MAX_TIME_SLICE`) or -1 if `ticks` is 0
+pub fn sys_set_time_slice(ticks: usize) -> isize {
+    match task::set_current_time_slice(ticks) {
+        Some(ticks) => ticks as isize,
+        None => -1,
+    }
+}
+
+/// get the time slice of current task in timer ticks
+pub fn sys_get_time_slice() -> isize {
+    task::current_time_slice() as isize
+}
+
+/// set the file mode creation mask of current task, return the old mask
+///
+/// 只保留权限位，文件系统创建新的索引节点时再用它去掉请求的权限位中的对应位
+pub fn sys_umask(mask: u32) -> isize {
+    crate::task::replace_current_umask(mask & 0o777) as isize
+}
+
+/// operate on current task, only `PR_SET_NAME` and `PR_GET_NAME` are supported,
+/// return -1 for other options or an unmapped name
+///
+/// 与 Linux 相同，名字缓冲区为 `TASK_NAME_LEN + 1` 字节：设置时超长的名字被截断，
+/// 不可打印的字符被替换为 `?` ；获取时总是以 `\0` 结尾
+pub fn sys_prctl(option: usize, arg2: usize) -> isize {
+    match option {
+        PR_SET_NAME => {
+            let Some(bytes) = user_c_bytes(arg2 as *const u8, TASK_NAME_LEN) else {
+                return -1;
+            };
+            task::set_current_name(TaskName::sanitized(&bytes));
+            0
+        }
+        PR_GET_NAME => {
+            let mut buf = [0u8; TASK_NAME_LEN + 1];
+            let name = task::current_desc().name;
+            buf[..name.as_str().len()].copy_from_slice(name.as_str().as_bytes());
+            copy_struct_to_user(
+                task::current_user_token(),
+                arg2 as *mut [u8; TASK_NAME_LEN + 1],
+                &buf,
+            );
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// get the soft and hard limit of `resource` into `rlim`, only `RLIMIT_CPU` is supported
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    match resource {
+        RLIMIT_CPU => {
+            copy_struct_to_user(task::current_user_token(), rlim, &task::current_cpu_limit());
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// set the soft and hard limit of `resource` from `rlim`, only `RLIMIT_CPU` is supported
+///
+/// 软限制不能超过硬限制，硬限制只能降低不能提高
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    match resource {
+        RLIMIT_CPU => {
+            let limit: RLimit = copy_struct_from_user(task::current_user_token(), rlim);
+            if task::set_current_cpu_limit(limit) {
+                0
+            } else {
+                -1
+            }
+        }
+        _ => -1,
+    }
+}
+
+/// write the layout of the address space of current task into buf of length `len`,
+/// one `start-end perms kind` line per area, return the length of the whole listing
+///
+/// 缓冲区不够大时只写入开头的部分，用户程序可以根据返回值重新分配缓冲区
+pub fn sys_get_maps(buf: *mut u8, len: usize) -> isize {
+    copy_listing_to_user(&task::current_maps(), buf, len)
+}
+
+/// write the names of all apps into buf of length `len`, one per line,
+/// return the length of the whole list
+///
+/// 在有文件系统之前，用户程序据此列出可以运行的应用，或者补全应用名
+pub fn sys_get_app_names(buf: *mut u8, len: usize) -> isize {
+    copy_listing_to_user(&loader::list_apps(), buf, len)
+}
+
+/// find the app named by the NUL-terminated `name`, return its app id or -1 if
+/// `name` is invalid or names no app
+pub fn sys_find_app(name: *const u8) -> isize {
+    let Some(name) = user_str(name, MAX_PATH_LEN) else {
+        return -1;
+    };
+    match loader::find_app(&name) {
+        Some(app_id) => app_id as isize,
+        None => -1,
+    }
+}
+
+/// 把 `listing` 的开头部分写入用户缓冲区 `buf` ，返回 `listing` 的完整长度
+fn copy_listing_to_user(listing: &str, buf: *mut u8, len: usize) -> isize {
+    let mut src = listing.as_bytes();
+    for buffer in translated_byte_buffer(task::current_user_token(), buf, len.min(src.len())) {
+        let (head, tail) = src.split_at(buffer.len());
+        buffer.copy_from_slice(head);
+        src = tail;
+    }
+    listing.len() as isize
+}
+
+/// map `len` bytes at the page-aligned `start` to new zeroed frames with permission
+/// `prot`, a combination of `PROT_READ`, `PROT_WRITE` and `PROT_EXEC`, return -1 if
+/// `start` is unaligned, `prot` is invalid or the range is out of the user half of the address
+/// space or overlaps existing mappings
+///
+/// 映射的长度按页向上取整；RISC-V 的页表项不允许可写而不可读，这样的 `prot` 也是无效的。
+/// 只支持匿名的私有映射：`flags` 必须为 `MAP_PRIVATE | MAP_ANONYMOUS` ，`fd` 为 -1 ，`offset` 为 0
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if flags != MAP_PRIVATE | MAP_ANONYMOUS || fd != usize::MAX || offset != 0 {
+        return -1;
+    }
+    let prot_all = PROT_READ | PROT_WRITE | PROT_EXEC;
+    if start % PAGE_SIZE != 0
+        || len == 0
+        || prot & !prot_all != 0
+        || prot & prot_all == 0
+        || (prot & PROT_WRITE != 0 && prot & PROT_READ == 0)
+    {
+        return -1;
+    }
+    let Some(end) = start.checked_add(len).filter(|&end| end <= USER_SPACE_END) else {
+        return -1;
+    };
+    let mut permission = MapPermission::U;
+    if prot & PROT_READ != 0 {
+        permission |= MapPermission::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
+    }
+    match task::current_mmap(start, end, permission) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// unmap `len` bytes at the page-aligned `start`, return -1 if `start` is
+/// unaligned or the range is not covered exactly by areas mapped by `mmap`
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return -1;
+    }
+    let Some(end) = start.checked_add(len).filter(|&end| end <= USER_SPACE_END) else {
+        return -1;
+    };
+    if task::current_munmap(start, end) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// move the program break by `increment` bytes, return the old break or -1 if the
+/// heap would shrink below its bottom or grow into another area
+///
+/// 堆在用户栈之上，新增的页面在第一次被访问时才分配
+pub fn sys_sbrk(increment: isize) -> isize {
+    match task::change_current_program_brk(increment) {
+        Some(old_brk) => old_brk as isize,
+        None => -1,
+    }
+}
+
+/// create a task running the app named by the NUL-terminated `path`, return its pid
+/// or -1 if `path` is invalid, names no app, or the app can not be loaded or mapped
+///
+/// 应用是文件系统根目录中的文件，路径即应用名；新任务直接由应用的映像创建，不复制当前任务的地址空间
+pub fn sys_spawn(path: *const u8) -> isize {
+    let Some(path) = user_str(path, MAX_PATH_LEN) else {
+        return -1;
+    };
+    match loader::find_app(&path) {
+        Some(app_id) => match task::spawn(app_id) {
+            Ok(pid) => pid as isize,
+            Err(err) => {
+                log::warn!("[kernel] spawn {}: {:?}", path, err);
+                -1
+            }
+        },
+        None => -1,
+    }
+}
+
+/// reap the exited child `pid`, any child if `pid` is -1, and write its exit code
+/// to `exit_code_ptr` unless it is null, return its pid, -1 if there is no such
+/// child or -2 if it has not exited yet
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    match task::reap_child(pid) {
+        Ok((pid, exit_code)) => {
+            if !exit_code_ptr.is_null() {
+                copy_struct_to_user(task::current_user_token(), exit_code_ptr, &exit_code);
+            }
+            pid as isize
+        }
+        Err(err) => err,
+    }
+}
+
+/// write the hart current task runs on to `cpu` and its NUMA node (always 0) to `node`,
+/// either of which can be null
+pub fn sys_getcpu(cpu: *mut u32, node: *mut u32) -> isize {
+    let token = task::current_user_token();
+    if !cpu.is_null() {
+        copy_struct_to_user(token, cpu, &(hart::hart_id() as u32));
+    }
+    if !node.is_null() {
+        copy_struct_to_user(token, node, &0u32);
+    }
+    0
+}
+
+/// number of harts of the machine
+pub fn sys_hart_count() -> isize {
+    hart::hart_count() as isize
+}
+
+/// print a line for every task on the kernel console, like `ps`, return the number of tasks
+pub fn sys_ps() -> isize {
+    task::print_tasks() as isize
+}
+
+/// print how fragmented the free frames and the kernel heap are on the kernel console,
+/// and also copy it to `report` unless it is null
+///
+/// 用于在应用运行过程中观察分配器的碎片情况，为改进分配器提供依据
+pub fn sys_mem_report(report: *mut MemReport) -> isize {
+    mm::print_fragmentation();
+    if !report.is_null() {
+        copy_struct_to_user(task::current_user_token(), report, &mm::mem_report());
+    }
+    0
+}
+
+/// copy the most recent at most `len` bytes of the kernel log kept in memory to `buf`,
+/// like `dmesg`, return the number of bytes copied
+///
+/// 日志中包括控制台上按级别过滤掉的记录，最早的一条记录可能只有后半部分
+pub fn sys_dmesg(buf: *mut u8, len: usize) -> isize {
+    let recent = logging::recent(len);
+    let mut src = recent.as_slice();
+    for buffer in translated_byte_buffer(task::current_user_token(), buf, recent.len()) {
+        let (head, tail) = src.split_at(buffer.len());
+        buffer.copy_from_slice(head);
+        src = tail;
+    }
+    recent.len() as isize
+}
+
+/// copy the scheduling information of current task, including which scheduler
+/// the kernel uses, to `ti`
+pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
+    copy_struct_to_user(task::current_user_token(), ti, &task::current_task_info());
+    0
+}
+
+/// copy the event counters of current task to `counters`
+pub fn sys_perf_read(counters: *mut PerfCounters) -> isize {
+    copy_struct_to_user(task::current_user_token(), counters, &task::current_perf());
+    0
+}
+
+/// configure fault injection at `site` (0 for frames, 1 for heap): fail every
+/// `every`-th allocation (0 to disable) and all allocations after `after` successes
+/// (`usize::MAX` to disable), return the faults injected under the old configuration
+///
+/// 没有启用 `fault_injection` 特性时返回 -1
+pub fn sys_fault_inject(site: usize, every: usize, after: usize) -> isize {
+    #[cfg(feature = "fault_injection")]
+    {
+        use crate::mm::fault_inject::{self, FaultSite};
+        match FaultSite::from_usize(site) {
+            Some(site) => fault_inject::configure(site, every, after) as isize,
+            None => -1,
+        }
+    }
+    #[cfg(not(feature = "fault_injection"))]
+    {
+        let _ = (site, every, after);
+        -1
+    }
 }
 
-/// get current time
-pub fn sys_get_time() -> isize {
+/// get current time in ms, and also write it into `ts` with `us` precision if `ts` is not null
+pub fn sys_get_time(ts: *mut TimeVal) -> isize {
+    if !ts.is_null() {
+        let us = timer::get_time_us();
+        let time_val = TimeVal {
+            sec: (us / timer::MICRO_PER_SEC) as u64,
+            usec: (us % timer::MICRO_PER_SEC) as u64,
+        };
+        copy_struct_to_user(task::current_user_token(), ts, &time_val);
+    }
     timer::get_time_ms() as isize
 }
@@ -0,0 +1,102 @@
+//! Validation of the user buffers passed to syscalls
+//!
+//! 分发系统调用之前，按照各个系统调用的参数约定检查其用户缓冲区是否落在当前任务地址空间中
+//! 允许相应访问的逻辑段内，不合法时直接返回 `-EFAULT` ，处理函数因此不会在访问用户缓冲区时出错。
+//! 以 `\0` 结尾的字符串长度未知，由处理函数通过 [`user_str`] 等在读取时检查
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use super::*;
+use crate::mm::UserAccess::{self, Read, Write};
+use crate::mm::{translated_c_bytes, translated_str};
+
+/// 一个系统调用最多传入的用户缓冲区个数
+const MAX_USER_BUFFERS: usize = 2;
+
+/// a user buffer passed to a syscall
+struct UserBuffer {
+    ptr: usize,
+    len: usize,
+    access: UserAccess,
+}
+
+fn buffer(ptr: usize, len: usize, access: UserAccess) -> Option<UserBuffer> {
+    Some(UserBuffer { ptr, len, access })
+}
+
+/// a user buffer which is not passed if `ptr` is null
+fn nullable(ptr: usize, len: usize, access: UserAccess) -> Option<UserBuffer> {
+    (ptr != 0).then_some(UserBuffer { ptr, len, access })
+}
+
+/// the user buffers syscall `syscall_id` accesses with `args`
+fn user_buffers(syscall_id: usize, args: &[usize; 6]) -> [Option<UserBuffer>; MAX_USER_BUFFERS] {
+    match syscall_id {
+        SYSCALL_READ => [buffer(args[1], args[2], Write), None],
+        SYSCALL_WRITE => [buffer(args[1], args[2], Read), None],
+        SYSCALL_PRCTL if args[0] == PR_GET_NAME => {
+            [buffer(args[1], TASK_NAME_LEN + 1, Write), None]
+        }
+        SYSCALL_GETDENTS => [
+            buffer(args[1], args[2].saturating_mul(size_of::<Dirent>()), Write),
+            None,
+        ],
+        SYSCALL_FSTAT => [buffer(args[1], size_of::<Stat>(), Write), None],
+        SYSCALL_GETRLIMIT => [buffer(args[1], size_of::<RLimit>(), Write), None],
+        SYSCALL_SETRLIMIT => [buffer(args[1], size_of::<RLimit>(), Read), None],
+        SYSCALL_GETCPU => [
+            nullable(args[0], size_of::<u32>(), Write),
+            nullable(args[1], size_of::<u32>(), Write),
+        ],
+        SYSCALL_GET_TIME => [nullable(args[0], size_of::<TimeVal>(), Write), None],
+        SYSCALL_WAITPID => [nullable(args[1], size_of::<i32>(), Write), None],
+        SYSCALL_TASK_INFO => [buffer(args[0], size_of::<TaskInfo>(), Write), None],
+        SYSCALL_GET_MAPS | SYSCALL_GET_APP_NAMES | SYSCALL_DMESG => {
+            [buffer(args[0], args[1], Write), None]
+        }
+        SYSCALL_PERF_READ => [buffer(args[0], size_of::<PerfCounters>(), Write), None],
+        SYSCALL_MEM_REPORT => [nullable(args[0], size_of::<MemReport>(), Write), None],
+        SYSCALL_FD_STAT => [buffer(args[1], size_of::<IoStats>(), Write), None],
+        _ => [None, None],
+    }
+}
+
+/// check the user buffers of syscall `syscall_id` with `args` against the address
+/// space of current task, `Err(-EFAULT)` if any of them is not accessible
+///
+/// 缓冲区中懒分配的页面在此时分配物理页帧，处理函数可以直接通过页表访问
+pub fn check_user_buffers(syscall_id: usize, args: &[usize; 6]) -> Result<(), isize> {
+    for buffer in user_buffers(syscall_id, args).into_iter().flatten() {
+        if !task::prepare_current_user_range(buffer.ptr, buffer.len, buffer.access) {
+            log::warn!(
+                "[kernel] {} passed inaccessible buffer [{:#x}, {:#x}) to syscall {}",
+                task::current_desc(),
+                buffer.ptr,
+                buffer.ptr.wrapping_add(buffer.len),
+                syscall_id
+            );
+            return Err(-EFAULT);
+        }
+    }
+    Ok(())
+}
+
+/// read the NUL-terminated string at `ptr` in the address space of current task,
+/// `None` if it is not readable, longer than `max_len` bytes or not UTF-8
+///
+/// 字符串所在的懒分配或者被换出的页面在读取时进入内存
+pub fn user_str(ptr: *const u8, max_len: usize) -> Option<String> {
+    translated_str(task::current_user_token(), ptr, max_len, |va| {
+        task::prepare_current_user_range(va, 1, Read)
+    })
+}
+
+/// read at most `limit` bytes of the NUL-terminated string at `ptr` in the address
+/// space of current task, `None` if they are not readable
+pub fn user_c_bytes(ptr: *const u8, limit: usize) -> Option<Vec<u8>> {
+    translated_c_bytes(task::current_user_token(), ptr, limit, |va| {
+        task::prepare_current_user_range(va, 1, Read)
+    })
+}
@@ -11,23 +11,41 @@
 
 pub use self::context::TaskContext;
 
-use lazy_static::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use ::alloc::string::String;
+use ::alloc::sync::Arc;
 use ::alloc::vec::Vec;
 
+use crate::config;
+use crate::fs::FileDesc;
 use crate::loader;
-use crate::sync::UPSafeCell;
+use crate::mm::{MapError, MapPermission, UserAccess};
+use crate::sync::{LazyInit, UPSafeCell};
+use crate::timer;
 use crate::trap::TrapContext;
+use abi::{PerfCounters, RLimit, TaskInfo, SCHEDULER_MLFQ, SCHEDULER_STRIDE};
 
 mod context;
+mod pid;
+mod ready_queue;
 mod switch;
+#[cfg(feature = "switch_audit")]
+mod switch_audit;
+mod table;
 #[allow(clippy::module_inception)]
 mod task;
 
+use self::ready_queue::ReadyQueue;
+use self::table::TaskTable;
+pub use self::task::{PerfEvent, SchedClass, SpawnError, TaskDesc, TaskName};
 use self::task::{TaskControlBlock, TaskStatus};
 
 // use self::task::TaskLifecycle;
 
+/// 任务表中曾经用到的位置数，每个位置对应一个内核栈，panic 时据此判断帧指针是否在内核栈中
+static TASK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// The task manager, where all the tasks are managed.
 ///
 /// Functions implemented on `TaskManager` deals with all task state transitions
@@ -38,8 +56,6 @@ use self::task::{TaskControlBlock, TaskStatus};
 /// borrowing checks to runtime. You can see examples on how to use `inner` in
 /// existing functions on `TaskManager`.
 pub struct TaskManager {
-    /// total number of tasks
-    num_app: usize,
     /// use inner value to get mutable access
     inner: UPSafeCell<TaskManagerInner>,
 }
@@ -47,34 +63,109 @@ pub struct TaskManager {
 /// The task manager inner in 'UPSafeCell'
 struct TaskManagerInner {
     /// task list
-    tasks: Vec<TaskControlBlock>,
+    tasks: TaskTable,
+    /// `Ready` tasks in scheduling order
+    ready_queue: ReadyQueue,
     /// id of current `Running` task
     current_task: usize,
+    /// 下次调度时优先运行的任务，见 [`yield_to`]
+    directed_next: Option<usize>,
+    /// 多级反馈队列上一次提升所有任务的时间，单位为 `us`
+    #[cfg(feature = "mlfq")]
+    last_boost_us: usize,
 }
 
-lazy_static! {
-    /// a `TaskManager` global instance through lazy_static!
-    pub static ref TASK_MANAGER: TaskManager = {
+impl TaskManagerInner {
+    /// Mark task `id` `Ready` and queue it.
+    fn make_ready(&mut self, id: usize) {
+        let task = &mut self.tasks[id];
+        task.task_status = TaskStatus::Ready;
+        task.sched_stats.on_ready(timer::get_time_us());
+        self.ready_queue.push(id, task);
+    }
+
+    /// Move every task to the top level of the multilevel feedback queue if
+    /// [`config::MLFQ_BOOST_MS`] has passed since the last boost.
+    ///
+    /// 避免长时间运行的任务停留在低级别而被饿死，也让行为改变的任务重新获得高优先级
+    #[cfg(feature = "mlfq")]
+    fn boost_if_due(&mut self, now_us: usize) {
+        if now_us - self.last_boost_us
+            < config::MLFQ_BOOST_MS * (timer::MICRO_PER_SEC / timer::MSEC_PER_SEC)
+        {
+            return;
+        }
+        self.last_boost_us = now_us;
+        for (id, task) in self.tasks.iter_mut() {
+            if task.mlfq_level == 0 {
+                continue;
+            }
+            task.mlfq_level = 0;
+            if task.task_status == TaskStatus::Ready {
+                self.ready_queue.requeue(id, task);
+            }
+        }
+    }
+
+    /// Report every `Ready` task that has waited longer than
+    /// [`config::STARVATION_BOUND_MS`], panic instead under feature `starvation_panic`.
+    ///
+    /// 空闲类任务本来就只在没有其他任务就绪时运行，不参与检查；每次等待只报告一次。
+    /// 只需从等待最久的任务开始检查，直到遇到没有饥饿的任务
+    fn detect_starvation(&mut self, now_us: usize) {
+        while let Some(id) = self.ready_queue.oldest(&self.tasks) {
+            let task = &mut self.tasks[id];
+            let wait_ms = (now_us - task.sched_stats.ready_since_us)
+                / (timer::MICRO_PER_SEC / timer::MSEC_PER_SEC);
+            if wait_ms <= config::STARVATION_BOUND_MS {
+                break;
+            }
+            self.ready_queue.pop_oldest();
+            if task.sched_class == SchedClass::Idle {
+                continue;
+            }
+            task.sched_stats.starvation_reported = true;
+            #[cfg(feature = "starvation_panic")]
+            panic!("{} starved: Ready for {}ms", task, wait_ms);
+            #[cfg(not(feature = "starvation_panic"))]
+            log::warn!("[kernel] {} starved: Ready for {}ms", task, wait_ms);
+        }
+    }
+}
+
+/// the global `TaskManager`, created by [`init`]
+pub static TASK_MANAGER: LazyInit<TaskManager> = LazyInit::new();
+
+impl TaskManager {
+    /// create the tasks of all apps
+    fn new() -> Self {
         println!("init TASK_MANAGER");
         let num_app = loader::get_num_app();
         println!("num_app = {}", num_app);
-        let mut tasks: Vec<TaskControlBlock> = Vec::new();
+        let mut inner = TaskManagerInner {
+            tasks: TaskTable::new(),
+            ready_queue: ReadyQueue::new(),
+            current_task: 0,
+            directed_next: None,
+            #[cfg(feature = "mlfq")]
+            last_boost_us: timer::get_time_us(),
+        };
         for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(loader::get_app_data(i), i));
+            println!("app_{}: {}", i, loader::get_app_name(i));
+            let task = TaskControlBlock::new(i, i)
+                .unwrap_or_else(|err| panic!("failed to create the task of app {}: {:?}", i, err));
+            inner.tasks.insert(i, task);
+            // 第一个任务由 `run_first_task` 直接运行，不进入就绪队列
+            if i != 0 {
+                inner.make_ready(i);
+            }
         }
+        TASK_COUNT.store(num_app, Ordering::Relaxed);
         TaskManager {
-            num_app,
-            inner: unsafe {
-                crate::sync::UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                })
-            },
+            inner: unsafe { crate::sync::UPSafeCell::new(inner) },
         }
-    };
-}
+    }
 
-impl TaskManager {
     /// Run the first task in task list.
     ///
     /// Generally, the first task in task list is an idle task (we call it zero process later).
@@ -83,10 +174,18 @@ impl TaskManager {
         let mut inner = self.inner.exclusive_access();
         let task0 = &mut inner.tasks[0];
         task0.task_status = TaskStatus::Running;
+        task0.stride = task0.stride.wrapping_add(task0.pass());
+        task0.slice_left = task0.quantum();
+        task0.scheduled_at_us = timer::get_time_us();
+        task0.sched_stats.on_run(task0.scheduled_at_us);
         // task0.lifecycle.first_run_time_ms = timer::get_time_ms();
         let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+        #[cfg(feature = "switch_audit")]
+        let name = task0.name;
         drop(inner);
         let mut _unused = TaskContext::zero_init();
+        #[cfg(feature = "switch_audit")]
+        self::switch_audit::begin(None, 0, name);
         // before this, we should drop local variables that must be dropped manually
         unsafe {
             self::switch::__switch(&mut _unused as *mut TaskContext, next_task_cx_ptr);
@@ -95,29 +194,364 @@ impl TaskManager {
     }
 
     /// Change the status of current `Running` task into `Ready`.
+    ///
+    /// 在多级反馈队列中，用完时间片的任务降一级，提前让出处理器的任务升一级
     fn mark_current_suspended(&self) {
         let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+        #[cfg(feature = "mlfq")]
+        {
+            let task = &mut inner.tasks[current];
+            task.mlfq_level = if task.slice_left == 0 {
+                (task.mlfq_level + 1).min(config::MLFQ_LEVELS - 1)
+            } else {
+                task.mlfq_level.saturating_sub(1)
+            };
+        }
+        inner.make_ready(current);
     }
 
-    /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
+    /// Change the status of current `Running` task into `Blocked` until `deadline_us`.
+    ///
+    /// 在多级反馈队列中，阻塞的任务和提前让出处理器的任务一样升一级
+    fn mark_current_sleeping(&self, deadline_us: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        task.task_status = TaskStatus::Blocked;
+        #[cfg(feature = "mlfq")]
+        {
+            task.mlfq_level = task.mlfq_level.saturating_sub(1);
+        }
+        timer::add_sleeper(deadline_us, current);
+    }
+
+    /// Make the sleeping tasks whose deadline has passed `Ready`.
+    fn wake_expired(&self) {
+        let expired = timer::take_expired(timer::get_time_us());
+        let mut inner = self.inner.exclusive_access();
+        for id in expired {
+            inner.make_ready(id);
+        }
+    }
+
+    /// Change the status of current `Running` task into `Zombie` with `exit_code`,
+    /// free its user pages and hand its children to the kernel.
+    ///
+    /// 内核栈还在使用，要等切换到其他任务后才能释放。
+    ///
+    /// 与 Unix 把孤儿过继给 init 进程不同，这里没有 init 任务：启动时每个应用都是一个没有父任务的
+    /// 独立任务，不存在一个始终运行、能够 `waitpid` 回收孤儿的任务。孤儿的 `parent` 因此被清为
+    /// `None` ，退出后由 [`TaskManager::take_orphan_zombies`] 在内核中回收，不能再被任何任务等待
+    fn mark_current_exited(&self, exit_code: i32) {
         let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+        let task = &inner.tasks[current];
+        let pid = task.pid.0;
+        log::info!(
+            "[kernel] {} ran {} times for {}us, waited {}us in total and {}us at most",
+            task,
+            task.sched_stats.runs,
+            task.cpu_time_us(),
+            task.sched_stats.total_wait_us,
+            task.sched_stats.max_wait_us
+        );
+        let task = &mut inner.tasks[current];
+        task.task_status = TaskStatus::Zombie;
+        task.exit_code = exit_code;
+        task.memory_set.recycle_data_pages();
         // inner.tasks[current].lifecycle.exit_time_ms = timer::get_time_ms();
+        // 孤儿任务交给内核，与启动时创建的任务一样，退出后由内核回收而不是过继给 init 任务
+        for (_, task) in inner.tasks.iter_mut() {
+            if task.parent == Some(pid) {
+                task.parent = None;
+            }
+        }
+    }
+
+    /// Take the `Zombie` tasks without a parent out of the task list,
+    /// except current task whose kernel stack is still in use.
+    fn take_orphan_zombies(&self) -> Vec<TaskControlBlock> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let orphans: Vec<usize> = inner
+            .tasks
+            .iter()
+            .filter(|(id, task)| {
+                *id != current && task.task_status == TaskStatus::Zombie && task.parent.is_none()
+            })
+            .map(|(id, _)| id)
+            .collect();
+        orphans
+            .into_iter()
+            .filter_map(|id| inner.tasks.remove(id))
+            .collect()
+    }
+
+    /// Reap a `Zombie` child of current `Running` task, any child if `pid` is -1,
+    /// return its pid and exit code.
+    ///
+    /// 没有符合条件的子任务时返回 `Err(-1)`，子任务都还未退出时返回 `Err(-2)`
+    fn reap_child(&self, pid: isize) -> Result<(usize, i32), isize> {
+        let mut inner = self.inner.exclusive_access();
+        let parent = inner.tasks[inner.current_task].pid.0;
+        let children: Vec<(usize, TaskStatus)> = inner
+            .tasks
+            .iter()
+            .filter(|(_, task)| {
+                task.parent == Some(parent) && (pid == -1 || task.pid.0 as isize == pid)
+            })
+            .map(|(id, task)| (id, task.task_status))
+            .collect();
+        if children.is_empty() {
+            return Err(-1);
+        }
+        let Some(&(child, _)) = children
+            .iter()
+            .find(|(_, status)| *status == TaskStatus::Zombie)
+        else {
+            return Err(-2);
+        };
+        let child = inner.tasks.remove(child).unwrap();
+        drop(inner);
+        // 释放任务控制块即回收 pid 、页表和内核栈
+        Ok((child.pid.0, child.exit_code))
     }
 
     /// Find next task to run and return task id.
     ///
-    /// In this case, we only return the first `Ready` task in task list.
+    /// Return the `Ready` task of the highest scheduling class with the smallest
+    /// stride, ties are broken in the order they became `Ready`.
     fn find_next_task(&self) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(target) = inner.directed_next.take() {
+            if inner
+                .tasks
+                .get(target)
+                .is_some_and(|task| task.task_status == TaskStatus::Ready)
+            {
+                inner.ready_queue.remove(target);
+                return Some(target);
+            }
+        }
+        let inner = &mut *inner;
+        inner.ready_queue.pop(&inner.tasks)
+    }
+
+    /// Create a `Ready` child of current `Running` task running app `app_id`,
+    /// return its pid.
+    fn spawn(&self, app_id: usize) -> Result<usize, SpawnError> {
+        let (task_id, parent, stride) = {
+            let inner = self.inner.exclusive_access();
+            let current = &inner.tasks[inner.current_task];
+            (inner.tasks.free_id(), current.pid.0, current.stride)
+        };
+        // 创建任务时需要访问内核地址空间，此时不持有任务管理器
+        let mut task = TaskControlBlock::new(app_id, task_id)?;
+        task.parent = Some(parent);
+        // 新任务从当前任务的行程开始，既不会长时间独占处理器，也不会被饿死
+        task.stride = stride;
+        let pid = task.pid.0;
+        let mut inner = self.inner.exclusive_access();
+        inner.tasks.insert(task_id, task);
+        inner.make_ready(task_id);
+        TASK_COUNT.fetch_max(task_id + 1, Ordering::Relaxed);
+        Ok(pid)
+    }
+
+    /// Make task `pid` run at the next schedule if it is `Ready`,
+    /// return whether it is.
+    fn direct_next(&self, pid: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let target = inner
+            .tasks
+            .iter()
+            .find(|(_, task)| task.pid.0 == pid && task.task_status == TaskStatus::Ready)
+            .map(|(id, _)| id);
+        if target.is_some() {
+            inner.directed_next = target;
+        }
+        target.is_some()
+    }
+
+    /// Set the priority of current `Running` task, return the effective priority
+    /// or `None` if `priority` is below [`config::MIN_PRIORITY`].
+    ///
+    /// 超过 [`config::MAX_PRIORITY`] 的优先级会被截断
+    fn set_current_priority(&self, priority: usize) -> Option<usize> {
+        if priority < config::MIN_PRIORITY {
+            return None;
+        }
+        let priority = priority.min(config::MAX_PRIORITY);
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].priority = priority;
+        Some(priority)
+    }
+
+    /// Set the time slice of current `Running` task in timer ticks, return the
+    /// effective length or `None` if `ticks` is 0.
+    ///
+    /// 超过 [`config::MAX_TIME_SLICE`] 的时间片会被截断，新的长度从下次被调度时开始生效
+    fn set_current_time_slice(&self, ticks: usize) -> Option<usize> {
+        if ticks == 0 {
+            return None;
+        }
+        let ticks = ticks.min(config::MAX_TIME_SLICE);
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].time_slice = ticks;
+        Some(ticks)
+    }
+
+    /// Get the time slice of current `Running` task in timer ticks.
+    fn get_current_time_slice(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].time_slice
+    }
+
+    /// Charge a timer tick to current `Running` task, return `true` if its
+    /// time slice has run out.
+    fn tick_current(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        task.slice_left = task.slice_left.saturating_sub(1);
+        task.slice_left == 0
+    }
+
+    /// Get the umask of current `Running` task.
+    fn get_current_umask(&self) -> u32 {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].umask
+    }
+
+    /// Replace the umask of current `Running` task, return the old one.
+    fn replace_current_umask(&self, umask: u32) -> u32 {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        core::mem::replace(&mut inner.tasks[current].umask, umask)
+    }
+
+    /// Get the file opened at `fd` by current `Running` task.
+    fn get_current_file(&self, fd: usize) -> Option<Arc<FileDesc>> {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task]
+            .fd_table
+            .get(fd)
+            .and_then(|file| file.clone())
+    }
+
+    /// Put `file` into the fd table of current `Running` task, return its fd.
+    fn add_current_file(&self, file: FileDesc) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        let fd = task.alloc_fd();
+        task.fd_table[fd] = Some(Arc::new(file));
+        fd
+    }
+
+    /// Remove the file at `fd` from the fd table of current `Running` task, return it.
+    fn take_current_file(&self, fd: usize) -> Option<Arc<FileDesc>> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].fd_table.get_mut(fd)?.take()
+    }
+
+    /// Get the CPU time limit of current `Running` task.
+    fn get_current_cpu_limit(&self) -> RLimit {
         let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].cpu_limit
+    }
+
+    /// Set the CPU time limit of current `Running` task, return `false` if the
+    /// soft limit exceeds the hard limit or the hard limit is raised.
+    fn set_current_cpu_limit(&self, limit: RLimit) -> bool {
+        let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+        let task = &mut inner.tasks[current];
+        if limit.cur > limit.max || limit.max > task.cpu_limit.max {
+            return false;
+        }
+        task.cpu_limit = limit;
+        task.cpu_soft_limit_reported = false;
+        true
+    }
+
+    /// Check the CPU time consumed by current `Running` task against its limit,
+    /// return `true` if the hard limit has been exceeded.
+    ///
+    /// 超出软限制时本应发送 SIGXCPU ，目前还没有信号机制，只输出一条警告
+    fn current_cpu_limit_exceeded(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        let cpu_time_sec = (task.cpu_time_us() / timer::MICRO_PER_SEC) as u64;
+        if cpu_time_sec >= task.cpu_limit.max {
+            return true;
+        }
+        if cpu_time_sec >= task.cpu_limit.cur && !task.cpu_soft_limit_reported {
+            task.cpu_soft_limit_reported = true;
+            log::warn!(
+                "[kernel] {} exceeded its soft CPU time limit of {}s",
+                task,
+                task.cpu_limit.cur
+            );
+        }
+        false
+    }
+
+    /// Warn that current `Running` task made the unsupported syscall `syscall_id`,
+    /// only the first time it does.
+    fn report_unsupported_syscall(&self, syscall_id: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        if !task.unsupported_syscalls.contains(&syscall_id) {
+            task.unsupported_syscalls.push(syscall_id);
+            log::warn!("[kernel] {} made unsupported syscall {}", task, syscall_id);
+        }
+    }
+
+    /// Move current `Running` task into scheduling class `class`.
+    ///
+    /// 行程只在同一类别的任务之间比较，因此把任务的行程对齐到新类别中行程最小的任务，
+    /// 避免它在新类别中长期独占或长期得不到调度
+    fn set_current_sched_class(&self, class: SchedClass) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        if inner.tasks[current].sched_class == class {
+            return;
+        }
+        let min_stride = inner
+            .tasks
+            .iter()
+            .filter(|(id, task)| {
+                *id != current
+                    && task.sched_class == class
+                    && task.task_status != TaskStatus::Zombie
+            })
+            .map(|(_, task)| task)
+            .reduce(|min, task| if task.stride_before(min) { task } else { min })
+            .map(|task| task.stride);
+        let task = &mut inner.tasks[current];
+        task.sched_class = class;
+        if let Some(stride) = min_stride {
+            task.stride = stride;
+        }
+    }
+
+    /// Get the scheduling class of current `Running` task.
+    fn get_current_sched_class(&self) -> SchedClass {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].sched_class
+    }
+
+    /// Get the priority of current `Running` task.
+    fn get_current_priority(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].priority
     }
 
     /// Get the current 'Running' task's token.
@@ -132,20 +566,277 @@ impl TaskManager {
         inner.tasks[inner.current_task].trap_ctx()
     }
 
+    /// Get the layout of the address space of current `Running` task.
+    fn get_current_maps(&self) -> String {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].memory_set.maps()
+    }
+
+    /// Check that `[start, start + len)` in the address space of current `Running`
+    /// task lies in user areas allowing `access`, and allocate the frames of its
+    /// lazy pages not accessed yet, which stay resident until the syscall returns.
+    fn prepare_current_user_range(&self, start: usize, len: usize, access: UserAccess) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let memory_set = &mut inner.tasks[current].memory_set;
+        memory_set.user_range_accessible(start, len, access)
+            && memory_set.populate_range(start, len)
+    }
+
+    /// Release the pages of current `Running` task pinned by
+    /// [`TaskManager::prepare_current_user_range`].
+    fn unpin_current_user_pages(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].memory_set.unpin_all();
+    }
+
+    /// Handle a page fault of current `Running` task at `va` lacking `permission`,
+    /// return `false` unless it is the first access to a lazy page.
+    fn handle_current_lazy_fault(&self, va: usize, permission: MapPermission) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current]
+            .memory_set
+            .handle_lazy_fault(va.into(), permission)
+    }
+
+    /// Map `[start, end)` in the address space of current `Running` task to new frames.
+    fn current_mmap(
+        &self,
+        start: usize,
+        end: usize,
+        permission: MapPermission,
+    ) -> Result<(), MapError> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current]
+            .memory_set
+            .mmap(start.into(), end.into(), permission)
+    }
+
+    /// Unmap `[start, end)` mapped by [`current_mmap`] in the address space of
+    /// current `Running` task, return whether it was.
+    fn current_munmap(&self, start: usize, end: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current]
+            .memory_set
+            .munmap(start.into(), end.into())
+    }
+
+    /// Move the program break of current `Running` task by `increment` bytes,
+    /// return the old break.
+    fn change_current_program_brk(&self, increment: isize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].change_program_brk(increment)
+    }
+
+    /// Print the memory areas of current `Running` task and why accessing `fault_va` faulted.
+    fn dump_current_memory_set(&self, fault_va: usize) {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task]
+            .memory_set
+            .dump_areas(fault_va.into());
+    }
+
+    /// Count an occurrence of `event` in current `Running` task.
+    fn count_current(&self, event: PerfEvent) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        *event.counter(&mut inner.tasks[current].perf) += 1;
+    }
+
+    fn get_current_pid(&self) -> (usize, Option<usize>) {
+        let inner = self.inner.exclusive_access();
+        let current = &inner.tasks[inner.current_task];
+        (current.pid.0, current.parent)
+    }
+
+    fn get_current_desc(&self) -> TaskDesc {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].desc()
+    }
+
+    /// Get the name and pid of current task without panicking, `None` if no task
+    /// has run yet or the task manager is borrowed.
+    fn try_get_current_desc(&self) -> Option<TaskDesc> {
+        let inner = self.inner.try_exclusive_access()?;
+        let task = inner.tasks.get(inner.current_task)?;
+        // 第一个任务开始运行之前，当前任务仍是 `Ready` 的
+        (task.task_status != TaskStatus::Ready).then(|| task.desc())
+    }
+
+    /// Rename current `Running` task.
+    fn set_current_name(&self, name: TaskName) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].name = name;
+    }
+
+    /// Print a line for every task, like `ps`.
+    fn print_tasks(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        println!(
+            "{:>5} {:>5} {:<8} {:<12} {:>5} {:>8} NAME",
+            "PID", "PPID", "STATE", "CLASS", "PRIO", "CPU(ms)"
+        );
+        let mut count = 0;
+        for (_, task) in inner.tasks.iter() {
+            println!(
+                "{:>5} {:>5} {:<8} {:<12} {:>5} {:>8} {}",
+                task.pid.0,
+                task.parent.unwrap_or(0),
+                ::alloc::format!("{:?}", task.task_status),
+                ::alloc::format!("{:?}", task.sched_class),
+                task.priority,
+                task.cpu_time_us() / (timer::MICRO_PER_SEC / timer::MSEC_PER_SEC),
+                task.name
+            );
+            count += 1;
+        }
+        count
+    }
+
+    fn others_have_fs_files_open(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        let open = inner
+            .tasks
+            .iter()
+            .filter(|&(id, task)| {
+                id != inner.current_task && task.task_status != TaskStatus::Zombie
+            })
+            .flat_map(|(_, task)| task.fd_table.iter().flatten())
+            .any(|desc| desc.in_fs());
+        open
+    }
+
+    /// print the I/O statistics of every open fd of every task, return the number of fds
+    fn print_io_stats(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        println!(
+            "{:>5} {:<16} {:>3} {:>8} {:>10} {:>8} {:>10} FILE",
+            "PID", "TASK", "FD", "READS", "READ(B)", "WRITES", "WRITE(B)"
+        );
+        let mut count = 0;
+        for (_, task) in inner.tasks.iter() {
+            for (fd, desc) in task.fd_table.iter().enumerate() {
+                let Some(desc) = desc else {
+                    continue;
+                };
+                let stats = desc.stats();
+                println!(
+                    "{:>5} {:<16} {:>3} {:>8} {:>10} {:>8} {:>10} {}",
+                    task.pid.0,
+                    task.name.as_str(),
+                    fd,
+                    stats.reads,
+                    stats.read_bytes,
+                    stats.writes,
+                    stats.write_bytes,
+                    desc.name
+                );
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn get_current_task_info(&self) -> TaskInfo {
+        let inner = self.inner.exclusive_access();
+        let task = &inner.tasks[inner.current_task];
+        TaskInfo {
+            scheduler: if cfg!(feature = "mlfq") {
+                SCHEDULER_MLFQ
+            } else {
+                SCHEDULER_STRIDE
+            },
+            mlfq_level: task.mlfq_level as u64,
+            quantum: task.quantum() as u64,
+            time_ms: (task.cpu_time_us() / (timer::MICRO_PER_SEC / timer::MSEC_PER_SEC)) as u64,
+            syscalls: task.perf.syscalls,
+            preemptions: task.perf.preemptions,
+            fault_fixups: task.perf.fault_fixups,
+        }
+    }
+
+    fn get_current_perf(&self) -> PerfCounters {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].perf
+    }
+
+    /// Panic if the kernel stack of current 'Running' task has overflowed.
+    #[cfg(feature = "stack_canary")]
+    fn check_current_kernel_stack(&self) {
+        let inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        self::task::check_kernel_stack_canary(&inner.tasks[current]);
+    }
+
+    /// Complete the audit of the `__switch` into current `Running` task.
+    #[cfg(feature = "switch_audit")]
+    fn finish_current_switch(&self) {
+        let current = self.inner.exclusive_access().current_task;
+        self::switch_audit::finish(current);
+    }
+
     /// Switch current `Running` task to the task we have found,
     /// or there is no `Ready` task and we can exit with all applications completed
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
+        // 在不持有任务管理器时释放，释放内核栈需要访问内核地址空间
+        drop(self.take_orphan_zombies());
+        #[cfg(feature = "mlfq")]
+        self.inner
+            .exclusive_access()
+            .boost_if_due(timer::get_time_us());
+        // 当前任务到此为止占用了处理器，之后等待睡眠到期的时间不计入
+        let stopped_at = timer::get_time_us();
+        let next = loop {
+            if let Some(next) = self.find_next_task() {
+                break Some(next);
+            }
+            // 没有 `Ready` 任务但还有任务在睡眠时，等到最早的睡眠到期
+            let Some(deadline_us) = timer::next_wakeup_us() else {
+                break None;
+            };
+            while timer::get_time_us() < deadline_us {
+                core::hint::spin_loop();
+            }
+            self.wake_expired();
+        };
+        if let Some(next) = next {
             let mut inner = self.inner.exclusive_access();
             let current = inner.current_task;
             inner.tasks[next].task_status = TaskStatus::Running;
+            let pass = inner.tasks[next].pass();
+            inner.tasks[next].stride = inner.tasks[next].stride.wrapping_add(pass);
+            inner.tasks[next].slice_left = inner.tasks[next].quantum();
+            let now = timer::get_time_us();
+            let ran_us = stopped_at - inner.tasks[current].scheduled_at_us;
+            inner.tasks[current].cpu_time_us += ran_us;
+            inner.tasks[next].scheduled_at_us = now;
+            inner.tasks[next].sched_stats.on_run(now);
+            inner.detect_starvation(now);
             // if 0 != inner.tasks[next].lifecycle.first_run_time_ms {
             //     inner.tasks[next].lifecycle.first_run_time_ms = timer::get_time_ms();
             // }
             inner.current_task = next;
+            if current != next {
+                *PerfEvent::ContextSwitch.counter(&mut inner.tasks[current].perf) += 1;
+            }
+            #[cfg(feature = "stack_canary")]
+            {
+                self::task::check_kernel_stack_canary(&inner.tasks[current]);
+                self::task::check_kernel_stack_canary(&inner.tasks[next]);
+            }
             let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
             let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+            #[cfg(feature = "switch_audit")]
+            let next_name = inner.tasks[next].name;
             core::mem::drop(inner);
+            #[cfg(feature = "switch_audit")]
+            self::switch_audit::begin(Some(current), next, next_name);
             // before this, we should drop local variables that must be dropped manually
             unsafe {
                 self::switch::__switch(current_task_cx_ptr, next_task_cx_ptr);
@@ -153,6 +844,19 @@ impl TaskManager {
             // go back to user mode
         } else {
             println!("All applications completed!");
+            let (zeroed_frames, zeroing_us) = crate::mm::zeroing_stats();
+            log::info!(
+                "[kernel] zeroed {} frames in {}us on {}",
+                zeroed_frames,
+                zeroing_us,
+                if cfg!(feature = "frame_zero_on_free") {
+                    "free"
+                } else {
+                    "alloc"
+                }
+            );
+            crate::mm::print_frame_stats();
+            crate::mm::print_fragmentation();
 
             #[cfg(feature = "board_qemu")]
             use crate::board::QEMUExit;
@@ -162,6 +866,11 @@ impl TaskManager {
     }
 }
 
+/// create the control blocks and address spaces of all apps
+pub fn init() {
+    TASK_MANAGER.init(TaskManager::new());
+}
+
 /// Run the first task in task list.
 pub fn run_first_task() {
     TASK_MANAGER.run_first_task();
@@ -178,9 +887,9 @@ fn mark_current_suspended() {
     TASK_MANAGER.mark_current_suspended();
 }
 
-/// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+/// Change the status of current `Running` task into `Zombie` with `exit_code`.
+fn mark_current_exited(exit_code: i32) {
+    TASK_MANAGER.mark_current_exited(exit_code);
 }
 
 /// Suspend the current 'Running' task and run the next task in task list.
@@ -189,17 +898,274 @@ pub fn suspend_current_and_run_next() {
     run_next_task();
 }
 
-/// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+/// Suspend the current 'Running' task and run task `pid` next,
+/// return `false` without suspending if it is not `Ready`.
+///
+/// 跳过调度算法的选择，但该任务依然照常累加行程，因此不会因此获得更多的 CPU 时间
+pub fn yield_to(pid: usize) -> bool {
+    if !TASK_MANAGER.direct_next(pid) {
+        return false;
+    }
+    suspend_current_and_run_next();
+    true
+}
+
+/// Create a `Ready` child of current task running app `app_id`, return its pid.
+///
+/// 新任务直接由应用的 ELF 映像创建，不复制当前任务的地址空间。应用无法加载或者物理页帧耗尽时
+/// 返回错误，内核继续运行
+pub fn spawn(app_id: usize) -> Result<usize, SpawnError> {
+    TASK_MANAGER.spawn(app_id)
+}
+
+/// number of tasks created so far, readable without borrowing the task manager
+pub fn task_count() -> usize {
+    TASK_COUNT.load(Ordering::Relaxed)
+}
+
+/// Block the current `Running` task for at least `ms` milliseconds and run the next task.
+pub fn sleep_current_and_run_next(ms: usize) {
+    let deadline_us = timer::get_time_us()
+        .saturating_add(ms.saturating_mul(timer::MICRO_PER_SEC / timer::MSEC_PER_SEC));
+    TASK_MANAGER.mark_current_sleeping(deadline_us);
+    run_next_task();
+}
+
+/// Make the sleeping tasks whose deadline has passed `Ready`, called on timer interrupts.
+pub fn wake_expired() {
+    TASK_MANAGER.wake_expired();
+}
+
+/// Exit the current 'Running' task with `exit_code` and run the next task in task list.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    // 输出退出的任务反复触发、被合并掉的警告的重复次数
+    log::logger().flush();
+    mark_current_exited(exit_code);
     run_next_task();
 }
 
+/// Reap a `Zombie` child of current task, any child if `pid` is -1, return its
+/// pid and exit code, `Err(-1)` if there is no such child or `Err(-2)` if it has not exited.
+pub fn reap_child(pid: isize) -> Result<(usize, i32), isize> {
+    TASK_MANAGER.reap_child(pid)
+}
+
+/// Set the priority of current `Running` task, return the effective priority
+/// or `None` if `priority` is below [`config::MIN_PRIORITY`].
+pub fn set_current_priority(priority: usize) -> Option<usize> {
+    TASK_MANAGER.set_current_priority(priority)
+}
+
+/// Set the time slice of current `Running` task in timer ticks, return the
+/// effective length or `None` if `ticks` is 0.
+pub fn set_current_time_slice(ticks: usize) -> Option<usize> {
+    TASK_MANAGER.set_current_time_slice(ticks)
+}
+
+/// Get the time slice of current `Running` task in timer ticks.
+pub fn current_time_slice() -> usize {
+    TASK_MANAGER.get_current_time_slice()
+}
+
+/// Charge a timer tick to current `Running` task, return `true` if its time
+/// slice has run out and it should be preempted.
+pub fn tick_current() -> bool {
+    TASK_MANAGER.tick_current()
+}
+
+/// Count an occurrence of `event` in current `Running` task.
+pub fn count_current(event: PerfEvent) {
+    TASK_MANAGER.count_current(event);
+}
+
+/// Get the pid of current `Running` task and the pid of its parent, if any.
+pub fn current_pid() -> (usize, Option<usize>) {
+    TASK_MANAGER.get_current_pid()
+}
+
+/// Get the name and pid of current `Running` task.
+pub fn current_desc() -> TaskDesc {
+    TASK_MANAGER.get_current_desc()
+}
+
+/// Get the name and pid of current task for a panic message, `None` if no task
+/// has run yet or the task manager is borrowed.
+pub fn try_current_desc() -> Option<TaskDesc> {
+    TASK_MANAGER.get()?.try_get_current_desc()
+}
+
+/// Warn that current `Running` task made the unsupported syscall `syscall_id`,
+/// once per syscall id per task.
+pub fn report_unsupported_syscall(syscall_id: usize) {
+    TASK_MANAGER.report_unsupported_syscall(syscall_id)
+}
+
+/// Rename current `Running` task, the new name shows in `ps`, logs and kill messages.
+pub fn set_current_name(name: TaskName) {
+    TASK_MANAGER.set_current_name(name)
+}
+
+/// Print a line for every task, like `ps`, return the number of tasks.
+pub fn print_tasks() -> usize {
+    TASK_MANAGER.print_tasks()
+}
+
+/// Print the I/O statistics of every open fd of every task, like `iostat`, return the number of fds.
+pub fn print_io_stats() -> usize {
+    TASK_MANAGER.print_io_stats()
+}
+
+/// Whether a task other than current `Running` task has a file of the file system open.
+///
+/// 已经退出的任务不会再使用它的文件，不计算在内
+pub fn others_have_fs_files_open() -> bool {
+    TASK_MANAGER.others_have_fs_files_open()
+}
+
+/// Get the scheduling information of current `Running` task.
+pub fn current_task_info() -> TaskInfo {
+    TASK_MANAGER.get_current_task_info()
+}
+
+/// Get the event counters of current `Running` task.
+pub fn current_perf() -> PerfCounters {
+    TASK_MANAGER.get_current_perf()
+}
+
+/// Get the priority of current `Running` task.
+pub fn current_priority() -> usize {
+    TASK_MANAGER.get_current_priority()
+}
+
+/// Get the umask of current `Running` task, applied to the permission bits of the
+/// files and directories it creates.
+pub fn current_umask() -> u32 {
+    TASK_MANAGER.get_current_umask()
+}
+
+/// Replace the umask of current `Running` task, return the old one.
+pub fn replace_current_umask(umask: u32) -> u32 {
+    TASK_MANAGER.replace_current_umask(umask)
+}
+
+/// Get the file opened at `fd` by current `Running` task, `None` if `fd` is not open.
+///
+/// 返回的是文件的引用计数，读写可能阻塞并切换任务，不能在持有任务管理器时进行
+pub fn current_file(fd: usize) -> Option<Arc<FileDesc>> {
+    TASK_MANAGER.get_current_file(fd)
+}
+
+/// Open `file` in current `Running` task at the lowest free fd, return the fd.
+pub fn add_current_file(file: FileDesc) -> usize {
+    TASK_MANAGER.add_current_file(file)
+}
+
+/// Close `fd` of current `Running` task, return the file or `None` if `fd` is not open.
+///
+/// 文件在最后一个引用被释放时关闭，可能还有正在进行的读写持有它
+pub fn take_current_file(fd: usize) -> Option<Arc<FileDesc>> {
+    TASK_MANAGER.take_current_file(fd)
+}
+
+/// Move current `Running` task into scheduling class `class`.
+pub fn set_current_sched_class(class: SchedClass) {
+    TASK_MANAGER.set_current_sched_class(class);
+}
+
+/// Get the scheduling class of current `Running` task.
+pub fn current_sched_class() -> SchedClass {
+    TASK_MANAGER.get_current_sched_class()
+}
+
+/// Get the CPU time limit of current `Running` task.
+pub fn current_cpu_limit() -> RLimit {
+    TASK_MANAGER.get_current_cpu_limit()
+}
+
+/// Set the CPU time limit of current `Running` task, return `false` if the
+/// soft limit exceeds the hard limit or the hard limit is raised.
+pub fn set_current_cpu_limit(limit: RLimit) -> bool {
+    TASK_MANAGER.set_current_cpu_limit(limit)
+}
+
+/// Return `true` if current `Running` task has run past its hard CPU time limit.
+pub fn current_cpu_limit_exceeded() -> bool {
+    TASK_MANAGER.current_cpu_limit_exceeded()
+}
+
 /// Get the current 'Running' task's token.
 pub fn current_user_token() -> usize {
     TASK_MANAGER.get_current_token()
 }
 
+/// Get the layout of the address space of current `Running` task,
+/// one `start-end perms kind` line per area.
+pub fn current_maps() -> String {
+    TASK_MANAGER.get_current_maps()
+}
+
+/// Check that `[start, start + len)` in the address space of current `Running` task
+/// lies in user areas allowing `access`, and make sure that its lazy pages are
+/// mapped so that the kernel can access it through the page table.
+///
+/// 物理页帧耗尽时同样返回 `false`
+pub fn prepare_current_user_range(start: usize, len: usize, access: UserAccess) -> bool {
+    TASK_MANAGER.prepare_current_user_range(start, len, access)
+}
+
+/// Release the pages pinned by [`prepare_current_user_range`], called when a syscall returns.
+pub fn unpin_current_user_pages() {
+    TASK_MANAGER.unpin_current_user_pages()
+}
+
+/// Handle a page fault of current `Running` task at `va` lacking `permission`,
+/// return `false` unless it is the first access to a lazy page and a frame is
+/// allocated for it.
+pub fn handle_current_lazy_fault(va: usize, permission: MapPermission) -> bool {
+    TASK_MANAGER.handle_current_lazy_fault(va, permission)
+}
+
+/// Map `[start, end)` in the address space of current `Running` task to new frames,
+/// fail if it overlaps existing areas.
+pub fn current_mmap(start: usize, end: usize, permission: MapPermission) -> Result<(), MapError> {
+    TASK_MANAGER.current_mmap(start, end, permission)
+}
+
+/// Unmap `[start, end)` in the address space of current `Running` task, return
+/// `false` unless it is covered exactly by areas mapped by [`current_mmap`].
+pub fn current_munmap(start: usize, end: usize) -> bool {
+    TASK_MANAGER.current_munmap(start, end)
+}
+
+/// Move the program break of current `Running` task by `increment` bytes, return
+/// the old break or `None` if the heap can not be resized so.
+pub fn change_current_program_brk(increment: isize) -> Option<usize> {
+    TASK_MANAGER.change_current_program_brk(increment)
+}
+
+/// Print the memory areas of current `Running` task and why accessing `fault_va` faulted.
+pub fn dump_current_memory_set(fault_va: usize) {
+    TASK_MANAGER.dump_current_memory_set(fault_va);
+}
+
+/// Panic if the kernel stack of current 'Running' task has overflowed.
+#[cfg(feature = "stack_canary")]
+pub fn check_current_kernel_stack() {
+    TASK_MANAGER.check_current_kernel_stack();
+}
+
+/// Complete the audit of the `__switch` into current `Running` task, if any.
+#[cfg(feature = "switch_audit")]
+pub fn finish_current_switch() {
+    TASK_MANAGER.finish_current_switch();
+}
+
+/// Panic if a `__switch` has not returned to user space yet.
+#[cfg(feature = "switch_audit")]
+pub fn check_no_pending_switch() {
+    self::switch_audit::check_no_pending();
+}
+
 /// Get the current 'Running' task's trap contexts.
 pub fn current_trap_cx() -> &'static mut TrapContext {
     TASK_MANAGER.get_current_trap_cx()
@@ -0,0 +1,63 @@
+//! Process identifiers
+
+use alloc::vec::Vec;
+
+use crate::sync::UPSafeCell;
+
+/// 第一个分配的 pid 。与 Linux 一样，pid 0 不分配给任何任务：系统调用中的 pid 0 表示当前任务，
+/// `getppid` 返回 0 表示没有父任务
+const FIRST_PID: usize = 1;
+
+/// allocator of recyclable pids
+pub struct PidAllocator {
+    /// 从未分配过的最小 pid
+    current: usize,
+    /// 被回收的 pid
+    recycled: Vec<usize>,
+}
+
+impl PidAllocator {
+    pub const fn new() -> Self {
+        Self {
+            current: FIRST_PID,
+            recycled: Vec::new(),
+        }
+    }
+
+    /// allocate a pid, reusing recycled pids first
+    pub fn alloc(&mut self) -> PidHandle {
+        if let Some(pid) = self.recycled.pop() {
+            PidHandle(pid)
+        } else {
+            self.current += 1;
+            PidHandle(self.current - 1)
+        }
+    }
+
+    /// recycle `pid`, panic if it is not allocated
+    pub fn dealloc(&mut self, pid: usize) {
+        assert!(
+            (FIRST_PID..self.current).contains(&pid) && !self.recycled.contains(&pid),
+            "pid {} has not been allocated",
+            pid
+        );
+        self.recycled.push(pid);
+    }
+}
+
+static PID_ALLOCATOR: UPSafeCell<PidAllocator> = unsafe { UPSafeCell::new(PidAllocator::new()) };
+
+/// a pid which is recycled when the handle is dropped
+#[derive(Debug)]
+pub struct PidHandle(pub usize);
+
+impl Drop for PidHandle {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+    }
+}
+
+/// allocate a pid
+pub fn pid_alloc() -> PidHandle {
+    PID_ALLOCATOR.exclusive_access().alloc()
+}
@@ -0,0 +1,127 @@
+//! Ready queue of the scheduler
+
+use alloc::collections::{BinaryHeap, VecDeque};
+use core::cmp::Ordering;
+
+use super::table::TaskTable;
+use super::task::{TaskControlBlock, TaskStatus};
+
+/// a `Ready` task in [`ReadyQueue`], with the scheduling key it was queued with
+///
+/// 任务在就绪队列中时其类别和调度键一般不会改变，改变时由 [`ReadyQueue::requeue`] 重新入队
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Entry {
+    rank: usize,
+    /// 见 [`TaskControlBlock::sched_key`]
+    key: usize,
+    /// 入队序号，与任务的 `ready_seq` 不同时说明该项已经失效
+    seq: usize,
+    id: usize,
+}
+
+impl Ord for Entry {
+    /// 大顶堆中先出队的项更大：类别高者优先，其次是调度键小者，最后是先入队者
+    ///
+    /// 队列中任意两个任务的行程之差不超过 `BIG_STRIDE / 2` ，按有符号数比较回绕后的差值
+    /// 在队列中的任务之间是全序；多级反馈队列的级别很小，这样比较也是正确的
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank
+            .cmp(&other.rank)
+            .then_with(|| (other.key.wrapping_sub(self.key) as isize).cmp(&0))
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `Ready` tasks ordered by scheduling class and [`TaskControlBlock::sched_key`],
+/// and by the time they became `Ready`
+///
+/// 入队和出队的开销与任务总数无关。任务离开就绪状态或重新入队后，原来的项失效，
+/// 在出队或到达队首时再丢弃
+pub struct ReadyQueue {
+    /// 按调度顺序排列
+    heap: BinaryHeap<Entry>,
+    /// 按进入就绪状态的先后排列的 `(任务号, 入队序号)` ，用于检查饥饿
+    fifo: VecDeque<(usize, usize)>,
+    next_seq: usize,
+}
+
+/// whether the entry `(id, seq)` still stands for a `Ready` task
+fn is_valid(tasks: &TaskTable, id: usize, seq: usize) -> bool {
+    tasks
+        .get(id)
+        .is_some_and(|task| task.task_status == TaskStatus::Ready && task.ready_seq == seq)
+}
+
+impl ReadyQueue {
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            fifo: VecDeque::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// queue task `id`, which has just become `Ready`
+    pub fn push(&mut self, id: usize, task: &mut TaskControlBlock) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        task.ready_seq = seq;
+        self.heap.push(Entry {
+            rank: task.sched_class.rank(),
+            key: task.sched_key(),
+            seq,
+            id,
+        });
+        self.fifo.push_back((id, seq));
+    }
+
+    /// queue the `Ready` task `id` again after its scheduling key changed
+    ///
+    /// 沿用原来的入队序号，因此不影响饥饿检查；原来的项在任务出队后随之失效
+    #[cfg_attr(not(feature = "mlfq"), allow(unused))]
+    pub fn requeue(&mut self, id: usize, task: &TaskControlBlock) {
+        self.heap.push(Entry {
+            rank: task.sched_class.rank(),
+            key: task.sched_key(),
+            seq: task.ready_seq,
+            id,
+        });
+    }
+
+    /// take the `Ready` task to run next out of the queue
+    pub fn pop(&mut self, tasks: &TaskTable) -> Option<usize> {
+        while let Some(entry) = self.heap.pop() {
+            if is_valid(tasks, entry.id, entry.seq) {
+                return Some(entry.id);
+            }
+        }
+        None
+    }
+
+    /// take task `id` out of the queue, when it is chosen to run out of order
+    pub fn remove(&mut self, id: usize) {
+        self.heap.retain(|entry| entry.id != id);
+    }
+
+    /// the `Ready` task waiting the longest since it was last checked for starvation
+    pub fn oldest(&mut self, tasks: &TaskTable) -> Option<usize> {
+        while let Some(&(id, seq)) = self.fifo.front() {
+            if is_valid(tasks, id, seq) {
+                return Some(id);
+            }
+            self.fifo.pop_front();
+        }
+        None
+    }
+
+    /// stop checking the task returned by [`ReadyQueue::oldest`] for starvation until it is queued again
+    pub fn pop_oldest(&mut self) {
+        self.fifo.pop_front();
+    }
+}
@@ -0,0 +1,125 @@
+//! Context switch auditing
+//!
+//! 只在 `switch_audit` 特性下编译。每次调用 `__switch` 之前记录一次尚未完成的切换，
+//! 被切换到的任务经过 `trap_return` 返回用户态时完成这次切换。若切换完成之前又进入了
+//! trap 处理或者开始了新的切换，或者回到 `trap_return` 的不是被切换到的任务，说明 `__switch`
+//! 跳到了错误的位置（例如 `TaskContext` 被破坏），输出切换双方的编号和切换记录后 panic 。
+
+use alloc::vec::Vec;
+
+use riscv::register::sstatus;
+
+use crate::sync::UPSafeCell;
+use crate::timer;
+
+use super::task::TaskName;
+
+/// a `__switch` that has not returned to user space through `trap_return` yet
+#[derive(Copy, Clone)]
+struct PendingSwitch {
+    /// 切换前运行的任务，`None` 表示启动时的上下文
+    from: Option<usize>,
+    to: usize,
+    /// 被切换到的任务的名字
+    to_name: TaskName,
+    started_at_us: usize,
+}
+
+/// timestamps of the switches of a task
+#[derive(Copy, Clone, Default, Debug)]
+struct SwitchRecord {
+    /// 最近一次被切换出去的时间，单位为 `us`
+    switched_out_us: usize,
+    /// 最近一次完成切换回到用户态的时间，单位为 `us`
+    switched_in_us: usize,
+    /// 从开始切换到该任务到完成切换的最长时间，单位为 `us`
+    longest_switch_us: usize,
+}
+
+struct SwitchAudit {
+    pending: Option<PendingSwitch>,
+    /// 按任务编号索引
+    records: Vec<SwitchRecord>,
+}
+
+impl SwitchAudit {
+    fn record(&mut self, task: usize) -> &mut SwitchRecord {
+        if self.records.len() <= task {
+            self.records.resize(task + 1, SwitchRecord::default());
+        }
+        &mut self.records[task]
+    }
+
+    /// panic with both tasks of `pending` and their records
+    fn report(&mut self, pending: PendingSwitch, what: core::fmt::Arguments) -> ! {
+        let from = pending.from.map(|from| (from, *self.record(from)));
+        let to = (pending.to, *self.record(pending.to));
+        panic!(
+            "{}: __switch from {:?} to {} ({}) started at {}us never completed, records: {:?} {:?}",
+            what, pending.from, pending.to, pending.to_name, pending.started_at_us, from, to
+        );
+    }
+}
+
+static AUDIT: UPSafeCell<SwitchAudit> = unsafe {
+    UPSafeCell::new(SwitchAudit {
+        pending: None,
+        records: Vec::new(),
+    })
+};
+
+/// record a switch from task `from` (`None` for the boot context) to task `to`
+/// named `to_name`, right before calling `__switch`
+pub fn begin(from: Option<usize>, to: usize, to_name: TaskName) {
+    assert!(
+        !sstatus::read().sie(),
+        "interrupts enabled across __switch from {:?} to {}",
+        from,
+        to
+    );
+    let now = timer::get_time_us();
+    let mut audit = AUDIT.exclusive_access();
+    if let Some(pending) = audit.pending {
+        audit.report(pending, format_args!("new switch to {} at {}us", to, now));
+    }
+    if let Some(from) = from {
+        audit.record(from).switched_out_us = now;
+    }
+    audit.pending = Some(PendingSwitch {
+        from,
+        to,
+        to_name,
+        started_at_us: now,
+    });
+}
+
+/// complete the pending switch, if any, when task `current` returns to user space
+pub fn finish(current: usize) {
+    let mut audit = AUDIT.exclusive_access();
+    let Some(pending) = audit.pending else {
+        return;
+    };
+    if pending.to != current {
+        audit.report(pending, format_args!("task {} resumed instead", current));
+    }
+    assert!(
+        !sstatus::read().sie(),
+        "interrupts enabled across __switch from {:?} to {}",
+        pending.from,
+        current
+    );
+    let now = timer::get_time_us();
+    audit.pending = None;
+    let record = audit.record(current);
+    record.switched_in_us = now;
+    record.longest_switch_us = record.longest_switch_us.max(now - pending.started_at_us);
+}
+
+/// panic if a trap is taken while a switch is still pending
+pub fn check_no_pending() {
+    let mut audit = AUDIT.exclusive_access();
+    if let Some(pending) = audit.pending {
+        let now = timer::get_time_us();
+        audit.report(pending, format_args!("trap taken at {}us", now));
+    }
+}
@@ -0,0 +1,79 @@
+//! Table of task control blocks indexed by task id
+
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+use super::task::TaskControlBlock;
+
+/// task control blocks indexed by task id, the id of a reaped task is reused
+///
+/// 任务号同时决定内核栈在内核地址空间中的位置，回收任务后空出的位置留给新任务
+pub struct TaskTable {
+    slots: Vec<Option<TaskControlBlock>>,
+}
+
+impl TaskTable {
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&TaskControlBlock> {
+        self.slots.get(id)?.as_ref()
+    }
+
+    /// every task with its id
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &TaskControlBlock)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| Some((id, slot.as_ref()?)))
+    }
+
+    /// every task with its id, mutably
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut TaskControlBlock)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(id, slot)| Some((id, slot.as_mut()?)))
+    }
+
+    /// the smallest free id
+    pub fn free_id(&self) -> usize {
+        self.slots
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.slots.len())
+    }
+
+    /// put `task` at `id`, which must be free
+    pub fn insert(&mut self, id: usize, task: TaskControlBlock) {
+        if id >= self.slots.len() {
+            self.slots.resize_with(id + 1, || None);
+        }
+        assert!(self.slots[id].is_none(), "task {} already exists", id);
+        self.slots[id] = Some(task);
+    }
+
+    /// take task `id` out of the table
+    pub fn remove(&mut self, id: usize) -> Option<TaskControlBlock> {
+        self.slots.get_mut(id)?.take()
+    }
+}
+
+impl Index<usize> for TaskTable {
+    type Output = TaskControlBlock;
+
+    fn index(&self, id: usize) -> &TaskControlBlock {
+        self.get(id)
+            .unwrap_or_else(|| panic!("task {} does not exist", id))
+    }
+}
+
+impl IndexMut<usize> for TaskTable {
+    fn index_mut(&mut self, id: usize) -> &mut TaskControlBlock {
+        self.slots
+            .get_mut(id)
+            .and_then(Option::as_mut)
+            .unwrap_or_else(|| panic!("task {} does not exist", id))
+    }
+}
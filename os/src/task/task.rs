@@ -1,18 +1,76 @@
 //! Types related to task management
 
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
 use crate::config;
-use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::fs::{FileDesc, Stdin, Stdout};
+use crate::loader;
+use crate::mm::{MapError, MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::timer;
 use crate::trap::{self, TrapContext};
+use abi::{PerfCounters, RLimit, SCHED_IDLE, SCHED_INTERACTIVE, SCHED_NORMAL, TASK_NAME_LEN};
 
+use super::pid::{pid_alloc, PidHandle};
 use super::TaskContext;
 
 /// task control block structure
 pub struct TaskControlBlock {
+    /// 进程标识符，任务控制块被释放时回收
+    pub pid: PidHandle,
+    /// 父任务的 pid ，启动时创建的任务和父任务已经退出的孤儿任务没有父任务
+    pub parent: Option<usize>,
+    /// 任务名，创建时为应用名，出现在日志、panic 信息和任务列表中
+    pub name: TaskName,
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
     pub memory_set: MemorySet,
     pub trap_cx_ppn: PhysPageNum,
     pub base_size: usize,
+    /// 堆的起始地址，即用户栈顶
+    pub heap_bottom: usize,
+    /// 堆的结束地址（program break），由 `sbrk` 调整
+    pub program_brk: usize,
+    /// 内核栈，任务控制块被释放时从内核地址空间中删除
+    #[cfg_attr(not(feature = "stack_canary"), allow(unused))]
+    pub kernel_stack: KernelStack,
+    /// 退出码，只在任务退出后有意义
+    pub exit_code: i32,
+    /// 调度优先级，取值范围为 `[MIN_PRIORITY, MAX_PRIORITY]`
+    pub priority: usize,
+    /// stride 调度中累计的行程，每次被调度时增加 `BIG_STRIDE / priority`
+    pub stride: usize,
+    /// 时间片长度，单位为时钟中断的周期
+    pub time_slice: usize,
+    /// 本次被调度后时间片中剩余的时钟中断周期数
+    pub slice_left: usize,
+    /// 在多级反馈队列中的级别，0 为最高级
+    #[cfg_attr(not(feature = "mlfq"), allow(unused))]
+    pub mlfq_level: usize,
+    /// 最近一次进入就绪队列时的序号
+    pub ready_seq: usize,
+    /// 调度类别，只在同一类别的任务之间按行程调度
+    pub sched_class: SchedClass,
+    /// 文件创建掩码，创建文件或目录时从请求的权限位中去掉这些位
+    pub umask: u32,
+    /// 文件描述符表，下标为文件描述符，`None` 表示未使用
+    pub fd_table: Vec<Option<Arc<FileDesc>>>,
+    /// 累计占用 CPU 的时间，单位为 `us`，不包括本次被调度后的运行时间
+    pub cpu_time_us: usize,
+    /// 本次被调度开始运行的时间，单位为 `us`
+    pub scheduled_at_us: usize,
+    /// CPU 时间限制（RLIMIT_CPU），单位为秒
+    pub cpu_limit: RLimit,
+    /// 是否已经报告过超出 CPU 时间的软限制
+    pub cpu_soft_limit_reported: bool,
+    /// 已经报告过的不支持的系统调用号，每个只报告一次
+    pub unsupported_syscalls: Vec<usize>,
+    /// 调度公平性统计
+    pub sched_stats: SchedStats,
+    /// 事件计数
+    pub perf: PerfCounters,
 }
 
 impl TaskControlBlock {
@@ -20,31 +78,138 @@ impl TaskControlBlock {
         self.trap_cx_ppn.as_mut::<TrapContext>()
     }
 
+    pub fn desc(&self) -> TaskDesc {
+        TaskDesc {
+            name: self.name,
+            pid: self.pid.0,
+        }
+    }
+
     pub fn get_user_token(&self) -> usize {
         self.memory_set.token()
     }
 
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
-        // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+    /// CPU time consumed so far in `us`, including the current run if it is `Running`
+    pub fn cpu_time_us(&self) -> usize {
+        match self.task_status {
+            TaskStatus::Running => self.cpu_time_us + (timer::get_time_us() - self.scheduled_at_us),
+            _ => self.cpu_time_us,
+        }
+    }
+
+    /// the stride added to `self.stride` each time the task is scheduled
+    pub fn pass(&self) -> usize {
+        BIG_STRIDE / self.priority
+    }
+
+    /// the time slice in timer ticks for the next run
+    ///
+    /// 多级反馈队列中级别越低，时间片越长
+    pub fn quantum(&self) -> usize {
+        #[cfg(feature = "mlfq")]
+        return self.time_slice << self.mlfq_level;
+        #[cfg(not(feature = "mlfq"))]
+        self.time_slice
+    }
+
+    /// the key ordering `Ready` tasks of the same scheduling class, the smaller goes first
+    ///
+    /// stride 调度中为行程，多级反馈队列中为级别；同键的任务按进入就绪状态的先后调度
+    pub fn sched_key(&self) -> usize {
+        #[cfg(feature = "mlfq")]
+        return self.mlfq_level;
+        #[cfg(not(feature = "mlfq"))]
+        self.stride
+    }
+
+    /// whether `self` has gone less far than `other` in stride scheduling
+    ///
+    /// 优先级不低于 `MIN_PRIORITY` 保证了任意两个任务的行程之差不超过 `BIG_STRIDE / 2`，
+    /// 因此行程溢出回绕后按有符号数比较差值依然正确
+    pub fn stride_before(&self, other: &Self) -> bool {
+        (self.stride.wrapping_sub(other.stride) as isize) < 0
+    }
+
+    /// the lowest file descriptor not in use, extending the fd table if all are
+    pub fn alloc_fd(&mut self) -> usize {
+        match self.fd_table.iter().position(Option::is_none) {
+            Some(fd) => fd,
+            None => {
+                self.fd_table.push(None);
+                self.fd_table.len() - 1
+            }
+        }
+    }
+
+    /// Move the program break by `increment` bytes, return the old break or `None`
+    /// if it would go below the bottom of the heap or the heap can not grow.
+    pub fn change_program_brk(&mut self, increment: isize) -> Option<usize> {
+        let old_brk = self.program_brk;
+        let new_brk = old_brk
+            .checked_add_signed(increment)
+            .filter(|&brk| brk >= self.heap_bottom && brk <= config::USER_SPACE_END)?;
+        self.memory_set
+            .resize_heap(self.heap_bottom.into(), new_brk.into())
+            .ok()?;
+        self.program_brk = new_brk;
+        Some(old_brk)
+    }
+
+    /// Create task `task_id` running app `app_id`, with the kernel stack slot of `task_id`.
+    ///
+    /// 失败时已经分配的页帧和内核栈都被回收
+    pub fn new(app_id: usize, task_id: usize) -> Result<Self, SpawnError> {
+        // memory_set with segments of the executable/trampoline/trap context/user stack
+        let data = loader::get_app_data(app_id);
+        let image = loader::load(&data).map_err(SpawnError::Load)?;
+        let (memory_set, user_sp, entry_point) =
+            MemorySet::from_image(&image).map_err(SpawnError::Map)?;
         let trap_cx_ppn: PhysPageNum = memory_set
             .translate(VirtAddr::from(config::TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
         let task_status = TaskStatus::Ready;
         // map a kernel-stack in kernel space
-        let (kernel_stack_bottom, kernel_stack_top) = config::kernel_stack_position(app_id);
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
-            kernel_stack_bottom.into(),
-            kernel_stack_top.into(),
-            MapPermission::R | MapPermission::W,
-        );
+        let kernel_stack =
+            KernelStack::new(task_id, image.stack_sizes.kernel).map_err(SpawnError::Map)?;
+        let kernel_stack_top = kernel_stack.top;
         let task_control_block = Self {
+            pid: pid_alloc(),
+            parent: None,
+            name: TaskName::new(&loader::get_app_name(app_id)),
             task_status,
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
             memory_set,
             trap_cx_ppn,
             base_size: user_sp,
+            heap_bottom: user_sp,
+            program_brk: user_sp,
+            kernel_stack,
+            exit_code: 0,
+            priority: config::DEFAULT_PRIORITY,
+            stride: 0,
+            time_slice: config::DEFAULT_TIME_SLICE,
+            slice_left: config::DEFAULT_TIME_SLICE,
+            mlfq_level: 0,
+            ready_seq: 0,
+            sched_class: SchedClass::Normal,
+            umask: config::DEFAULT_UMASK,
+            // 0 -> stdin, 1 -> stdout, 2 -> stderr
+            fd_table: vec![
+                Some(Arc::new(FileDesc::new("stdin", Arc::new(Stdin)))),
+                Some(Arc::new(FileDesc::new("stdout", Arc::new(Stdout)))),
+                Some(Arc::new(FileDesc::new("stderr", Arc::new(Stdout)))),
+            ],
+            cpu_time_us: 0,
+            scheduled_at_us: 0,
+            cpu_limit: RLimit {
+                cur: config::DEFAULT_CPU_LIMIT,
+                max: config::DEFAULT_CPU_LIMIT,
+            },
+            cpu_soft_limit_reported: false,
+            unsupported_syscalls: Vec::new(),
+            sched_stats: SchedStats::new(timer::get_time_us()),
+            perf: PerfCounters::default(),
         };
         // prepare TrapContext in user space
         let trap_cx: &mut TrapContext = task_control_block.trap_ctx();
@@ -55,14 +220,255 @@ impl TaskControlBlock {
             kernel_stack_top,
             trap::trap_handler as usize,
         );
-        task_control_block
+        Ok(task_control_block)
+    }
+}
+
+/// error returned when a task can not be created
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SpawnError {
+    /// 应用的可执行文件无法加载
+    Load(loader::LoadError),
+    /// 建立地址空间或内核栈失败，通常是物理页帧耗尽
+    Map(MapError),
+}
+
+impl Display for TaskControlBlock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.desc().fmt(f)
+    }
+}
+
+/// name and pid of a task, displayed as `name (pid N)` in diagnostics
+#[derive(Copy, Clone)]
+pub struct TaskDesc {
+    pub name: TaskName,
+    pub pid: usize,
+}
+
+impl Display for TaskDesc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+/// name of a task, truncated to [`TASK_NAME_LEN`] bytes
+///
+/// 定长存放，在任务切换和 panic 等不便分配内存的路径上也可以复制和输出
+#[derive(Copy, Clone)]
+pub struct TaskName {
+    bytes: [u8; TASK_NAME_LEN],
+    len: usize,
+}
+
+impl TaskName {
+    pub fn new(name: &str) -> Self {
+        let mut len = name.len().min(TASK_NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; TASK_NAME_LEN];
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self { bytes, len }
+    }
+
+    /// Name a task with `bytes` given by an app, replacing everything but printable
+    /// ASCII characters with `?`.
+    ///
+    /// 应用设置的名字会原样出现在内核日志和 `ps` 的输出中，不能包含换行和控制字符
+    pub fn sanitized(bytes: &[u8]) -> Self {
+        let len = bytes.len().min(TASK_NAME_LEN);
+        let mut name = [0; TASK_NAME_LEN];
+        for (dst, &c) in name.iter_mut().zip(&bytes[..len]) {
+            *dst = if c.is_ascii_graphic() || c == b' ' {
+                c
+            } else {
+                b'?'
+            };
+        }
+        Self { bytes: name, len }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap()
+    }
+}
+
+impl Display for TaskName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// kernel stack of a task, unmapped from kernel space when dropped
+pub struct KernelStack {
+    pub bottom: usize,
+    pub top: usize,
+}
+
+impl KernelStack {
+    /// Map a kernel stack of `size` bytes in the kernel stack slot `task_id`.
+    pub fn new(task_id: usize, size: usize) -> Result<Self, MapError> {
+        let (bottom, top) = config::kernel_stack_position(task_id, size);
+        KERNEL_SPACE.exclusive_access().insert_framed_area(
+            bottom.into(),
+            top.into(),
+            MapPermission::R | MapPermission::W,
+        )?;
+        #[cfg(feature = "stack_canary")]
+        self::init_kernel_stack_canary(bottom);
+        Ok(Self { bottom, top })
+    }
+}
+
+impl Drop for KernelStack {
+    /// 同一位置之后可能映射新任务的内核栈，因此删除映射后立即刷新快表
+    fn drop(&mut self) {
+        let mut kernel_space = KERNEL_SPACE.exclusive_access();
+        kernel_space.remove_area_with_start_vpn(VirtAddr::from(self.bottom).into());
+        kernel_space.activate();
+    }
+}
+
+/// an event counted in [`PerfCounters`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PerfEvent {
+    PageFault,
+    Syscall,
+    ContextSwitch,
+    TlbFlush,
+    Preemption,
+    FaultFixup,
+}
+
+impl PerfEvent {
+    /// the counter of the event in `counters`
+    pub fn counter(self, counters: &mut PerfCounters) -> &mut u64 {
+        match self {
+            PerfEvent::PageFault => &mut counters.page_faults,
+            PerfEvent::Syscall => &mut counters.syscalls,
+            PerfEvent::ContextSwitch => &mut counters.context_switches,
+            PerfEvent::TlbFlush => &mut counters.tlb_flushes,
+            PerfEvent::Preemption => &mut counters.preemptions,
+            PerfEvent::FaultFixup => &mut counters.fault_fixups,
+        }
+    }
+}
+
+/// scheduling fairness statistics of a task
+#[derive(Copy, Clone, Debug)]
+pub struct SchedStats {
+    /// 被调度运行的次数
+    pub runs: usize,
+    /// 在就绪状态下等待调度的总时间，单位为 `us`
+    pub total_wait_us: usize,
+    /// 单次等待调度的最长时间，单位为 `us`
+    pub max_wait_us: usize,
+    /// 最近一次进入就绪状态的时间，单位为 `us`
+    pub ready_since_us: usize,
+    /// 本次等待是否已经被报告为饥饿
+    pub starvation_reported: bool,
+}
+
+impl SchedStats {
+    pub fn new(now_us: usize) -> Self {
+        Self {
+            runs: 0,
+            total_wait_us: 0,
+            max_wait_us: 0,
+            ready_since_us: now_us,
+            starvation_reported: false,
+        }
+    }
+
+    /// the task becomes `Ready` at `now_us`
+    pub fn on_ready(&mut self, now_us: usize) {
+        self.ready_since_us = now_us;
+        self.starvation_reported = false;
+    }
+
+    /// the task is scheduled to run at `now_us` after waiting since it became `Ready`
+    pub fn on_run(&mut self, now_us: usize) {
+        let wait_us = now_us - self.ready_since_us;
+        self.runs += 1;
+        self.total_wait_us += wait_us;
+        self.max_wait_us = self.max_wait_us.max(wait_us);
+    }
+}
+
+/// stride 调度中的大常数，任务每次被调度时行程增加 `BIG_STRIDE / priority`
+const BIG_STRIDE: usize = isize::MAX as usize;
+
+/// 写在每个应用内核栈最底部的魔数，若被改写说明内核栈发生了溢出
+#[cfg(feature = "stack_canary")]
+const KERNEL_STACK_CANARY: usize = 0x5afe_57ac_cafe_babe;
+
+/// write the canary at the bottom of a freshly mapped kernel stack
+///
+/// 通过物理页帧写入，无需依赖刚插入的映射已在快表中生效
+#[cfg(feature = "stack_canary")]
+fn init_kernel_stack_canary(kernel_stack_bottom: usize) {
+    let ppn: PhysPageNum = KERNEL_SPACE
+        .exclusive_access()
+        .translate(VirtAddr::from(kernel_stack_bottom).into())
+        .unwrap()
+        .ppn();
+    *ppn.as_mut::<usize>() = KERNEL_STACK_CANARY;
+}
+
+/// check the canary at the bottom of the kernel stack of task `task_id`,
+/// panic with the owner of the kernel stack if it has been overwritten
+#[cfg(feature = "stack_canary")]
+pub fn check_kernel_stack_canary(task: &TaskControlBlock) {
+    let (kernel_stack_bottom, kernel_stack_top) = (task.kernel_stack.bottom, task.kernel_stack.top);
+    let canary = unsafe { (kernel_stack_bottom as *const usize).read_volatile() };
+    if canary != KERNEL_STACK_CANARY {
+        panic!(
+            "kernel stack of {} [{:#x}, {:#x}) corrupted, canary = {:#x}",
+            task, kernel_stack_bottom, kernel_stack_top, canary
+        );
+    }
+}
+
+/// scheduling class of a task
+///
+/// 有 `Ready` 的交互类任务时只调度交互类任务，其次是普通类任务，
+/// 空闲类任务只在没有其他 `Ready` 任务时才会运行
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SchedClass {
+    Normal = 0,
+    Interactive = 1,
+    Idle = 2,
+}
+
+impl SchedClass {
+    pub fn from_usize(class: usize) -> Option<Self> {
+        match class {
+            SCHED_NORMAL => Some(SchedClass::Normal),
+            SCHED_INTERACTIVE => Some(SchedClass::Interactive),
+            SCHED_IDLE => Some(SchedClass::Idle),
+            _ => None,
+        }
+    }
+
+    /// 类别的优先顺序，数值越大越先被调度
+    pub fn rank(self) -> usize {
+        match self {
+            SchedClass::Idle => 0,
+            SchedClass::Normal => 1,
+            SchedClass::Interactive => 2,
+        }
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-/// task status: UnInit, Ready, Running, Exited
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// task status: Ready, Running, Blocked, Zombie
+///
+/// 阻塞的任务在等待的事件发生（如睡眠到期）之前不会被调度。
+/// 退出的任务先成为僵尸，保留退出码直到被父任务回收，其地址空间中的数据页在退出时即被释放
 pub enum TaskStatus {
     Ready,
     Running,
-    Exited,
+    Blocked,
+    Zombie,
 }
@@ -1,13 +1,45 @@
 //! RISC-V timer-related functionality
 
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use riscv::register;
 
+use crate::dtb::DeviceTree;
+use crate::sync::UPSafeCell;
 use crate::{config, sbi};
 
-const TICKS_PER_SEC: usize = 100;
 pub const MSEC_PER_SEC: usize = 1000;
 pub const MICRO_PER_SEC: usize = 1_000_000;
 
+/// `mtime` 寄存器的计数频率，启动时以设备树中的 `timebase-frequency` 为准
+static CLOCK_FREQ: AtomicUsize = AtomicUsize::new(config::CLOCK_FREQ);
+
+/// frequency of the `mtime` register in Hz
+pub fn clock_freq() -> usize {
+    CLOCK_FREQ.load(Ordering::Relaxed)
+}
+
+/// read the timebase frequency from the device tree at `dtb_pa`,
+/// keep the board default [`config::CLOCK_FREQ`] if it can not be found
+///
+/// 只能在开启分页之前调用，见 [`crate::dtb`]
+pub fn init(dtb_pa: usize) {
+    match DeviceTree::from_pa(dtb_pa).and_then(|dt| dt.timebase_frequency()) {
+        Some(freq) if freq >= MICRO_PER_SEC => {
+            CLOCK_FREQ.store(freq, Ordering::Relaxed);
+            println!("[kernel] timebase frequency: {} Hz", freq);
+        }
+        _ => println!(
+            "[kernel] timebase frequency not found in device tree at {:#x}, assume {} Hz",
+            dtb_pa,
+            config::CLOCK_FREQ
+        ),
+    }
+}
+
 /// read the `mtime` register
 pub fn get_time() -> usize {
     register::time::read()
@@ -15,15 +47,66 @@ pub fn get_time() -> usize {
 
 /// 获取处理器自上电以来经过的时间，单位为 `ms`
 pub fn get_time_ms() -> usize {
-    register::time::read() / (config::CLOCK_FREQ / MSEC_PER_SEC)
+    register::time::read() / (clock_freq() / MSEC_PER_SEC)
 }
 
 /// 获取处理器自上电以来经过的时间，单位为 `us`
 pub fn get_time_us() -> usize {
-    register::time::read() / (config::CLOCK_FREQ / MICRO_PER_SEC)
+    register::time::read() / (clock_freq() / MICRO_PER_SEC)
 }
 
 /// set the next timer interrupt
 pub fn set_next_trigger() {
-    sbi::set_timer(get_time() + config::CLOCK_FREQ / TICKS_PER_SEC);
+    sbi::set_timer(get_time() + clock_freq() / config::TICKS_PER_SEC);
+}
+
+/// sleeping tasks as `(wakeup deadline in us, task id)`, the earliest deadline first
+static SLEEP_QUEUE: UPSafeCell<BinaryHeap<Reverse<(usize, usize)>>> =
+    unsafe { UPSafeCell::new(BinaryHeap::new()) };
+
+/// wake task `task_id` up at `deadline_us`
+pub fn add_sleeper(deadline_us: usize, task_id: usize) {
+    SLEEP_QUEUE
+        .exclusive_access()
+        .push(Reverse((deadline_us, task_id)));
+}
+
+/// take the tasks whose deadline has passed at `now_us` out of the sleep queue
+pub fn take_expired(now_us: usize) -> Vec<usize> {
+    let mut queue = SLEEP_QUEUE.exclusive_access();
+    let mut expired = Vec::new();
+    while let Some(&Reverse((deadline_us, task_id))) = queue.peek() {
+        if deadline_us > now_us {
+            break;
+        }
+        queue.pop();
+        expired.push(task_id);
+    }
+    expired
+}
+
+/// the earliest wakeup deadline in us, `None` if no task is sleeping
+pub fn next_wakeup_us() -> Option<usize> {
+    SLEEP_QUEUE
+        .exclusive_access()
+        .peek()
+        .map(|&Reverse((deadline_us, _))| deadline_us)
+}
+
+/// check the detected timebase frequency against the time conversions
+#[allow(unused)]
+pub fn timer_test() {
+    assert!(clock_freq() >= MICRO_PER_SEC);
+    let start = get_time();
+    let start_us = get_time_us();
+    // 忙等 10ms
+    while get_time() - start < clock_freq() / 100 {}
+    let elapsed_us = get_time_us() - start_us;
+    assert!(
+        (10_000..MICRO_PER_SEC).contains(&elapsed_us),
+        "10ms took {}us",
+        elapsed_us
+    );
+    assert!(get_time_ms() >= start_us / (MICRO_PER_SEC / MSEC_PER_SEC));
+    println!("timer_test passed!");
 }
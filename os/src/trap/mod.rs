@@ -23,7 +23,9 @@ use riscv::register::{
     sie, stval, stvec,
 };
 
-use crate::{config, syscall, task, timer};
+use crate::mm::MapPermission;
+use crate::task::{self, PerfEvent};
+use crate::{config, fs, logging, mm, syscall, timer};
 
 core::arch::global_asm!(include_str!("trap.S"));
 
@@ -61,28 +63,61 @@ pub fn enable_timer_interrupt() {
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler() -> ! {
     self::set_kernel_trap_entry();
+    #[cfg(feature = "switch_audit")]
+    task::check_no_pending_switch();
+    // __alltraps 切换到内核地址空间时刷新了快表
+    task::count_current(PerfEvent::TlbFlush);
     let cx: &mut TrapContext = task::current_trap_cx();
     let scause: Scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
+            task::count_current(PerfEvent::Syscall);
             cx.sepc += 4;
-            cx.x[10] = syscall::syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+            cx.x[10] = syscall::syscall(cx.x[17], args) as usize;
+        }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+            if task::handle_current_lazy_fault(stval, self::needed_permission(scause.cause())) =>
+        {
+            // 第一次访问懒分配的页面，分配页帧后重新执行出错的指令
+            task::count_current(PerfEvent::PageFault);
+            task::count_current(PerfEvent::FaultFixup);
         }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            println!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            task::exit_current_and_run_next();
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            task::count_current(PerfEvent::PageFault);
+            emergency_println!("[kernel] PageFault in {}, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", task::current_desc(), stval, cx.sepc);
+            task::dump_current_memory_set(stval);
+            task::exit_current_and_run_next(-2);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            task::exit_current_and_run_next();
+            emergency_println!(
+                "[kernel] IllegalInstruction in {}, kernel killed it.",
+                task::current_desc()
+            );
+            task::exit_current_and_run_next(-3);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             timer::set_next_trigger();
-            task::suspend_current_and_run_next();
+            task::wake_expired();
+            logging::report_repeats();
+            fs::sync_if_due();
+            if task::current_cpu_limit_exceeded() {
+                emergency_println!(
+                    "[kernel] CPU time limit exceeded in {}, kernel killed it.",
+                    task::current_desc()
+                );
+                task::exit_current_and_run_next(-1);
+            } else if task::tick_current() {
+                task::count_current(PerfEvent::Preemption);
+                task::suspend_current_and_run_next();
+            }
         }
         _ => {
             panic!(
@@ -92,15 +127,39 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    // 此时没有持有任何借用，是处理内核堆耗尽的安全点
+    if mm::take_heap_exhausted() {
+        emergency_println!(
+            "[kernel] kernel heap exhausted in {} ({} bytes of reserve in use), kernel killed it.",
+            task::current_desc(),
+            mm::reserve_in_use()
+        );
+        task::exit_current_and_run_next(-1);
+    }
     self::trap_return();
 }
 
+/// the permission the access causing page fault `cause` needs
+fn needed_permission(cause: Trap) -> MapPermission {
+    match cause {
+        Trap::Exception(Exception::StorePageFault) => MapPermission::W,
+        Trap::Exception(Exception::InstructionPageFault) => MapPermission::X,
+        _ => MapPermission::R,
+    }
+}
+
 #[no_mangle]
 /// set the new addr of __restore asm function in TRAMPOLINE page,
 /// set the reg a0 = trap_cx_ptr, reg a1 = phy addr of usr page table,
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
     self::set_user_trap_entry();
+    #[cfg(feature = "stack_canary")]
+    task::check_current_kernel_stack();
+    #[cfg(feature = "switch_audit")]
+    task::finish_current_switch();
+    // __restore 切换到应用地址空间时刷新快表
+    task::count_current(PerfEvent::TlbFlush);
     let trap_cx_ptr: usize = config::TRAP_CONTEXT;
     let user_satp = task::current_user_token();
     extern "C" {
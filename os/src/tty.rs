@@ -0,0 +1,144 @@
+//! Terminal line discipline over the SBI console, echoing on the user channel
+//!
+//! In cooked mode, input is echoed and kept in a line buffer that can be
+//! edited with backspace, and becomes readable only after Enter is pressed.
+//! In raw mode, every key becomes readable as soon as it arrives, without echo.
+
+use lazy_static::*;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::console::user_putchar;
+use crate::sbi::console_getchar;
+use crate::sync::UPSafeCell;
+use crate::task;
+
+const LF: u8 = b'\n';
+const CR: u8 = b'\r';
+const BS: u8 = 0x08;
+const DEL: u8 = 0x7f;
+
+/// line discipline mode of the terminal
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TtyMode {
+    /// 行缓冲、回显并支持退格编辑
+    Cooked = 0,
+    /// 逐字符读取，不回显
+    Raw = 1,
+}
+
+impl TtyMode {
+    pub fn from_usize(mode: usize) -> Option<Self> {
+        match mode {
+            0 => Some(TtyMode::Cooked),
+            1 => Some(TtyMode::Raw),
+            _ => None,
+        }
+    }
+}
+
+struct Tty {
+    mode: TtyMode,
+    /// 正在编辑、还没有按下回车的一行输入
+    line: Vec<u8>,
+    /// 已经可以被读取的输入
+    ready: VecDeque<u8>,
+}
+
+impl Tty {
+    /// 取出控制台上所有已经到达的字符
+    fn poll(&mut self) {
+        loop {
+            // SBI 在没有输入时返回 -1
+            let c = console_getchar();
+            if c == usize::MAX {
+                break;
+            }
+            self.input(c as u8);
+        }
+    }
+
+    fn input(&mut self, c: u8) {
+        if self.mode == TtyMode::Raw {
+            self.ready.push_back(c);
+            return;
+        }
+        match c {
+            CR | LF => {
+                user_putchar(LF);
+                self.line.push(LF);
+                self.ready.extend(self.line.drain(..));
+            }
+            BS | DEL => {
+                if self.line.pop().is_some() {
+                    for erase in [BS, b' ', BS] {
+                        user_putchar(erase);
+                    }
+                }
+            }
+            _ => {
+                user_putchar(c);
+                self.line.push(c);
+            }
+        }
+    }
+
+    fn set_mode(&mut self, mode: TtyMode) {
+        // 切换到 raw 模式时，还没有提交的一行立即变为可读
+        if mode == TtyMode::Raw {
+            self.ready.extend(self.line.drain(..));
+        }
+        self.mode = mode;
+    }
+}
+
+lazy_static! {
+    static ref TTY: UPSafeCell<Tty> = unsafe {
+        UPSafeCell::new(Tty {
+            mode: TtyMode::Cooked,
+            line: Vec::new(),
+            ready: VecDeque::new(),
+        })
+    };
+}
+
+/// Block by yielding to other tasks until some input is readable, return whether
+/// current task was suspended meanwhile.
+pub fn wait_readable() -> bool {
+    let mut suspended = false;
+    loop {
+        {
+            let mut tty = TTY.exclusive_access();
+            tty.poll();
+            if !tty.ready.is_empty() {
+                return suspended;
+            }
+        }
+        // 让出处理器之前必须释放对 TTY 的借用，其他任务也可能读取终端
+        task::suspend_current_and_run_next();
+        suspended = true;
+    }
+}
+
+/// Read the readable terminal input into `buf`, return the number of bytes read.
+///
+/// 不会阻塞，没有可读的输入时返回 0 ，需要等待时先调用 [`wait_readable`] 。`buf` 通常在用户
+/// 地址空间中，持有它时切换任务，它所在的页面就可能被换出
+pub fn read(buf: &mut [u8]) -> usize {
+    let mut tty = TTY.exclusive_access();
+    tty.poll();
+    let n = buf.len().min(tty.ready.len());
+    for (dst, src) in buf.iter_mut().zip(tty.ready.drain(..n)) {
+        *dst = src;
+    }
+    n
+}
+
+pub fn mode() -> TtyMode {
+    TTY.exclusive_access().mode
+}
+
+pub fn set_mode(mode: TtyMode) {
+    TTY.exclusive_access().set_mode(mode);
+}
@@ -0,0 +1,49 @@
+//! Polling driver for the transmitter of an NS16550A UART
+//!
+//! SBI 只负责第一个串口，其余串口由内核直接通过 MMIO 寄存器输出，不使用中断
+
+/// 发送保持寄存器
+const THR: usize = 0;
+/// 中断使能寄存器
+const IER: usize = 1;
+/// FIFO 控制寄存器
+const FCR: usize = 2;
+/// 线路控制寄存器
+const LCR: usize = 3;
+/// 线路状态寄存器
+const LSR: usize = 5;
+/// 发送保持寄存器为空
+const LSR_THRE: u8 = 1 << 5;
+
+/// an NS16550A UART whose registers are mapped at `base`
+#[derive(Copy, Clone, Debug)]
+pub struct Uart {
+    base: usize,
+}
+
+impl Uart {
+    pub const fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    fn read(&self, reg: usize) -> u8 {
+        unsafe { ((self.base + reg) as *const u8).read_volatile() }
+    }
+
+    fn write(&self, reg: usize, value: u8) {
+        unsafe { ((self.base + reg) as *mut u8).write_volatile(value) }
+    }
+
+    /// disable interrupts, use 8 data bits without parity and enable the FIFOs
+    pub fn init(&self) {
+        self.write(IER, 0);
+        self.write(LCR, 0b11);
+        self.write(FCR, 0b111);
+    }
+
+    /// wait until the transmitter can take `c`, then send it
+    pub fn putchar(&self, c: u8) {
+        while self.read(LSR) & LSR_THRE == 0 {}
+        self.write(THR, c);
+    }
+}
@@ -0,0 +1,43 @@
+//! Handlers run by [`crate::exit`] before the task exits
+//!
+//! 用户程序没有析构机制，可以在这里注册退出前需要做的事情，例如刷新缓冲区或打印测试结果
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 最多能注册的退出处理函数个数
+pub const MAX_EXIT_HOOKS: usize = 8;
+
+/// 已注册的处理函数的地址
+static EXIT_HOOKS: [AtomicUsize; MAX_EXIT_HOOKS] = [const { AtomicUsize::new(0) }; MAX_EXIT_HOOKS];
+/// 已注册、还没有运行的处理函数个数
+static EXIT_HOOK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// register `hook` to run on exit, return -1 if [`MAX_EXIT_HOOKS`] hooks are registered
+///
+/// 与 C 的 `atexit` 一样，后注册的处理函数先运行
+pub fn atexit(hook: fn()) -> isize {
+    let count = EXIT_HOOK_COUNT.load(Ordering::Relaxed);
+    if count == MAX_EXIT_HOOKS {
+        return -1;
+    }
+    EXIT_HOOKS[count].store(hook as usize, Ordering::Relaxed);
+    EXIT_HOOK_COUNT.store(count + 1, Ordering::Relaxed);
+    0
+}
+
+/// run the registered hooks, the last registered first
+///
+/// 每个处理函数运行前先被取出，因此处理函数中再调用 `exit` 也不会重复运行
+pub(crate) fn run_exit_hooks() {
+    loop {
+        let count = EXIT_HOOK_COUNT.load(Ordering::Relaxed);
+        if count == 0 {
+            break;
+        }
+        EXIT_HOOK_COUNT.store(count - 1, Ordering::Relaxed);
+        let hook = EXIT_HOOKS[count - 1].load(Ordering::Relaxed);
+        // 只有 `atexit` 写入，保存的都是 `fn()` 的地址
+        let hook: fn() = unsafe { core::mem::transmute(hook) };
+        hook();
+    }
+}
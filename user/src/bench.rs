@@ -0,0 +1,51 @@
+//! Self-timing benchmarks
+//!
+//! [`run`] 把一段计算重复若干轮并报告每轮的平均时间。比较内核改动（例如陷入路径的修改）前后
+//! 基准程序的结果可以发现性能退化；同时运行多个基准程序时，各自的时间也反映了调度的公平性
+
+use crate::console::Fixed;
+use crate::time::{Duration, Instant};
+
+/// the result of a benchmark
+#[derive(Copy, Clone, Debug)]
+pub struct BenchResult {
+    pub rounds: u32,
+    /// 所有轮次的总时间
+    pub total: Duration,
+}
+
+impl BenchResult {
+    /// the average time of a round in `us`
+    pub fn per_round_us(&self) -> u64 {
+        self.total.as_micros() as u64 / self.rounds as u64
+    }
+
+    /// Throughput of processing `bytes` bytes in each round, in `MiB/s` with 3 decimals.
+    pub fn mib_per_sec(&self, bytes: usize) -> Fixed {
+        let us = (self.total.as_micros() as u128).max(1);
+        let milli_mib = bytes as u128 * self.rounds as u128 * 1000 * 1_000_000 / us / (1 << 20);
+        Fixed(milli_mib as i64, 3)
+    }
+}
+
+/// run `f` `rounds` times and print the average time of a round in `ms`
+///
+/// 计算结果需要经过 [`core::hint::black_box`] ，否则可能被编译器优化掉
+pub fn run(name: &str, rounds: u32, mut f: impl FnMut()) -> BenchResult {
+    assert!(rounds > 0);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    let result = BenchResult {
+        rounds,
+        total: start.elapsed(),
+    };
+    println!(
+        "[bench] {}: {} rounds, {} ms/round",
+        name,
+        rounds,
+        Fixed(result.per_round_us() as i64, 3)
+    );
+    result
+}
@@ -3,13 +3,19 @@
 
 extern crate user_lib;
 
+use user_lib::task_info;
+use user_lib::time::{sleep, Duration, Instant};
+use user_lib::TaskInfo;
+
 #[no_mangle]
 fn main() -> i32 {
-    let current_timer = user_lib::get_time();
-    let wait_for = current_timer + 3000;
-    while user_lib::get_time() < wait_for {
-        user_lib::yield_();
-    }
+    let start = Instant::now();
+    sleep(Duration::from_millis(3000));
+    assert!(start.elapsed() >= Duration::from_millis(3000));
+    // 睡眠期间不会被调度，几乎不占用处理器
+    let mut info = TaskInfo::default();
+    task_info(&mut info);
+    assert!(info.time_ms < 1000, "slept busily for {}ms", info.time_ms);
     user_lib::println!("Test sleep OK!");
     0
 }
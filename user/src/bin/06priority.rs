@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{get_priority, set_priority};
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(get_priority(), 16);
+    assert_eq!(set_priority(1), -1);
+    assert_eq!(set_priority(-5), -1);
+    assert_eq!(get_priority(), 16);
+    assert_eq!(set_priority(4), 4);
+    assert_eq!(get_priority(), 4);
+    assert_eq!(set_priority(isize::MAX), 1024);
+    assert_eq!(get_priority(), 1024);
+    user_lib::println!("Test priority OK!");
+    0
+}
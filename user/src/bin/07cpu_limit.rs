@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getrlimit, setrlimit, RLimit, RLIMIT_CPU, RLIM_INFINITY};
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_CPU, &mut limit), 0);
+    assert_eq!(limit.max, RLIM_INFINITY);
+    assert_eq!(setrlimit(RLIMIT_CPU, &RLimit { cur: 2, max: 1 }), -1);
+    assert_eq!(setrlimit(RLIMIT_CPU, &RLimit { cur: 1, max: 2 }), 0);
+    assert_eq!(
+        setrlimit(
+            RLIMIT_CPU,
+            &RLimit {
+                cur: 1,
+                max: RLIM_INFINITY
+            }
+        ),
+        -1
+    );
+    println!("Spin until the kernel kills me after 2s of CPU time...");
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{sched_getscheduler, sched_setscheduler, SCHED_IDLE, SCHED_NORMAL};
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(sched_getscheduler(0), SCHED_NORMAL as isize);
+    assert_eq!(sched_setscheduler(0, 42), -1);
+    assert_eq!(sched_setscheduler(1, SCHED_IDLE), -1);
+    assert_eq!(sched_setscheduler(0, SCHED_IDLE), 0);
+    assert_eq!(sched_getscheduler(0), SCHED_IDLE as isize);
+    // 只有在其他应用都退出之后才会运行到这里
+    println!("Test sched_idle OK!");
+    0
+}
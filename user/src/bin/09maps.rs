@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::get_maps;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0u8; 1024];
+    let len = get_maps(&mut buf) as usize;
+    assert!(len <= buf.len(), "maps truncated");
+    let maps = core::str::from_utf8(&buf[..len]).unwrap();
+    print!("{}", maps);
+    // 栈上的缓冲区必须位于 stack 逻辑段中
+    let buf_addr = buf.as_ptr() as usize;
+    let in_stack = maps.lines().any(|line| {
+        let mut fields = line.split(' ');
+        let range = fields.next().unwrap();
+        let (start, end) = range.split_once('-').unwrap();
+        let start = usize::from_str_radix(start, 16).unwrap();
+        let end = usize::from_str_radix(end, 16).unwrap();
+        fields.nth(1) == Some("stack") && (start..end).contains(&buf_addr)
+    });
+    assert!(in_stack);
+    println!("Test maps OK!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+// 递归需要约 100KiB 的用户栈，远超默认大小
+user_lib::stack_size!(128 * 1024, 0);
+
+/// 递归的深度
+const DEPTH: usize = 512;
+
+/// 每层递归在栈上占用一个 128 字节的数组
+fn recurse(depth: usize) -> usize {
+    let frame = black_box([depth as u8; 128]);
+    if depth == 0 {
+        return frame[0] as usize;
+    }
+    recurse(depth - 1) + frame[127] as usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let expected: usize = (0..=DEPTH).map(|depth| depth as u8 as usize).sum();
+    assert_eq!(recurse(black_box(DEPTH)), expected);
+    println!("Test stack_size OK!");
+    0
+}
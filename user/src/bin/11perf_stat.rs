@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_priority, perf, yield_};
+
+/// 每个测试中系统调用的次数
+const ROUNDS: u64 = 100;
+
+#[no_mangle]
+fn main() -> i32 {
+    let events = perf::stat("get_priority x100", || {
+        for _ in 0..ROUNDS {
+            get_priority();
+        }
+    });
+    assert_eq!(events.syscalls, ROUNDS);
+    assert_eq!(events.page_faults, 0);
+    // 每次系统调用进出内核各刷新一次快表，期间的时钟中断会带来更多
+    assert!(events.tlb_flushes >= 2 * ROUNDS);
+
+    let events = perf::stat("yield x100", || {
+        for _ in 0..ROUNDS {
+            yield_();
+        }
+    });
+    assert_eq!(events.syscalls, ROUNDS);
+    println!("Test perf_stat OK!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getcpu, hart_count, yield_};
+
+#[no_mangle]
+fn main() -> i32 {
+    let harts = hart_count();
+    assert!(harts >= 1);
+    let hart = getcpu();
+    println!("running on hart {} of {}", hart, harts);
+    assert!(hart < harts);
+    // 内核只在一个核上运行，让出处理器之后依然在同一个核上
+    yield_();
+    assert_eq!(getcpu(), hart);
+    println!("Test getcpu OK!");
+    0
+}
@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fault_inject, spawn, yield_to, FAULT_SITE_FRAME};
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(spawn("no_such_app\0"), -1);
+    // 内核启用 `fault_injection` 时，让建立地址空间的中途物理页帧耗尽，spawn 失败而内核继续运行
+    if fault_inject(FAULT_SITE_FRAME, 0, 4) >= 0 {
+        assert_eq!(spawn("00power_3\0"), -1);
+        fault_inject(FAULT_SITE_FRAME, 0, usize::MAX);
+    }
+    let pid = spawn("00power_3\0");
+    assert!(pid > 0);
+    println!("spawned 00power_3 as pid {}", pid);
+    // 新任务是 `Ready` 的，可以直接让它运行
+    assert_eq!(yield_to(pid as usize), 0);
+    println!("Test spawn OK!");
+    0
+}
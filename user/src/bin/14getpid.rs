@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getpid, getppid, spawn};
+
+#[no_mangle]
+fn main() -> i32 {
+    let pid = getpid();
+    // pid 0 不分配给任何任务，启动时创建的应用没有父任务
+    assert!(pid > 0);
+    assert_eq!(getppid(), 0);
+    let child = spawn("00power_3\0");
+    assert!(child > 0 && child != pid);
+    assert_eq!(getpid(), pid);
+    println!("pid {} spawned pid {}", pid, child);
+    println!("Test getpid OK!");
+    0
+}
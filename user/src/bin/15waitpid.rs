@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getpid, spawn, wait, waitpid};
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut exit_code: i32 = -1;
+    // 还没有子任务
+    assert_eq!(wait(&mut exit_code), -1);
+    let child = spawn("00power_3\0");
+    assert!(child > 0);
+    assert_eq!(waitpid(getpid(), &mut exit_code), -1);
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    println!("reaped pid {} with exit code {}", child, exit_code);
+    // 被访存异常杀死的子任务退出码为 -2
+    let child = spawn("04load_fault\0");
+    assert!(child > 0);
+    assert_eq!(wait(&mut exit_code), child);
+    assert_eq!(exit_code, -2);
+    // 子任务已被回收
+    assert_eq!(waitpid(child, &mut exit_code), -1);
+    println!("Test waitpid OK!");
+    0
+}
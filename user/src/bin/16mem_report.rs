@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_report, spawn, wait, MemReport};
+
+/// 同时存在的子任务个数
+const CHILDREN: usize = 4;
+
+/// 读取碎片情况并检查各项统计之间的关系；其他任务同时在分配和释放，不比较两次读取的结果
+fn report() -> MemReport {
+    let mut report = MemReport::default();
+    assert_eq!(mem_report(&mut report), 0);
+    assert!(report.free_frames > 0);
+    assert!(report.largest_free_run > 0 && report.largest_free_run <= report.free_frames);
+    assert!(report.recycled_frames <= report.free_frames);
+    assert!(report.heap_free_blocks > 0);
+    assert!(report.heap_largest_block.is_power_of_two());
+    assert!(report.heap_largest_block <= report.heap_free_bytes);
+    // 最小的块也有一个指针大小
+    assert!(report.heap_free_blocks * 8 <= report.heap_free_bytes);
+    report
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    report();
+    // 子任务的地址空间和内核栈占用页帧和内核堆，回收后留下空洞
+    for _ in 0..CHILDREN {
+        assert!(spawn("00power_3\0") > 0);
+    }
+    report();
+    let mut exit_code: i32 = 0;
+    for _ in 0..CHILDREN {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    report();
+    println!("Test mem_report OK!");
+    0
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{get_time_slice, set_time_slice};
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(get_time_slice(), 1);
+    assert_eq!(set_time_slice(0), -1);
+    assert_eq!(get_time_slice(), 1);
+    assert_eq!(set_time_slice(5), 5);
+    assert_eq!(get_time_slice(), 5);
+    assert_eq!(set_time_slice(usize::MAX), 100);
+    assert_eq!(get_time_slice(), 100);
+    user_lib::println!("Test time_slice OK!");
+    0
+}
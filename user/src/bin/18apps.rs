@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{app_exists, get_app_names};
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0u8; 1024];
+    let len = get_app_names(&mut buf) as usize;
+    assert!(len <= buf.len(), "app names truncated");
+    let names = core::str::from_utf8(&buf[..len]).unwrap();
+    print!("{}", names);
+    assert!(names.lines().any(|name| name == "18apps"));
+    assert!(app_exists("00power_3\0"));
+    assert!(!app_exists("no_such_app\0"));
+    // 按前缀补全应用名
+    let mut completions = names.lines().filter(|name| name.starts_with("00"));
+    assert_eq!(completions.next(), Some("00power_3"));
+    assert_eq!(completions.next(), None);
+    println!("Test apps OK!");
+    0
+}
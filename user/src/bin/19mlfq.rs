@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, task_info, yield_, TaskInfo, SCHEDULER_MLFQ};
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut info = TaskInfo::default();
+    task_info(&mut info);
+    if info.scheduler != SCHEDULER_MLFQ {
+        println!("stride scheduler in use, skip mlfq test");
+        return 0;
+    }
+    assert_eq!(info.mlfq_level, 0);
+    // 一直占用处理器，用完时间片后会降级
+    let start = get_time();
+    while get_time() - start < 100 {}
+    task_info(&mut info);
+    let busy_level = info.mlfq_level;
+    println!("level {} after running busily", busy_level);
+    assert!(busy_level > 0);
+    // 每次都提前让出处理器，逐级升回最高级
+    for _ in 0..busy_level {
+        yield_();
+    }
+    task_info(&mut info);
+    println!("level {} after yielding", info.mlfq_level);
+    assert_eq!(info.mlfq_level, 0);
+    println!("Test mlfq OK!");
+    0
+}
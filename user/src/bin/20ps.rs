@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{ps, spawn, waitpid};
+
+#[no_mangle]
+fn main() -> i32 {
+    assert!(ps() >= 1);
+    let child = spawn("00power_3\0");
+    assert!(child > 0);
+    // 其他任务可能同时退出，只能确定当前任务和子任务都在列表中
+    assert!(ps() >= 2);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    println!("Test ps OK!");
+    0
+}
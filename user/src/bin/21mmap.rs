@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, PROT_EXEC, PROT_READ, PROT_WRITE};
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096 * 2;
+
+#[no_mangle]
+fn main() -> i32 {
+    // 起始地址未对齐、长度为 0 、权限不合法
+    assert_eq!(mmap(START + 1, LEN, PROT_READ), -1);
+    assert_eq!(mmap(START, 0, PROT_READ), -1);
+    assert_eq!(mmap(START, LEN, 0), -1);
+    assert_eq!(mmap(START, LEN, 1 << 3), -1);
+    assert_eq!(mmap(START, LEN, PROT_WRITE), -1);
+
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    let words = unsafe { core::slice::from_raw_parts_mut(START as *mut usize, LEN / 8) };
+    assert!(words.iter().all(|&w| w == 0));
+    for (i, w) in words.iter_mut().enumerate() {
+        *w = i;
+    }
+    assert!(words.iter().enumerate().all(|(i, &w)| w == i));
+
+    // 与已有映射重叠
+    assert_eq!(mmap(START + 4096, LEN, PROT_READ), -1);
+    assert_eq!(mmap(START - 4096, LEN, PROT_READ | PROT_EXEC), -1);
+    // 只取消一部分或取消未映射的范围
+    assert_eq!(munmap(START, 4096), -1);
+    assert_eq!(munmap(START + LEN, LEN), -1);
+
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(munmap(START, LEN), -1);
+    // 取消之后可以重新映射，内容已清零
+    assert_eq!(mmap(START, 4096, PROT_READ), 0);
+    assert_eq!(unsafe { (START as *const usize).read_volatile() }, 0);
+    assert_eq!(munmap(START, 4096), 0);
+    println!("Test mmap OK!");
+    0
+}
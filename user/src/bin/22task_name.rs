@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_task_name, ps, set_task_name, TASK_NAME_LEN};
+
+/// 当前任务名，不含结尾的 `\0`
+fn task_name(buf: &mut [u8; TASK_NAME_LEN + 1]) -> &str {
+    assert_eq!(get_task_name(buf), 0);
+    let len = buf.iter().position(|&c| c == 0).unwrap();
+    core::str::from_utf8(&buf[..len]).unwrap()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0xffu8; TASK_NAME_LEN + 1];
+    assert_eq!(task_name(&mut buf), "22task_name");
+
+    assert_eq!(set_task_name("worker-1\0"), 0);
+    assert_eq!(task_name(&mut buf), "worker-1");
+    // 过长的名字被截断，换行等不可打印的字符被替换
+    assert_eq!(set_task_name("a-very-long-task-name\0"), 0);
+    assert_eq!(task_name(&mut buf), "a-very-long-tas");
+    assert_eq!(set_task_name("bad\nname\x1b[0m\0"), 0);
+    assert_eq!(task_name(&mut buf), "bad?name?[0m");
+    assert_eq!(set_task_name("\0"), 0);
+    assert_eq!(task_name(&mut buf), "");
+
+    assert_eq!(set_task_name("renamed\0"), 0);
+    // 在内核控制台的任务列表中应当显示新名字
+    assert!(ps() >= 1);
+    println!("Test task name OK!");
+    0
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getpid, raw_syscall, ENOSYS};
+
+#[no_mangle]
+fn main() -> i32 {
+    // 内核只在第一次调用某个不支持的系统调用时输出警告
+    for id in [9999, 9999, 1000, usize::MAX] {
+        assert_eq!(raw_syscall(id, [1, 2, 3]), -ENOSYS);
+    }
+    // 之后其他系统调用仍然正常
+    assert!(getpid() > 0);
+    println!("Test ENOSYS OK!");
+    0
+}
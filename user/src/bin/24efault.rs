@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::abi::{
+    SYSCALL_GETRLIMIT, SYSCALL_GET_TIME, SYSCALL_READ, SYSCALL_TASK_INFO, SYSCALL_WRITE,
+};
+use user_lib::{get_time, raw_syscall, TaskInfo, EFAULT};
+
+/// 应用地址空间中没有映射的地址
+const UNMAPPED: usize = 0x2000_0000;
+
+#[no_mangle]
+fn main() -> i32 {
+    // 空指针和未映射的缓冲区
+    assert_eq!(raw_syscall(SYSCALL_WRITE, [1, 0, 4]), -EFAULT);
+    assert_eq!(raw_syscall(SYSCALL_READ, [0, UNMAPPED, 16]), -EFAULT);
+    assert_eq!(raw_syscall(SYSCALL_TASK_INFO, [0, 0, 0]), -EFAULT);
+    // 缓冲区的开头合法，但延伸到了未映射的区域
+    let local = 0u8;
+    let ptr = &local as *const u8 as usize;
+    assert_eq!(
+        raw_syscall(SYSCALL_WRITE, [1, ptr, usize::MAX - ptr]),
+        -EFAULT
+    );
+    // 内核不能写入只读的代码段
+    let text = main as usize;
+    assert_eq!(raw_syscall(SYSCALL_GETRLIMIT, [0, text, 0]), -EFAULT);
+    assert_eq!(raw_syscall(SYSCALL_TASK_INFO, [text, 0, 0]), -EFAULT);
+
+    // 允许为空的指针和合法的缓冲区不受影响
+    assert!(raw_syscall(SYSCALL_GET_TIME, [0, 0, 0]) >= 0);
+    let mut info = TaskInfo::default();
+    assert_eq!(
+        raw_syscall(
+            SYSCALL_TASK_INFO,
+            [&mut info as *mut TaskInfo as usize, 0, 0]
+        ),
+        0
+    );
+    assert!(get_time() >= 0);
+    println!("Test EFAULT OK!");
+    0
+}
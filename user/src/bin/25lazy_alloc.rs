@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time_of_day, mmap, munmap, TimeVal, PROT_READ, PROT_WRITE};
+
+const START: usize = 0x1000_0000;
+/// 远大于物理内存，只有按需分配页帧时才能映射
+const LEN: usize = 64 * 1024 * 1024;
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    // 只有访问过的页面才会分配页帧
+    for offset in [0, PAGE_SIZE, LEN / 2, LEN - 8] {
+        let ptr = (START + offset) as *mut usize;
+        unsafe {
+            assert_eq!(ptr.read_volatile(), 0);
+            ptr.write_volatile(offset);
+            assert_eq!(ptr.read_volatile(), offset);
+        }
+    }
+    // 内核也可以写入还没有访问过的页面
+    let ts = unsafe { &mut *((START + LEN / 4) as *mut TimeVal) };
+    assert!(get_time_of_day(ts) >= 0);
+    assert!(ts.sec > 0 || ts.usec > 0);
+    assert_eq!(munmap(START, LEN), 0);
+    println!("Test lazy allocation OK!");
+    0
+}
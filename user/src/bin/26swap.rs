@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, get_time_of_day, mmap, munmap, open, read, TimeVal, EFAULT, O_RDONLY, PROT_READ,
+    PROT_WRITE,
+};
+
+const START: usize = 0x2000_0000;
+/// 大于 `make run FEATURES=swap` 时 QEMU 的内存，页面只有换出后才能全部写入
+const LEN: usize = 48 * 1024 * 1024;
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    for offset in (0..LEN).step_by(PAGE_SIZE) {
+        let ptr = (START + offset) as *mut usize;
+        unsafe {
+            ptr.write_volatile(offset);
+            ptr.add(PAGE_SIZE / 8 - 1).write_volatile(!offset);
+        }
+    }
+    // 先写入的页面已经被换出，读取时换入
+    for offset in (0..LEN).step_by(PAGE_SIZE) {
+        let ptr = (START + offset) as *const usize;
+        unsafe {
+            assert_eq!(ptr.read_volatile(), offset);
+            assert_eq!(ptr.add(PAGE_SIZE / 8 - 1).read_volatile(), !offset);
+        }
+    }
+    // 内核写入换出的页面前也会先换入
+    let ts = unsafe { &mut *((START + 8) as *mut TimeVal) };
+    assert!(get_time_of_day(ts) >= 0);
+    assert!(ts.sec > 0 || ts.usec > 0);
+    let last = unsafe {
+        (START as *const usize)
+            .add(PAGE_SIZE / 8 - 1)
+            .read_volatile()
+    };
+    assert_eq!(last, !0);
+    // 缓冲区比内存还大时，它的页面不能同时留在内存中，系统调用失败而不是让内核 panic
+    let fd = open("26swap\0", O_RDONLY);
+    assert!(fd > 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+    let len = read(fd as usize, buf);
+    assert!(len == -EFAULT || len > 0);
+    close(fd as usize);
+    assert_eq!(munmap(START, LEN), 0);
+    println!("Test swap OK!");
+    0
+}
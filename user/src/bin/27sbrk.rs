@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sbrk;
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 8;
+
+#[no_mangle]
+fn main() -> i32 {
+    let bottom = sbrk(0);
+    assert!(bottom > 0);
+    assert_eq!(sbrk((PAGES * PAGE_SIZE) as isize), bottom);
+    assert_eq!(sbrk(0) as usize, bottom as usize + PAGES * PAGE_SIZE);
+    // 新增的页面在第一次访问时才分配，内容全为零
+    for page in 0..PAGES {
+        let ptr = (bottom as usize + page * PAGE_SIZE) as *mut usize;
+        unsafe {
+            assert_eq!(ptr.read_volatile(), 0);
+            ptr.write_volatile(page);
+        }
+    }
+    for page in 0..PAGES {
+        let ptr = (bottom as usize + page * PAGE_SIZE) as *const usize;
+        assert_eq!(unsafe { ptr.read_volatile() }, page);
+    }
+    // 收缩后再增长，被释放的页面重新分配时清零
+    let top = bottom as usize + PAGES * PAGE_SIZE;
+    assert_eq!(sbrk(-(PAGE_SIZE as isize)) as usize, top);
+    assert_eq!(sbrk(PAGE_SIZE as isize) as usize, top - PAGE_SIZE);
+    let last = (top - PAGE_SIZE) as *const usize;
+    assert_eq!(unsafe { last.read_volatile() }, 0);
+    // 堆不能小于 0 字节
+    assert_eq!(sbrk(-(((PAGES + 1) * PAGE_SIZE) as isize)), -1);
+    assert_eq!(sbrk(-((PAGES * PAGE_SIZE) as isize)) as usize, top);
+    assert_eq!(sbrk(0), bottom);
+    println!("Test sbrk OK!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::time::{Duration, Instant};
+use user_lib::{get_priority, sbrk, task_info, TaskInfo};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 4;
+
+fn info() -> TaskInfo {
+    let mut info = TaskInfo::default();
+    task_info(&mut info);
+    info
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let before = info();
+    // 读取 `TaskInfo` 本身也是一次系统调用
+    for _ in 0..10 {
+        get_priority();
+    }
+    assert_eq!(info().syscalls - before.syscalls, 11);
+
+    // 第一次访问懒分配的页面时，缺页异常由内核修复
+    let before = info();
+    let heap = sbrk((PAGES * PAGE_SIZE) as isize) as usize;
+    for page in 0..PAGES {
+        unsafe { ((heap + page * PAGE_SIZE) as *mut u8).write_volatile(1) };
+    }
+    let after = info();
+    assert!(after.fault_fixups - before.fault_fixups >= PAGES as u64);
+    sbrk(-((PAGES * PAGE_SIZE) as isize));
+
+    // 忙等若干个时间片，时钟中断抢占的次数与系统调用分开统计。启用交换时栈可能被换出后再换入，
+    // 因此不检查这期间的缺页次数
+    let before = info();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {}
+    let after = info();
+    assert!(after.preemptions > before.preemptions);
+    println!(
+        "syscalls = {}, preemptions = {}, fault fixups = {}",
+        after.syscalls, after.preemptions, after.fault_fixups
+    );
+    println!("Test task events OK!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{read, write};
+
+#[no_mangle]
+fn main() -> i32 {
+    let msg = b"written to stderr\n";
+    assert_eq!(write(2, msg), msg.len() as isize);
+    // 标准输出不可读，标准输入不可写，没有打开的文件描述符不能读写
+    let mut buf = [0u8; 8];
+    assert_eq!(read(1, &mut buf), -1);
+    assert_eq!(write(0, msg), -1);
+    assert_eq!(write(3, msg), -1);
+    assert_eq!(read(100, &mut buf), -1);
+    println!("Test fd table OK!");
+    0
+}
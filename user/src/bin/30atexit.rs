@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user_lib::{atexit, MAX_EXIT_HOOKS};
+
+/// 已经运行的处理函数个数
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+fn count() {
+    RAN.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 最先注册，因此最后运行
+fn summary() {
+    assert_eq!(RAN.load(Ordering::Relaxed), MAX_EXIT_HOOKS - 1);
+    println!("Test atexit OK!");
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(atexit(summary), 0);
+    for _ in 1..MAX_EXIT_HOOKS {
+        assert_eq!(atexit(count), 0);
+    }
+    assert_eq!(atexit(count), -1);
+    assert_eq!(RAN.load(Ordering::Relaxed), 0);
+    0
+}
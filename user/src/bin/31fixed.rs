@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::fmt::Write;
+
+use user_lib::console::{print_fixed, Fixed};
+
+/// 把格式化的结果写入定长缓冲区，便于比较
+struct Buffer {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.bytes
+            .get_mut(self.len..end)
+            .ok_or(core::fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+fn format(value: i64, digits: u32) -> Buffer {
+    let mut buffer = Buffer {
+        bytes: [0; 32],
+        len: 0,
+    };
+    write!(buffer, "{}", Fixed(value, digits)).unwrap();
+    buffer
+}
+
+fn check(value: i64, digits: u32, expected: &str) {
+    let buffer = format(value, digits);
+    assert_eq!(&buffer.bytes[..buffer.len], expected.as_bytes());
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    check(12345, 3, "12.345");
+    check(1500, 3, "1.500");
+    check(7, 3, "0.007");
+    check(-7, 3, "-0.007");
+    check(-12345, 2, "-123.45");
+    check(42, 0, "42");
+    check(i64::MIN, 3, "-9223372036854775.808");
+    print!("1.5 ms = ");
+    print_fixed(1500, 3);
+    println!(" ms");
+    println!("Test fixed-point formatting OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+use user_lib::bench;
+
+user_lib::stack_size!(128 * 1024, 0);
+
+const N: usize = 64;
+const ROUNDS: u32 = 10;
+
+type Matrix = [[u32; N]; N];
+
+fn matmul(a: &Matrix, b: &Matrix, c: &mut Matrix) {
+    for i in 0..N {
+        for j in 0..N {
+            let mut sum = 0u32;
+            for k in 0..N {
+                sum = sum.wrapping_add(a[i][k].wrapping_mul(b[k][j]));
+            }
+            c[i][j] = sum;
+        }
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut a: Matrix = [[0; N]; N];
+    let mut b: Matrix = [[0; N]; N];
+    let mut c: Matrix = [[0; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            a[i][j] = (i * N + j) as u32;
+            b[i][j] = if i == j { 1 } else { 0 };
+        }
+    }
+    // 与单位矩阵相乘，结果等于原矩阵
+    bench::run("matmul 64x64", ROUNDS, || {
+        matmul(black_box(&a), black_box(&b), &mut c);
+        black_box(&c);
+    });
+    assert!(c == a);
+    println!("Test bench matmul OK!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+use user_lib::bench;
+
+user_lib::stack_size!(128 * 1024, 0);
+
+const LIMIT: usize = 64 * 1024;
+const ROUNDS: u32 = 10;
+/// 小于 65536 的素数个数
+const PRIMES_BELOW_LIMIT: usize = 6542;
+
+/// count the primes below `LIMIT` with the sieve of Eratosthenes
+fn sieve(composite: &mut [bool; LIMIT]) -> usize {
+    composite.fill(false);
+    let mut count = 0;
+    for n in 2..LIMIT {
+        if composite[n] {
+            continue;
+        }
+        count += 1;
+        for multiple in (n * n..LIMIT).step_by(n) {
+            composite[multiple] = true;
+        }
+    }
+    count
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut composite = [false; LIMIT];
+    let mut count = 0;
+    bench::run("sieve 65536", ROUNDS, || {
+        count = sieve(black_box(&mut composite));
+    });
+    assert_eq!(count, PRIMES_BELOW_LIMIT);
+    println!("Test bench sieve OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+use user_lib::bench;
+
+user_lib::stack_size!(128 * 1024, 0);
+
+const SIZE: usize = 32 * 1024;
+const ROUNDS: u32 = 200;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut src = [0u8; SIZE];
+    let mut dst = [0u8; SIZE];
+    for (i, byte) in src.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let result = bench::run("memcpy 32 KiB", ROUNDS, || {
+        dst.copy_from_slice(black_box(&src));
+        black_box(&mut dst);
+    });
+    println!("memcpy bandwidth: {} MiB/s", result.mib_per_sec(SIZE));
+    assert!(dst == src);
+    println!("Test bench memcpy OK!");
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::abi::SYSCALL_WRITE;
+use user_lib::{dmesg, raw_syscall, EFAULT};
+
+const REPEATS: usize = 1000;
+/// 内核每次输出的警告中都包含的文本
+const WARNING: &str = "35warn_flood (pid";
+const BAD_BUFFER: &str = "passed inaccessible buffer [0x0, 0x4) to syscall 64";
+const SUMMARY: &str = "last message repeated";
+
+/// 每次都传入同样的非法缓冲区，使内核反复输出同一条警告。
+/// 内核只保留第一条警告，其余的合并为 "last message repeated N times" （时钟中断时也会报告一次，
+/// 因此可能有几条），从内存中的日志检查这一点
+#[no_mangle]
+fn main() -> i32 {
+    for _ in 0..REPEATS {
+        assert_eq!(raw_syscall(SYSCALL_WRITE, [1, 0, 4]), -EFAULT);
+    }
+    // 不合并的话，这么长的日志足以装下 20 条左右的警告
+    let mut log = [0u8; 2048];
+    let len = dmesg(&mut log) as usize;
+    let lines = log[..len]
+        .split(|&c| c == b'\n')
+        .filter_map(|line| core::str::from_utf8(line).ok());
+    let (mut warnings, mut summaries) = (0, 0);
+    for line in lines {
+        if line.contains(WARNING) && line.contains(BAD_BUFFER) {
+            warnings += 1;
+        } else if line.contains(SUMMARY) {
+            summaries += 1;
+        }
+    }
+    // 其他任务的警告插入进来时，同一条警告会被分成几段，每段各保留一条
+    assert!((1..=4).contains(&warnings), "{} warnings kept", warnings);
+    assert!(summaries >= 1);
+    println!("Test warn flood OK!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, make_tmp_dir, open, read, write, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY,
+};
+
+const FILE: &str = "/tmp/36file_rw.txt\0";
+
+#[no_mangle]
+fn main() -> i32 {
+    make_tmp_dir();
+    // 不存在的文件只有指定 `O_CREAT` 时才会被创建
+    assert_eq!(open("36no_such_file\0", O_RDONLY), -1);
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd > 2);
+    let fd = fd as usize;
+    let msg = b"Hello, easy-fs!";
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd, &mut buf), -1);
+    assert_eq!(close(fd), 0);
+    assert_eq!(close(fd), -1);
+
+    // 读到文件末尾后返回 0
+    let fd = open(FILE, O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], msg);
+    assert_eq!(read(fd, &mut buf), 0);
+    assert_eq!(write(fd, msg), -1);
+
+    // 关闭的文件描述符被重新使用
+    assert_eq!(close(fd), 0);
+    assert_eq!(open(FILE, O_RDWR) as usize, fd);
+    assert_eq!(write(fd, b"Hi"), 2);
+    assert_eq!(close(fd), 0);
+    let fd = open(FILE, O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], b"Hillo, easy-fs!");
+    close(fd);
+
+    // `O_TRUNC` 清空文件
+    let fd = open(FILE, O_WRONLY | O_TRUNC) as usize;
+    close(fd);
+    let fd = open(FILE, O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), 0);
+    close(fd);
+    println!("Test file read/write OK!");
+    0
+}
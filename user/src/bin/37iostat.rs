@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fd_stat, iostat, make_tmp_dir, open, read, write, IoStats, O_CREAT, O_RDWR, O_TRUNC,
+};
+
+const FILE: &str = "/tmp/37iostat.txt\0";
+
+fn stats(fd: usize) -> IoStats {
+    let mut stats = IoStats::default();
+    assert_eq!(fd_stat(fd, &mut stats), 0);
+    stats
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    make_tmp_dir();
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_RDWR) as usize;
+    let block = [b'x'; 100];
+    for _ in 0..3 {
+        assert_eq!(write(fd, &block), 100);
+    }
+    close(fd);
+
+    let fd = open(FILE, O_RDWR) as usize;
+    let mut buf = [0u8; 128];
+    while read(fd, &mut buf) > 0 {}
+    let stats = stats(fd);
+    // 新打开的文件描述符重新开始统计，最后一次读到了文件末尾
+    assert_eq!(stats.writes, 0);
+    assert_eq!(stats.reads, 4);
+    assert_eq!(stats.read_bytes, 300);
+
+    // 标准输出的统计包括之前的输出
+    let before = self::stats(1);
+    println!("I/O statistics of all tasks:");
+    let after = self::stats(1);
+    assert_eq!(after.writes, before.writes + 1);
+    assert!(iostat() >= 4);
+    close(fd);
+
+    let mut unused = IoStats::default();
+    assert_eq!(fd_stat(fd, &mut unused), -1);
+    println!("Test iostat OK!");
+    0
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fs_rollback, make_tmp_dir, open, write, O_CREAT, O_RDONLY, O_WRONLY};
+
+const FILE: &str = "/tmp/38fs_rollback.txt\0";
+
+#[no_mangle]
+fn main() -> i32 {
+    make_tmp_dir();
+    let fd = open(FILE, O_CREAT | O_WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"scratch"), 7);
+    close(fd as usize);
+    if fs_rollback() < 0 {
+        println!("fs_overlay is not enabled or other tasks have files open, skipped");
+        return 0;
+    }
+    // 回滚之后新建的文件不再存在，启动时就有的应用不受影响
+    assert_eq!(open(FILE, O_RDONLY), -1);
+    let fd = open("38fs_rollback\0", O_RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    println!("Test fs rollback OK!");
+    0
+}
@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, make_tmp_dir, open, write, Stat, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY, S_IFCHR,
+    S_IFREG,
+};
+
+const FILE: &str = "/tmp/39fstat.txt\0";
+const FILE_TYPE_MASK: u32 = 0o170000;
+
+#[no_mangle]
+fn main() -> i32 {
+    make_tmp_dir();
+    // 标准输出是终端，不在文件系统中
+    let mut st = Stat::default();
+    assert_eq!(fstat(1, &mut st), 0);
+    assert_eq!(st.mode & FILE_TYPE_MASK, S_IFCHR);
+
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_WRONLY) as usize;
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.mode & FILE_TYPE_MASK, S_IFREG);
+    assert_eq!(st.nlink, 1);
+    assert_eq!(st.size, 0);
+    let ino = st.ino;
+    assert_ne!(ino, 0);
+
+    // 大小随写入增长，同一个文件再次打开时索引节点编号不变
+    let data = [0x5au8; 1000];
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.size, data.len() as u64);
+    close(fd);
+    let fd = open(FILE, O_RDONLY) as usize;
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(st.size, data.len() as u64);
+    close(fd);
+    assert_eq!(fstat(fd, &mut st), -1);
+    println!("Test fstat OK!");
+    0
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, getdents, make_tmp_dir, mkdir, open, read, write, Dirent, Stat, O_CREAT,
+    O_RDONLY, O_TRUNC, O_WRONLY, S_IFDIR, S_IFREG,
+};
+
+const DIR: &str = "/tmp/40dir\0";
+const SUB: &str = "/tmp/40dir/sub/\0";
+const FILE: &str = "/tmp/40dir/sub/40mkdir.txt\0";
+
+#[no_mangle]
+fn main() -> i32 {
+    // 再次运行时目录已经存在，只检查第二次创建失败
+    make_tmp_dir();
+    mkdir(DIR);
+    mkdir(SUB);
+    assert_eq!(mkdir(DIR), -1);
+    assert_eq!(mkdir("/tmp/40no_such_dir/sub\0"), -1);
+
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd > 2);
+    assert_eq!(write(fd as usize, b"in a directory"), 14);
+    close(fd as usize);
+    // 普通文件之下不能再有文件
+    assert_eq!(mkdir("/tmp/40dir/sub/40mkdir.txt/x\0"), -1);
+    assert_eq!(
+        open("/tmp/40dir/sub/40mkdir.txt/x\0", O_CREAT | O_WRONLY),
+        -1
+    );
+
+    // 目录只能只读打开，不能读写，链接数包括子目录的 `..`
+    assert_eq!(open(DIR, O_WRONLY), -1);
+    let fd = open(DIR, O_RDONLY) as usize;
+    let mut st = Stat::default();
+    assert_eq!(fstat(fd, &mut st), 0);
+    // 默认的文件创建掩码为 0o022
+    assert_eq!(st.mode, S_IFDIR | 0o755);
+    assert_eq!(st.nlink, 3);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), -1);
+    let mut dirents = [Dirent::default(); 4];
+    assert_eq!(getdents(fd, &mut dirents), 1);
+    assert_eq!(dirents[0].name(), "sub");
+    assert_eq!(dirents[0].kind, S_IFDIR);
+    assert_eq!(getdents(fd, &mut dirents), 0);
+    close(fd);
+
+    let fd = open(SUB, O_RDONLY) as usize;
+    assert_eq!(getdents(fd, &mut dirents), 1);
+    assert_eq!(dirents[0].name(), "40mkdir.txt");
+    assert_eq!(dirents[0].kind, S_IFREG);
+    close(fd);
+    let fd = open(FILE, O_RDONLY) as usize;
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.mode, S_IFREG | 0o644);
+    assert_eq!(getdents(fd, &mut dirents), -1);
+    close(fd);
+    println!("Test mkdir OK!");
+    0
+}
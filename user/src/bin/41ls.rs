@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, getdents, open, Dirent, Stat, O_RDONLY, S_IFDIR};
+
+/// 路径的最大字节数，包括结尾的 `\0`
+const MAX_PATH: usize = 256;
+/// 每次读取的目录项个数
+const BATCH: usize = 8;
+
+/// 列出以 `/` 结尾、长度为 `len` 的目录 `path` 中的文件，子目录递归列出，返回列出的文件个数
+fn list(path: &mut [u8; MAX_PATH], len: usize, depth: usize) -> usize {
+    path[len] = 0;
+    let fd = open(core::str::from_utf8(&path[..=len]).unwrap(), O_RDONLY);
+    if fd < 0 {
+        println!(
+            "ls: can not open {}",
+            core::str::from_utf8(&path[..len]).unwrap()
+        );
+        return 0;
+    }
+    let fd = fd as usize;
+    let mut count = 0;
+    let mut dirents = [Dirent::default(); BATCH];
+    loop {
+        let n = getdents(fd, &mut dirents);
+        if n <= 0 {
+            break;
+        }
+        for dirent in dirents[..n as usize].iter() {
+            let name = dirent.name();
+            count += 1;
+            let end = len + name.len();
+            // 留出结尾的 `/` 和 `\0`
+            if end + 2 > MAX_PATH {
+                println!("{:indent$}{} (path too long)", "", name, indent = depth * 2);
+                continue;
+            }
+            path[len..end].copy_from_slice(name.as_bytes());
+            if dirent.kind == S_IFDIR {
+                println!("{:indent$}{}/", "", name, indent = depth * 2);
+                path[end] = b'/';
+                count += list(path, end + 1, depth + 1);
+            } else {
+                path[end] = 0;
+                let mut st = Stat::default();
+                let file = open(core::str::from_utf8(&path[..=end]).unwrap(), O_RDONLY);
+                if file >= 0 {
+                    fstat(file as usize, &mut st);
+                    close(file as usize);
+                }
+                println!(
+                    "{:indent$}{:<24} {:>8}",
+                    "",
+                    name,
+                    st.size,
+                    indent = depth * 2
+                );
+            }
+        }
+    }
+    close(fd);
+    count
+}
+
+/// 递归列出文件系统中的所有文件和目录，以及普通文件的大小
+#[no_mangle]
+fn main() -> i32 {
+    let mut path = [0u8; MAX_PATH];
+    path[0] = b'/';
+    let count = list(&mut path, 1, 0);
+    println!("{} files", count);
+    0
+}
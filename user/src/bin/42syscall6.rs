@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::abi::SYSCALL_MMAP;
+use user_lib::{munmap, raw_syscall6, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const START: usize = 0x3000_0000;
+const PAGE_SIZE: usize = 4096;
+
+/// `mmap` 通过 `a0`~`a5` 传入 6 个参数，后 3 个参数分别出错时都被拒绝，
+/// 说明每个寄存器都原样到达了内核，包括高位
+#[no_mangle]
+fn main() -> i32 {
+    let valid = [
+        START,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        usize::MAX,
+        0,
+    ];
+    let invalid = [
+        (3, MAP_ANONYMOUS),
+        (3, 1 << 63 | MAP_PRIVATE | MAP_ANONYMOUS),
+        (4, 3),
+        (4, usize::MAX >> 1),
+        (5, PAGE_SIZE),
+        (5, 1 << 63),
+    ];
+    for (idx, arg) in invalid {
+        let mut args = valid;
+        args[idx] = arg;
+        assert_eq!(
+            raw_syscall6(SYSCALL_MMAP, args),
+            -1,
+            "a{} = {:#x}",
+            idx,
+            arg
+        );
+    }
+    assert_eq!(raw_syscall6(SYSCALL_MMAP, valid), 0);
+    let ptr = START as *mut usize;
+    unsafe {
+        ptr.write_volatile(0x5a5a_a5a5_5a5a_a5a5);
+        assert_eq!(ptr.read_volatile(), 0x5a5a_a5a5_5a5a_a5a5);
+    }
+    assert_eq!(munmap(START, PAGE_SIZE), 0);
+    println!("Test syscall6 OK!");
+    0
+}
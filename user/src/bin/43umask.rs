@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, make_tmp_dir, mkdir, open, umask, Stat, O_CREAT, O_RDONLY, O_WRONLY, S_IFDIR,
+    S_IFREG,
+};
+
+const FILE: &str = "/tmp/43umask.txt\0";
+const DIR: &str = "/tmp/43umask_dir\0";
+
+/// `path` 的文件模式
+fn mode(path: &str) -> u32 {
+    let fd = open(path, O_RDONLY);
+    assert!(fd > 0);
+    let mut st = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut st), 0);
+    close(fd as usize);
+    st.mode
+}
+
+/// 新建的文件和目录的权限位去掉了文件创建掩码中的位。再次运行时文件和目录已经存在，
+/// 它们是在同样的掩码下创建的
+#[no_mangle]
+fn main() -> i32 {
+    make_tmp_dir();
+    assert_eq!(umask(0o077), 0o022);
+    let fd = open(FILE, O_CREAT | O_WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    mkdir(DIR);
+    assert_eq!(mode(FILE), S_IFREG | 0o600);
+    assert_eq!(mode(DIR), S_IFDIR | 0o700);
+    // 掩码中超出权限位的部分被忽略
+    assert_eq!(umask(0o7777), 0o077);
+    assert_eq!(umask(0o022), 0o777);
+    println!("Test umask OK!");
+    0
+}
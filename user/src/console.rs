@@ -2,8 +2,18 @@ use core::fmt::Write;
 
 struct Stdout;
 
+pub const STDIN: usize = 0;
 pub const STDOUT: usize = 1;
 
+/// `ioctl` request: get the line discipline mode of the terminal
+pub const TTY_GET_MODE: usize = 1;
+/// `ioctl` request: set the line discipline mode of the terminal
+pub const TTY_SET_MODE: usize = 2;
+/// 行缓冲、回显并支持退格编辑
+pub const TTY_MODE_COOKED: usize = 0;
+/// 逐字符读取，不回显
+pub const TTY_MODE_RAW: usize = 1;
+
 impl core::fmt::Write for Stdout {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         crate::write(STDOUT, s.as_bytes());
@@ -11,10 +21,55 @@ impl core::fmt::Write for Stdout {
     }
 }
 
+/// read one byte from the standard input
+pub fn getchar() -> u8 {
+    let mut c = [0u8; 1];
+    crate::read(STDIN, &mut c);
+    c[0]
+}
+
+/// switch the terminal between raw and cooked mode
+pub fn set_raw_mode(raw: bool) -> isize {
+    let mode = if raw { TTY_MODE_RAW } else { TTY_MODE_COOKED };
+    crate::ioctl(STDIN, TTY_SET_MODE, mode)
+}
+
 pub fn print(args: core::fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// a fixed-point number `value / 10^digits`, displayed with exactly `digits` decimals
+///
+/// 只用整数格式化，不引入浮点数格式化的代码，例如 `Fixed(12345, 3)` 显示为 `12.345` ，
+/// 可以直接用在 `println!` 中。`digits` 不能超过 19
+#[derive(Copy, Clone, Debug)]
+pub struct Fixed(pub i64, pub u32);
+
+impl core::fmt::Display for Fixed {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let Fixed(value, digits) = *self;
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.unsigned_abs();
+        if digits == 0 {
+            return write!(f, "{}{}", sign, value);
+        }
+        let scale = 10u64.pow(digits);
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            value / scale,
+            value % scale,
+            width = digits as usize
+        )
+    }
+}
+
+/// print `x / 10^digits` with `digits` decimals, e.g. `print_fixed(1500, 3)` prints `1.500`
+pub fn print_fixed(x: i64, digits: u32) {
+    print(format_args!("{}", Fixed(x, digits)));
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -4,9 +4,76 @@
 
 #[macro_use]
 pub mod console;
+#[macro_use]
+pub mod log;
 
+mod atexit;
+pub mod bench;
 mod lang_items;
+pub mod perf;
 mod syscall;
+pub mod time;
+
+pub use abi;
+pub use abi::{
+    Dirent, IoStats, MemReport, RLimit, SignalAction, Stat, TaskInfo, TimeVal, EFAULT, ENOSYS,
+    MAP_ANONYMOUS, MAP_PRIVATE, NAME_MAX, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, PROT_EXEC,
+    PROT_READ, PROT_WRITE, PR_GET_NAME, PR_SET_NAME, RLIMIT_CPU, RLIM_INFINITY, SCHEDULER_MLFQ,
+    SCHEDULER_STRIDE, SCHED_IDLE, SCHED_INTERACTIVE, SCHED_NORMAL, S_IFCHR, S_IFDIR, S_IFREG,
+    TASK_NAME_LEN,
+};
+pub use atexit::{atexit, MAX_EXIT_HOOKS};
+
+/// 故障注入点：物理页帧分配
+pub const FAULT_SITE_FRAME: usize = 0;
+/// 故障注入点：内核堆分配
+pub const FAULT_SITE_HEAP: usize = 1;
+
+/// ELF note asking the kernel for stacks of other sizes than the defaults, see [`stack_size!`]
+///
+/// 布局与内核中 `loader/elf.rs` 解析的 note 一致
+#[repr(C, align(4))]
+pub struct StackSizeNote {
+    name_size: u32,
+    desc_size: u32,
+    note_type: u32,
+    name: [u8; 8],
+    desc: [u8; 16],
+}
+
+impl StackSizeNote {
+    pub const fn new(user: usize, kernel: usize) -> Self {
+        let user = (user as u64).to_le_bytes();
+        let kernel = (kernel as u64).to_le_bytes();
+        let mut desc = [0u8; 16];
+        let mut i = 0;
+        while i < 8 {
+            desc[i] = user[i];
+            desc[8 + i] = kernel[i];
+            i += 1;
+        }
+        Self {
+            name_size: 6,
+            desc_size: 16,
+            note_type: 1,
+            name: *b"rCore\0\0\0",
+            desc,
+        }
+    }
+}
+
+/// request a user stack of `$user` bytes and a kernel stack of `$kernel` bytes,
+/// 0 keeps the default size
+///
+/// 栈大小不能超过内核 `config.rs` 中的上限，否则应用无法加载
+#[macro_export]
+macro_rules! stack_size {
+    ($user: expr, $kernel: expr) => {
+        #[used]
+        #[link_section = ".note.rcore.stack"]
+        static STACK_SIZE_NOTE: $crate::StackSizeNote = $crate::StackSizeNote::new($user, $kernel);
+    };
+}
 
 #[no_mangle]
 #[link_section = ".text.entry"]
@@ -21,11 +88,63 @@ fn main() -> i32 {
     panic!("Cannot find main!");
 }
 
+/// open the file `path` (ending with `\0`) with `flags`, a combination of `O_*`,
+/// return its fd or -1
+pub fn open(path: &str, flags: u32) -> isize {
+    crate::syscall::sys_open(path, flags)
+}
+
+/// create the directory `path` (ending with `\0`), return -1 if it exists or its
+/// parent does not
+pub fn mkdir(path: &str) -> isize {
+    crate::syscall::sys_mkdir(path)
+}
+
+/// the directory for the scratch files of tests, created by [`make_tmp_dir`]
+pub const TMP_DIR: &str = "/tmp\0";
+
+/// create [`TMP_DIR`] unless it exists
+///
+/// 根目录中的普通文件都可能被当作应用，测试写入的文件因此都放在这个目录中
+pub fn make_tmp_dir() {
+    mkdir(TMP_DIR);
+}
+
+/// read the next entries of the directory opened as `fd` into `dirents`, return
+/// the number of entries read, 0 at the end of the directory
+pub fn getdents(fd: usize, dirents: &mut [Dirent]) -> isize {
+    crate::syscall::sys_getdents(fd, dirents)
+}
+
+pub fn close(fd: usize) -> isize {
+    crate::syscall::sys_close(fd)
+}
+
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    crate::syscall::sys_read(fd, buf)
+}
+
+pub fn ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    crate::syscall::sys_ioctl(fd, request, arg)
+}
+
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     crate::syscall::sys_write(fd, buf)
 }
 
+/// get the status of the file with `fd`, return -1 if it is not open
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    crate::syscall::sys_fstat(fd, st)
+}
+
+/// write the modified blocks of the file system back to the disk
+pub fn sync() -> isize {
+    crate::syscall::sys_sync()
+}
+
+/// run the handlers registered by [`atexit`] and exit with `exit_code`
 pub fn exit(exit_code: i32) -> isize {
+    atexit::run_exit_hooks();
     crate::syscall::sys_exit(exit_code)
 }
 
@@ -33,6 +152,192 @@ pub fn yield_() -> isize {
     crate::syscall::sys_yield()
 }
 
+/// yield to task `pid`, which runs next if it is ready, return -1 without yielding otherwise
+pub fn yield_to(pid: usize) -> isize {
+    crate::syscall::sys_yield_to(pid)
+}
+
+pub fn sched_setscheduler(pid: usize, policy: usize) -> isize {
+    crate::syscall::sys_sched_setscheduler(pid, policy)
+}
+
+pub fn sched_getscheduler(pid: usize) -> isize {
+    crate::syscall::sys_sched_getscheduler(pid)
+}
+
+pub fn set_priority(prio: isize) -> isize {
+    crate::syscall::sys_set_priority(prio)
+}
+
+pub fn get_priority() -> isize {
+    crate::syscall::sys_get_priority()
+}
+
+/// set the time slice of current app in timer ticks, return the effective length or -1 if `ticks` is 0
+pub fn set_time_slice(ticks: usize) -> isize {
+    crate::syscall::sys_set_time_slice(ticks)
+}
+
+/// time slice of current app in timer ticks
+pub fn get_time_slice() -> isize {
+    crate::syscall::sys_get_time_slice()
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    crate::syscall::sys_getrlimit(resource, rlim)
+}
+
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    crate::syscall::sys_setrlimit(resource, rlim)
+}
+
+pub fn umask(mask: u32) -> u32 {
+    crate::syscall::sys_umask(mask) as u32
+}
+
+pub fn getpid() -> isize {
+    syscall::sys_getpid()
+}
+
+/// pid of the parent, 0 if there is none
+pub fn getppid() -> isize {
+    syscall::sys_getppid()
+}
+
+/// name current task `name` (ending with `\0`) in `ps` and kernel messages,
+/// truncated to [`TASK_NAME_LEN`] bytes
+pub fn set_task_name(name: &str) -> isize {
+    syscall::sys_prctl(PR_SET_NAME, name.as_ptr() as usize)
+}
+
+/// write the name of current task into `buf`, ending with `\0`
+pub fn get_task_name(buf: &mut [u8; TASK_NAME_LEN + 1]) -> isize {
+    syscall::sys_prctl(PR_GET_NAME, buf.as_mut_ptr() as usize)
+}
+
+/// make the syscall `id` directly, for syscalls without a wrapper in this library
+pub fn raw_syscall(id: usize, args: [usize; 3]) -> isize {
+    syscall::syscall(id, args)
+}
+
+/// make the syscall `id` with all 6 arguments directly
+pub fn raw_syscall6(id: usize, args: [usize; 6]) -> isize {
+    syscall::syscall6(id, args)
+}
+
+/// run the app `path` (ending with `\0`) in a new task, return its pid or -1
+pub fn spawn(path: &str) -> isize {
+    syscall::sys_spawn(path)
+}
+
+/// wait for any child to exit and reap it, return its pid or -1 if there is no child
+pub fn wait(exit_code: &mut i32) -> isize {
+    waitpid(-1, exit_code)
+}
+
+/// wait for the child `pid` to exit and reap it, return `pid` or -1 if there is no such child
+pub fn waitpid(pid: isize, exit_code: &mut i32) -> isize {
+    loop {
+        match syscall::sys_waitpid(pid, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            exit_pid => return exit_pid,
+        }
+    }
+}
+
+/// id of the hart current app runs on
+pub fn getcpu() -> usize {
+    let (mut cpu, mut node) = (0u32, 0u32);
+    syscall::sys_getcpu(&mut cpu, &mut node);
+    cpu as usize
+}
+
+/// number of harts of the machine
+pub fn hart_count() -> usize {
+    syscall::sys_hart_count() as usize
+}
+
+/// print the fragmentation of the kernel's frame allocator and heap on the kernel console,
+/// and return it in `report`
+pub fn mem_report(report: &mut MemReport) -> isize {
+    syscall::sys_mem_report(report)
+}
+
 pub fn get_time() -> isize {
     syscall::sys_get_time()
 }
+
+pub fn get_time_of_day(ts: &mut TimeVal) -> isize {
+    syscall::sys_get_time_of_day(ts)
+}
+
+pub fn fault_inject(site: usize, every: usize, after: usize) -> isize {
+    syscall::sys_fault_inject(site, every, after)
+}
+
+pub fn get_maps(buf: &mut [u8]) -> isize {
+    syscall::sys_get_maps(buf)
+}
+
+/// map `len` bytes of zeroed memory at the page-aligned `start` with permission `prot`
+pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
+    syscall::sys_mmap(start, len, prot, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0)
+}
+
+/// unmap `len` bytes at `start`, which must be mapped by [`mmap`] as a whole
+pub fn munmap(start: usize, len: usize) -> isize {
+    syscall::sys_munmap(start, len)
+}
+
+/// move the end of the heap by `increment` bytes, return the old end
+pub fn sbrk(increment: isize) -> isize {
+    syscall::sys_sbrk(increment)
+}
+
+/// write the names of all apps into `buf`, one per line, return the length of the whole list
+pub fn get_app_names(buf: &mut [u8]) -> isize {
+    syscall::sys_get_app_names(buf)
+}
+
+/// whether there is an app named `name` (ending with `\0`)
+pub fn app_exists(name: &str) -> bool {
+    syscall::sys_find_app(name) >= 0
+}
+
+/// read the most recent kernel log kept in memory into `buf`, return the number of bytes read
+pub fn dmesg(buf: &mut [u8]) -> isize {
+    syscall::sys_dmesg(buf)
+}
+
+/// list every task on the kernel console, return the number of tasks
+pub fn ps() -> isize {
+    syscall::sys_ps()
+}
+
+/// get the reads and writes done through `fd` since it was opened, return -1 if it is not open
+pub fn fd_stat(fd: usize, stats: &mut IoStats) -> isize {
+    syscall::sys_fd_stat(fd, stats)
+}
+
+/// list the open fds of every task with their I/O statistics on the kernel console,
+/// return the number of open fds
+pub fn iostat() -> isize {
+    syscall::sys_iostat()
+}
+
+/// discard every write to the file system since boot, return -1 if the kernel
+/// is built without the `fs_overlay` feature or another task has a file of the file
+/// system open
+pub fn fs_rollback() -> isize {
+    syscall::sys_fs_rollback()
+}
+
+pub fn task_info(ti: &mut TaskInfo) -> isize {
+    syscall::sys_task_info(ti)
+}
+
+pub fn perf_read(counters: &mut perf::PerfCounters) -> isize {
+    syscall::sys_perf_read(counters)
+}
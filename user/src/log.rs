@@ -0,0 +1,118 @@
+//! Colored, leveled logging for user programs
+//!
+//! The default level is taken from the `LOG` environment variable at build
+//! time, the same way as the kernel, and can be changed by each program with
+//! [`set_level`].
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// log level, from the most severe to the most verbose
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[repr(usize)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    fn color_code(&self) -> u8 {
+        match self {
+            Level::Error => 31, // Red
+            Level::Warn => 93,  // BrightYellow
+            Level::Info => 34,  // Blue
+            Level::Debug => 32, // Green
+            Level::Trace => 90, // BrightBlack
+        }
+    }
+}
+
+/// 还没有从 `LOG` 环境变量中读取默认级别
+const LEVEL_UNSET: usize = usize::MAX;
+
+/// 当前允许输出的最详细的级别，`0` 表示关闭日志
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(LEVEL_UNSET);
+
+fn default_level() -> Option<Level> {
+    match option_env!("LOG") {
+        Some("error") => Some(Level::Error),
+        Some("warn") => Some(Level::Warn),
+        Some("info") => Some(Level::Info),
+        Some("debug") => Some(Level::Debug),
+        Some("trace") => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+/// Set the most verbose level to print, `None` turns logging off.
+pub fn set_level(level: Option<Level>) {
+    MAX_LEVEL.store(level.map_or(0, |level| level as usize), Ordering::Relaxed);
+}
+
+fn max_level() -> usize {
+    let max_level = MAX_LEVEL.load(Ordering::Relaxed);
+    if max_level != LEVEL_UNSET {
+        return max_level;
+    }
+    self::set_level(default_level());
+    MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn log(level: Level, args: core::fmt::Arguments) {
+    if level as usize > self::max_level() {
+        return;
+    }
+    crate::console::print(format_args!(
+        "\x1b[{}m[{}] {}\x1b[0m\n",
+        level.color_code(),
+        level.name(),
+        args
+    ));
+}
+
+#[macro_export]
+macro_rules! error {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::log::log($crate::log::Level::Error, format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! info {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::log::log($crate::log::Level::Trace, format_args!($fmt $(, $($arg)+)?));
+    }
+}
@@ -0,0 +1,44 @@
+//! Per-task event counters kept by the kernel
+//!
+//! [`stat()`] 类似 `perf stat` ，统计一段代码运行期间当前任务发生的各种事件。
+
+pub use abi::PerfCounters;
+
+use crate::time::Instant;
+
+/// 读取计数的系统调用本身带来的事件：第二次读取的系统调用，以及两次读取各自进出内核时的一次快表刷新
+const READ_OVERHEAD: PerfCounters = PerfCounters {
+    page_faults: 0,
+    syscalls: 1,
+    context_switches: 0,
+    tlb_flushes: 2,
+    preemptions: 0,
+    fault_fixups: 0,
+};
+
+/// the counters of the current task
+pub fn read() -> PerfCounters {
+    let mut counters = PerfCounters::default();
+    crate::perf_read(&mut counters);
+    counters
+}
+
+/// run `f`, print the events it caused and the time it took like `perf stat`,
+/// and return the events
+pub fn stat(name: &str, f: impl FnOnce()) -> PerfCounters {
+    let start = Instant::now();
+    let before = self::read();
+    f();
+    let after = self::read();
+    let elapsed = start.elapsed();
+    let delta = after - before - READ_OVERHEAD;
+    println!("\n Performance counter stats for '{}':\n", name);
+    println!("{:>12}      page-faults", delta.page_faults);
+    println!("{:>12}      syscalls", delta.syscalls);
+    println!("{:>12}      context-switches", delta.context_switches);
+    println!("{:>12}      tlb-flushes", delta.tlb_flushes);
+    println!("{:>12}      preemptions", delta.preemptions);
+    println!("{:>12}      fault-fixups", delta.fault_fixups);
+    println!("\n{:>12}us    time elapsed\n", elapsed.as_micros());
+    delta
+}
@@ -1,11 +1,13 @@
 use core::arch::asm;
 
-pub const SYSCALL_WRITE: usize = 64;
-pub const SYSCALL_EXIT: usize = 93;
-pub const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
+use abi::*;
 
-fn syscall(id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(id: usize, args: [usize; 3]) -> isize {
+    syscall6(id, [args[0], args[1], args[2], 0, 0, 0])
+}
+
+/// 通过 `a0`~`a5` 传递全部 6 个参数，用于参数多于 3 个的系统调用
+pub fn syscall6(id: usize, args: [usize; 6]) -> isize {
     let mut ret: isize;
     unsafe {
         asm!(
@@ -13,21 +15,97 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
             inlateout("x10") args[0] => ret,
             in("x11") args[1],
             in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
             in("x17") id
         );
     }
     ret
 }
 
+/// 功能：打开一个文件。
+/// 参数：`path` 为文件的路径，必须以 `\0` 结尾，从根目录开始，各级目录用 `/` 分隔；`flags` 为 `O_RDONLY` 、`O_WRONLY` 或 `O_RDWR` ，
+///      可以再加上 `O_CREAT` （文件不存在时创建）和 `O_TRUNC` （打开时清空文件）。
+/// 返回值：成功返回最小的未使用的文件描述符，文件不存在或无法创建时返回 -1 。
+///        目录只能以 `O_RDONLY` 打开，用于 `sys_getdents` 。
+/// syscall ID：56
+pub fn sys_open(path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+/// 功能：创建一个目录。
+/// 参数：`path` 为目录的路径，必须以 `\0` 结尾，从根目录开始，各级目录用 `/` 分隔。
+/// 返回值：成功返回 0 ，目录已经存在、上级目录不存在或路径不合法时返回 -1 。
+/// syscall ID：34
+pub fn sys_mkdir(path: &str) -> isize {
+    syscall(SYSCALL_MKDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+/// 功能：读取一个打开的目录中的目录项，每次调用从上次结束的位置继续。
+/// 参数：`fd` 为以 `O_RDONLY` 打开的目录的文件描述符；`dirents` 为保存目录项的数组。
+/// 返回值：返回读取的目录项个数，读完所有目录项后返回 0 ，`fd` 不是打开的目录时返回 -1 。
+/// syscall ID：61
+pub fn sys_getdents(fd: usize, dirents: &mut [Dirent]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS,
+        [fd, dirents.as_mut_ptr() as usize, dirents.len()],
+    )
+}
+
+/// 功能：关闭一个文件描述符。
+/// 参数：`fd` 为要关闭的文件描述符。
+/// 返回值：成功返回 0 ，`fd` 没有打开时返回 -1 。
+/// syscall ID：57
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+}
+
+/// 功能：从文件中读取一段内容到缓冲区。
+/// 参数：`fd` 表示待读取文件的文件描述符；
+///      `buffer` 表示内存中缓冲区的起始地址；
+/// 返回值：返回成功读取的长度，读到文件末尾时返回 0 ，`fd` 不可读时返回 -1 。
+///        标准输入在 cooked 模式下每次最多读取一行。
+/// syscall ID：63
+pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_READ,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+/// 功能：对设备进行控制，目前只支持终端。
+/// 参数：`fd` 表示终端的文件描述符；`request` 表示请求类型；`arg` 为请求的参数。
+/// 返回值：出错时返回 -1 ，否则返回值取决于请求类型。
+/// syscall ID：29
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, request, arg])
+}
+
 /// 功能：将内存中缓冲区中的数据写入文件。
 /// 参数：`fd` 表示待写入文件的文件描述符；
 ///      `buffer` 表示内存中缓冲区的起始地址；
-/// 返回值：返回成功写入的长度。
+/// 返回值：返回成功写入的长度，`fd` 不可写时返回 -1 。
 /// syscall ID：64
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+/// 功能：获取一个打开的文件的状态。
+/// 参数：`fd` 为文件描述符；`st` 为保存状态的结构体。
+/// 返回值：成功返回 0 ，`fd` 没有打开时返回 -1 。
+/// syscall ID：80
+pub fn sys_fstat(fd: usize, st: &mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, [fd, st as *mut Stat as usize, 0])
+}
+
+/// 功能：把文件系统中修改过的块写回磁盘。
+/// 返回值：总是返回 0 。
+/// syscall ID：81
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
 /// 功能：退出应用程序并将返回值告知批处理系统。
 /// 参数：`exit_code` 表示应用程序的返回值。
 /// 返回值：
@@ -36,10 +114,304 @@ pub fn sys_exit(exit_code: i32) -> isize {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0])
 }
 
+/// 功能：当前应用睡眠一段时间，期间不会被调度。
+/// 参数：`ms` 为睡眠的毫秒数，实际睡眠时间按时钟中断的周期向上取整。
+/// 返回值：总是返回 0 。
+/// syscall ID：101
+pub fn sys_sleep(ms: usize) -> isize {
+    syscall(SYSCALL_SLEEP, [ms, 0, 0])
+}
+
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
+pub fn sys_yield_to(pid: usize) -> isize {
+    syscall(SYSCALL_YIELD_TO, [pid, 0, 0])
+}
+
+/// 功能：设置应用的调度类别。有就绪的交互类应用时只调度交互类应用，其次是普通类应用，
+///      空闲类应用只在没有其他就绪应用时运行。
+/// 参数：`pid` 只能为 0 ，表示当前应用；`policy` 为 `SCHED_NORMAL`/`SCHED_INTERACTIVE`/`SCHED_IDLE` 之一。
+/// 返回值：成功返回 0 ，参数不合法时返回 -1 。
+/// syscall ID：119
+pub fn sys_sched_setscheduler(pid: usize, policy: usize) -> isize {
+    syscall(SYSCALL_SCHED_SETSCHEDULER, [pid, policy, 0])
+}
+
+/// 功能：获取应用的调度类别。
+/// 参数：`pid` 只能为 0 ，表示当前应用。
+/// 返回值：成功返回调度类别，参数不合法时返回 -1 。
+/// syscall ID：120
+pub fn sys_sched_getscheduler(pid: usize) -> isize {
+    syscall(SYSCALL_SCHED_GETSCHEDULER, [pid, 0, 0])
+}
+
+/// 功能：设置当前应用的优先级。
+/// 参数：`prio` 表示新的优先级，不能小于 2 ，过大的优先级会被截断。
+/// 返回值：成功时返回实际生效的优先级，`prio` 过小时返回 -1 。
+/// syscall ID：140
+pub fn sys_set_priority(prio: isize) -> isize {
+    syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
+}
+
+/// 功能：获取当前应用的优先级。
+/// 返回值：当前应用实际生效的优先级。
+/// syscall ID：141
+pub fn sys_get_priority() -> isize {
+    syscall(SYSCALL_GET_PRIORITY, [0, 0, 0])
+}
+
+/// 功能：设置当前应用的时间片长度，从下次被调度时开始生效。
+/// 参数：`ticks` 为时间片包含的时钟中断周期数，不能为 0 ，过长的时间片会被截断。
+/// 返回值：成功时返回实际生效的时间片长度，`ticks` 为 0 时返回 -1 。
+/// syscall ID：506
+pub fn sys_set_time_slice(ticks: usize) -> isize {
+    syscall(SYSCALL_SET_TIME_SLICE, [ticks, 0, 0])
+}
+
+/// 功能：获取当前应用的时间片长度。
+/// 返回值：时间片包含的时钟中断周期数。
+/// syscall ID：507
+pub fn sys_get_time_slice() -> isize {
+    syscall(SYSCALL_GET_TIME_SLICE, [0, 0, 0])
+}
+
+/// 功能：获取当前应用的资源限制。
+/// 参数：`resource` 表示资源的种类，目前只支持 CPU 时间 `RLIMIT_CPU`；
+///      `rlim` 用于保存软限制和硬限制。
+/// 返回值：成功返回 0 ，不支持的资源种类返回 -1 。
+/// syscall ID：163
+pub fn sys_getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    syscall(
+        SYSCALL_GETRLIMIT,
+        [resource, rlim as *mut RLimit as usize, 0],
+    )
+}
+
+/// 功能：设置当前应用的资源限制。CPU 时间超出软限制时内核输出警告，超出硬限制时杀死应用。
+/// 参数：`resource` 表示资源的种类，目前只支持 CPU 时间 `RLIMIT_CPU`；
+///      `rlim` 为新的软限制和硬限制。
+/// 返回值：成功返回 0 ；软限制超过硬限制、试图提高硬限制或资源种类不支持时返回 -1 。
+/// syscall ID：164
+pub fn sys_setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    syscall(
+        SYSCALL_SETRLIMIT,
+        [resource, rlim as *const RLimit as usize, 0],
+    )
+}
+
+/// 功能：设置当前应用的文件创建掩码。
+/// 参数：`mask` 表示新的掩码，只有低 9 位权限位有效。
+/// 返回值：原来的掩码。
+/// syscall ID：166
+pub fn sys_umask(mask: u32) -> isize {
+    syscall(SYSCALL_UMASK, [mask as usize, 0, 0])
+}
+
+/// 功能：对当前应用进行控制，目前只支持设置和获取任务名。
+/// 参数：`option` 为 `PR_SET_NAME` 或 `PR_GET_NAME` ；`arg2` 为名字缓冲区的地址。
+///      设置时名字以 `\0` 结尾，超过 15 字节的部分被截断，不可打印的字符被替换为 `?` ；
+///      获取时缓冲区至少为 16 字节，名字以 `\0` 结尾。
+/// 返回值：成功返回 0 ，`option` 不支持或名字不可访问时返回 -1 。
+/// syscall ID：167
+pub fn sys_prctl(option: usize, arg2: usize) -> isize {
+    syscall(SYSCALL_PRCTL, [option, arg2, 0])
+}
+
+/// 功能：获取当前应用所在的核以及 NUMA 节点。
+/// 参数：`cpu` 用于保存核的编号；`node` 用于保存 NUMA 节点的编号，目前总是 0 。
+/// 返回值：总是返回 0 。
+/// syscall ID：168
+pub fn sys_getcpu(cpu: &mut u32, node: &mut u32) -> isize {
+    syscall(
+        SYSCALL_GETCPU,
+        [cpu as *mut u32 as usize, node as *mut u32 as usize, 0],
+    )
+}
+
 pub fn sys_get_time() -> isize {
     syscall(SYSCALL_GET_TIME, [0, 0, 0])
 }
+
+/// 功能：获取当前时间。
+/// 参数：`ts` 用于保存以秒和微秒表示的当前时间。
+/// 返回值：以毫秒表示的当前时间。
+/// syscall ID：169
+pub fn sys_get_time_of_day(ts: &mut TimeVal) -> isize {
+    syscall(SYSCALL_GET_TIME, [ts as *mut TimeVal as usize, 0, 0])
+}
+
+/// 功能：获取当前应用的进程标识符。
+/// 返回值：当前应用的 pid 。
+/// syscall ID：172
+pub fn sys_getpid() -> isize {
+    syscall(SYSCALL_GETPID, [0, 0, 0])
+}
+
+/// 功能：获取父进程的进程标识符。
+/// 返回值：父进程的 pid ，没有父进程时返回 0 。
+/// syscall ID：173
+pub fn sys_getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0, 0, 0])
+}
+
+/// 功能：回收一个已经退出的子任务，并获取其退出码。
+/// 参数：`pid` 为要回收的子任务的 pid ，为 -1 时表示任意一个子任务；
+/// `exit_code` 为保存退出码的地址，为空指针时不保存。
+/// 返回值：成功返回子任务的 pid ；不存在符合条件的子任务时返回 -1 ；子任务都还未退出时返回 -2 。
+/// syscall ID：260
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+}
+
+/// 功能：调整当前应用的堆的结束地址（program break）。
+/// 参数：`increment` 为结束地址的增量，可以为负数，为 0 时只查询。
+/// 返回值：成功返回调整前的结束地址；堆会小于 0 字节或与其他映射重叠时返回 -1 。
+/// syscall ID：214
+pub fn sys_sbrk(increment: isize) -> isize {
+    syscall(SYSCALL_SBRK, [increment as usize, 0, 0])
+}
+
+/// 功能：取消 `mmap` 建立的映射。
+/// 参数：`start` 为起始地址，必须按页对齐；`len` 为长度，按页向上取整。
+/// 返回值：成功返回 0 ；参数不合法或该范围不恰好由若干次 `mmap` 的映射组成时返回 -1 。
+/// syscall ID：215
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [start, len, 0])
+}
+
+/// 功能：在当前应用的地址空间中映射一段新分配并清零的内存。
+/// 参数：`start` 为起始地址，必须按页对齐；`len` 为长度，按页向上取整；
+///      `prot` 为 `PROT_READ`/`PROT_WRITE`/`PROT_EXEC` 的组合，不能为 0 ，可写时必须可读；
+///      只支持匿名的私有映射，`flags` 为 `MAP_PRIVATE | MAP_ANONYMOUS` ，`fd` 为 -1 ，`offset` 为 0 。
+/// 返回值：成功返回 0 ；参数不合法或与已有的映射重叠时返回 -1 。
+/// syscall ID：222
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot, flags, fd, offset])
+}
+
+/// 功能：新建一个运行指定应用的任务，不复制当前应用的地址空间。
+/// 参数：`path` 为应用名，必须以 `\0` 结尾。
+/// 返回值：成功返回新任务的 pid ，应用不存在、无法加载或者物理页帧耗尽时返回 -1 。
+/// syscall ID：400
+pub fn sys_spawn(path: &str) -> isize {
+    syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
+}
+
+/// 功能：配置内核分配路径上的故障注入，只在内核启用 `fault_injection` 特性时有效。
+/// 参数：`site` 为注入点，0 表示物理页帧分配，1 表示内核堆分配；
+///      `every` 表示每 `every` 次分配失败一次，为 0 时不启用；
+///      `after` 表示成功 `after` 次之后所有分配都失败，为 `usize::MAX` 时不启用。
+/// 返回值：原配置下注入的失败次数，内核不支持或参数不合法时返回 -1 。
+/// syscall ID：500
+pub fn sys_fault_inject(site: usize, every: usize, after: usize) -> isize {
+    syscall(SYSCALL_FAULT_INJECT, [site, every, after])
+}
+
+/// 功能：获取当前应用地址空间的布局，每个逻辑段一行，格式为 `start-end perms kind` 。
+/// 参数：`buffer` 为保存结果的缓冲区，不够大时只写入开头的部分。
+/// 返回值：完整结果的长度。
+/// syscall ID：501
+pub fn sys_get_maps(buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GET_MAPS,
+        [buffer.as_mut_ptr() as usize, buffer.len(), 0],
+    )
+}
+
+/// 功能：获取所有应用的名字，每行一个。
+/// 参数：`buffer` 为保存结果的缓冲区，不够大时只写入开头的部分。
+/// 返回值：完整结果的长度。
+/// syscall ID：508
+pub fn sys_get_app_names(buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GET_APP_NAMES,
+        [buffer.as_mut_ptr() as usize, buffer.len(), 0],
+    )
+}
+
+/// 功能：按名字查找应用。
+/// 参数：`name` 为应用名，必须以 `\0` 结尾。
+/// 返回值：应用的编号，应用不存在时返回 -1 。
+/// syscall ID：509
+pub fn sys_find_app(name: &str) -> isize {
+    syscall(SYSCALL_FIND_APP, [name.as_ptr() as usize, 0, 0])
+}
+
+/// 功能：获取当前应用的调度信息，包括内核使用的调度器。
+/// 参数：`ti` 为保存调度信息的地址。
+/// 返回值：总是返回 0 。
+/// syscall ID：410
+pub fn sys_task_info(ti: &mut TaskInfo) -> isize {
+    syscall(SYSCALL_TASK_INFO, [ti as *mut TaskInfo as usize, 0, 0])
+}
+
+/// 功能：在内核控制台上列出所有任务，类似 `ps` 。
+/// 返回值：任务的个数。
+/// syscall ID：510
+pub fn sys_ps() -> isize {
+    syscall(SYSCALL_PS, [0, 0, 0])
+}
+
+/// 功能：获取通过一个文件描述符进行的读写的统计。
+/// 参数：`fd` 为文件描述符；`stats` 为保存统计的结构体。
+/// 返回值：成功返回 0 ，`fd` 没有打开时返回 -1 。
+/// syscall ID：511
+pub fn sys_fd_stat(fd: usize, stats: &mut IoStats) -> isize {
+    syscall(SYSCALL_FD_STAT, [fd, stats as *mut IoStats as usize, 0])
+}
+
+/// 功能：在内核控制台上列出所有任务打开的文件描述符及其读写统计，类似 `iostat` 。
+/// 返回值：打开的文件描述符的个数。
+/// syscall ID：512
+pub fn sys_iostat() -> isize {
+    syscall(SYSCALL_IOSTAT, [0, 0, 0])
+}
+
+/// 功能：丢弃启动以来对文件系统的所有写入，只在内核启用 `fs_overlay` 特性时有效。
+/// 返回值：成功返回 0 ，内核不支持或者其他任务打开着文件系统中的文件时返回 -1 。之前打开的文件不能再使用。
+/// syscall ID：513
+pub fn sys_fs_rollback() -> isize {
+    syscall(SYSCALL_FS_ROLLBACK, [0, 0, 0])
+}
+
+/// 功能：读取内核保存在内存中的最近的日志，类似 `dmesg` ，包括控制台上按级别过滤掉的记录。
+/// 参数：`buffer` 为保存日志的缓冲区，日志比它长时只读取最近的部分。
+/// 返回值：读取的字节数。
+/// syscall ID：515
+pub fn sys_dmesg(buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_DMESG,
+        [buffer.as_mut_ptr() as usize, buffer.len(), 0],
+    )
+}
+
+pub fn sys_perf_read(counters: &mut PerfCounters) -> isize {
+    syscall(
+        SYSCALL_PERF_READ,
+        [counters as *mut PerfCounters as usize, 0, 0],
+    )
+}
+
+/// 功能：获取机器上核的个数，内核目前只在其中一个核上运行。
+/// 返回值：核的个数。
+/// syscall ID：504
+pub fn sys_hart_count() -> isize {
+    syscall(SYSCALL_HART_COUNT, [0, 0, 0])
+}
+
+/// 功能：在内核控制台上输出空闲物理页帧和内核堆空闲块的碎片情况。
+/// 参数：`report` 不为空时，同样的统计结果也写入它指向的 `MemReport` 。
+/// 返回值：成功返回 0 ，`report` 不可写时返回 -EFAULT 。
+/// syscall ID：505
+pub fn sys_mem_report(report: *mut MemReport) -> isize {
+    syscall(SYSCALL_MEM_REPORT, [report as usize, 0, 0])
+}
@@ -0,0 +1,90 @@
+//! Monotonic time measurement and sleeping
+//!
+//! [`Instant`] 基于内核提供的微秒级时间，时间间隔使用 [`core::time::Duration`] 表示。
+
+use core::ops::{Add, Sub};
+
+pub use core::time::Duration;
+
+use crate::TimeVal;
+
+/// 每秒的微秒数
+const MICRO_PER_SEC: u64 = 1_000_000;
+
+/// time elapsed since boot in microseconds
+pub fn get_time_us() -> u64 {
+    let mut ts = TimeVal::default();
+    crate::get_time_of_day(&mut ts);
+    ts.sec * MICRO_PER_SEC + ts.usec
+}
+
+/// a measurement of the monotonic clock, with microsecond precision
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Instant {
+    /// 自启动以来经过的微秒数
+    us: u64,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Self { us: get_time_us() }
+    }
+
+    /// time elapsed from `earlier` to `self`, zero if `earlier` is later than `self`
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_micros(self.us.saturating_sub(earlier.us))
+    }
+
+    /// time elapsed since `self` was measured
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        let us = u64::try_from(duration.as_micros()).ok()?;
+        Some(Self {
+            us: self.us.checked_add(us)?,
+        })
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+/// give up the processor until `deadline` has passed
+///
+/// 内核按毫秒睡眠，不足一毫秒的部分向上取整
+pub fn sleep_until(deadline: Instant) {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let remaining_us = (deadline - now).as_micros() as u64;
+        crate::syscall::sys_sleep(remaining_us.div_ceil(1000) as usize);
+    }
+}
+
+/// give up the processor for at least `duration`
+pub fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration);
+}
+
+/// give up the processor for at least `ms` milliseconds
+pub fn sleep_ms(ms: u64) {
+    sleep(Duration::from_millis(ms));
+}